@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateResult } from "./DateResult";
+
+export type BlameRange = { commitId: string, startLine: number, numLines: number, originalStartLine: number, originalFile: string, author: string, authorEmail: string, date: DateResult | null, summary: string, boundary: boolean, };
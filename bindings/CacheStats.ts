@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type CacheStats = { name: string, numRepos: number, bytes: number, maxBytes: number, hits: bigint, misses: bigint, evictions: bigint, invalidations: bigint, };
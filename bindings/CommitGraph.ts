@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { GraphEdge } from "./GraphEdge";
+
+export type CommitGraph = { columns: Array<number>, edges: Array<GraphEdge>, numColumns: number, };
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SignatureFormat } from "./SignatureFormat";
+import type { SignatureStatus } from "./SignatureStatus";
+
+export type CommitSignature = { commitId: string, status: SignatureStatus, format: SignatureFormat | null, signer: string | null, key: string | null, };
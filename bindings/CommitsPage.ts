@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Commit } from "./Commit";
+import type { RefInfo } from "./RefInfo";
+
+export type CommitsPage = { startIndex: number, commits: Array<Commit>, refs: Array<RefInfo>, hasMore: boolean, };
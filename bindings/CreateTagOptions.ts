@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { TagKind } from "./TagKind";
+
+export type CreateTagOptions = { repoPath: string, name: string, commitId: string, kind: TagKind, message: string | null, };
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CommitInfo } from "./CommitInfo";
+import type { Patch } from "./Patch";
+
+export type FileHistoryEntry = { commit: CommitInfo, patch: Patch, };
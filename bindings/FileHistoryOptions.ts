@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type FileHistoryOptions = { repoPath: string, filePath: string, commitId: string | null, maxCount: number | null, };
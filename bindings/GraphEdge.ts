@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type GraphEdge = { childIndex: number, parentIndex: number | null, childColumn: number, lane: number, parentColumn: number | null, mergeParent: boolean, throughGap: boolean, };
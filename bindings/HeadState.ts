@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type HeadState = { "branch": { name: string, commitId: string, } } | { "detached": { commitId: string, } } | { "unborn": { name: string, } } | { "rebasing": { branch: string | null, onto: string | null, commitId: string | null, } } | { "bisecting": { originalHead: string, commitId: string | null, } };
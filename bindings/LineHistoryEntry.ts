@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CommitInfo } from "./CommitInfo";
+import type { Hunk } from "./Hunk";
+
+export type LineHistoryEntry = { commit: CommitInfo, oldFile: string | null, newFile: string, hunks: Array<Hunk>, };
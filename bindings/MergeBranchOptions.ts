@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MergeMode } from "./MergeMode";
+import type { RefInfo } from "./RefInfo";
+
+export type MergeBranchOptions = { repoPath: string, refInfo: RefInfo, mode: MergeMode, };
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MergeMessage } from "./MergeMessage";
+
+export type MergePreview = { alreadyMerged: boolean, canFastForward: boolean, conflictedFiles: Array<string>, messages: Array<MergeMessage>, };
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RebaseTodoLine } from "./RebaseTodoLine";
+
+export type RebaseState = { headName: string, onto: string, currentStep: number, totalSteps: number, done: Array<RebaseTodoLine>, remaining: Array<RebaseTodoLine>, stoppedCommitId: string | null, interactive: boolean, };
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RebaseOp } from "./RebaseOp";
+
+export type RebaseTodoItem = { commitId: string, op: RebaseOp, message: string | null, };
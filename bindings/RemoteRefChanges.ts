@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RefInfo } from "./RefInfo";
+
+export type RemoteRefChanges = { added: Array<RefInfo>, removed: Array<RefInfo>, updated: Array<RefInfo>, };
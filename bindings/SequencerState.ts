@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RebaseTodoLine } from "./RebaseTodoLine";
+import type { SequencerOperation } from "./SequencerOperation";
+
+export type SequencerState = { operation: SequencerOperation, currentCommitId: string | null, remaining: Array<RebaseTodoLine>, originalHead: string | null, };
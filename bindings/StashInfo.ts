@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateResult } from "./DateResult";
+
+export type StashInfo = { stashId: string, commitId: string, baseCommitId: string, branch: string | null, message: string, date: DateResult, hasUntracked: boolean, };
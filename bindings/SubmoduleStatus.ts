@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SubmoduleState } from "./SubmoduleState";
+
+export type SubmoduleStatus = { path: string, state: SubmoduleState, recordedCommitId: string, checkedOutCommitId: string | null, describe: string | null, dirty: boolean, };
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateResult } from "./DateResult";
+import type { TagSignature } from "./TagSignature";
+
+export type TagInfo = { name: string, fullName: string, commitId: string, annotated: boolean, tagger: string | null, taggerEmail: string | null, date: DateResult | null, message: string | null, signature: TagSignature, };
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type WorktreeInfo = { path: string, headCommitId: string | null, branch: string | null, bare: boolean, locked: boolean, lockReason: string | null, prunable: boolean, current: boolean, };
@@ -0,0 +1,342 @@
+use std::process::Command;
+
+use serde::Deserialize;
+use ts_rs::TS;
+
+use crate::f;
+use crate::git::git_settings::GIT_PATH;
+use crate::git::git_types::{RefInfo, RefLocation, RefType};
+use crate::git::queries::commit_calcs::{find_commit_ancestors, get_commit_map_cloned};
+use crate::git::queries::refs::head_info::calc_head_info;
+use crate::git::queries::refs::ref_name::check_short_ref_name;
+use crate::git::run_git_action::{
+  run_git_action, run_git_action_with_vec, RunGitActionOptions,
+};
+use crate::git::store::STORE;
+use crate::server::git_request::ReqOptions;
+use crate::server::request_util::{ES, R};
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct CreateBranchOptions {
+  pub repo_path: String,
+  pub name: String,
+  // Commit id or ref name.
+  pub start_point: String,
+  pub checkout: bool,
+}
+
+pub fn create_branch(options: &CreateBranchOptions) -> R<u32> {
+  let CreateBranchOptions {
+    repo_path,
+    name,
+    start_point,
+    checkout,
+  } = options;
+
+  check_new_branch_name("create_branch", repo_path, name)?;
+
+  // Git would take it as an option.
+  if start_point.starts_with('-') {
+    return Err(ES::from("create_branch: Start point can't start with '-'."));
+  }
+
+  let args = if *checkout {
+    vec!["checkout", "-b", name, start_point]
+  } else {
+    vec!["branch", name, start_point]
+  };
+
+  Ok(run_git_action(RunGitActionOptions {
+    repo_path,
+    commands: [args],
+  }))
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct RenameBranchOptions {
+  pub repo_path: String,
+  pub old_name: String,
+  pub new_name: String,
+  // Also push the new name to the upstream remote and delete the old one there.
+  pub rename_remote: bool,
+}
+
+pub fn rename_branch(options: &RenameBranchOptions) -> R<u32> {
+  let RenameBranchOptions {
+    repo_path,
+    old_name,
+    new_name,
+    rename_remote,
+  } = options;
+
+  check_new_branch_name("rename_branch", repo_path, new_name)?;
+
+  let mut commands = vec![vec![
+    String::from("branch"),
+    String::from("-m"),
+    old_name.clone(),
+    new_name.clone(),
+  ]];
+
+  if *rename_remote {
+    let refs = get_refs("rename_branch", repo_path)?;
+    let local = find_local_branch(&refs, old_name).ok_or(ES::from(&f!(
+      "rename_branch: Branch {} not found.",
+      old_name
+    )))?;
+    let remote = find_sibling(local, &refs).ok_or(ES::from(&f!(
+      "rename_branch: Branch {} doesn't have a remote.",
+      old_name
+    )))?;
+    let remote_name = remote
+      .remote_name
+      .clone()
+      .ok_or(ES::from("rename_branch: Remote name missing."))?;
+
+    commands.push(vec![
+      String::from("push"),
+      String::from("--set-upstream"),
+      remote_name.clone(),
+      new_name.clone(),
+    ]);
+    commands.push(vec![
+      String::from("push"),
+      remote_name,
+      String::from("--delete"),
+      remote.short_name.clone(),
+    ]);
+  }
+
+  Ok(run_git_action_with_vec(repo_path, commands))
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct BranchOptions {
+  pub repo_path: String,
+  pub name: String,
+}
+
+/*
+Same idea as git's "not fully merged" check: a branch is merged if its commit is
+reachable from HEAD or from its own upstream. Uses the cached commits, so the result
+is only as fresh as the last load_commits_and_refs.
+ */
+pub fn is_branch_merged(options: &BranchOptions) -> R<bool> {
+  let BranchOptions { repo_path, name } = options;
+
+  let (commits, refs) = STORE
+    .get_commits_and_refs(repo_path)
+    .ok_or(ES::from("is_branch_merged: Commits not found."))?;
+  let commit_map = get_commit_map_cloned(&commits);
+
+  let branch = find_local_branch(&refs, name).ok_or(ES::from(&f!(
+    "is_branch_merged: Branch {} not found.",
+    name
+  )))?;
+
+  let head = calc_head_info(&ReqOptions {
+    repo_path: repo_path.clone(),
+  })?
+  .commit;
+
+  let mut targets = vec![head.id];
+  if let Some(remote) = find_sibling(branch, &refs) {
+    targets.push(remote.commit_id.clone());
+  }
+
+  for id in targets {
+    if id == branch.commit_id {
+      return Ok(true);
+    }
+
+    let merged = match (
+      commit_map.get(&id),
+      commit_map.contains_key(&branch.commit_id),
+    ) {
+      (Some(c), true) => {
+        find_commit_ancestors(c, &commit_map).contains(branch.commit_id.as_str())
+      }
+      // One of them is older than the commits we've loaded.
+      _ => is_ancestor_with_git(repo_path, &branch.commit_id, &id)?,
+    };
+
+    if merged {
+      return Ok(true);
+    }
+  }
+
+  Ok(false)
+}
+
+fn is_ancestor_with_git(repo_path: &str, ancestor: &str, commit: &str) -> R<bool> {
+  let status = Command::new(GIT_PATH.as_path())
+    .args(["merge-base", "--is-ancestor", ancestor, commit])
+    .current_dir(repo_path)
+    .status()?;
+
+  // 1 means it isn't an ancestor. Anything else is an error, like a missing commit.
+  match status.code() {
+    Some(0) => Ok(true),
+    Some(1) => Ok(false),
+    _ => Err(ES::from("is_branch_merged: Failed to compare commits.")),
+  }
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct DeleteBranchOptions {
+  pub repo_path: String,
+  pub name: String,
+  // Delete even if the branch isn't fully merged.
+  pub force: bool,
+  pub delete_remote: bool,
+}
+
+pub fn delete_branch(options: &DeleteBranchOptions) -> R<u32> {
+  let DeleteBranchOptions {
+    repo_path,
+    name,
+    force,
+    delete_remote,
+  } = options;
+
+  if !force
+    && !is_branch_merged(&BranchOptions {
+      repo_path: repo_path.clone(),
+      name: name.clone(),
+    })?
+  {
+    return Err(ES::from(&f!(
+      "delete_branch: Branch {} is not fully merged.",
+      name
+    )));
+  }
+
+  // -D because we've already done the merged check.
+  let mut commands = vec![vec![
+    String::from("branch"),
+    String::from("-D"),
+    name.clone(),
+  ]];
+
+  if *delete_remote {
+    let refs = get_refs("delete_branch", repo_path)?;
+
+    if let Some(remote) =
+      find_local_branch(&refs, name).and_then(|b| find_sibling(b, &refs))
+    {
+      if let Some(remote_name) = &remote.remote_name {
+        commands.push(vec![
+          String::from("push"),
+          remote_name.clone(),
+          String::from("--delete"),
+          remote.short_name.clone(),
+        ]);
+      }
+    }
+  }
+
+  Ok(run_git_action_with_vec(repo_path, commands))
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct SetUpstreamOptions {
+  pub repo_path: String,
+  pub branch: String,
+  // E.g. "origin/main". None unsets the upstream.
+  pub upstream: Option<String>,
+}
+
+pub fn set_upstream(options: &SetUpstreamOptions) -> u32 {
+  let SetUpstreamOptions {
+    repo_path,
+    branch,
+    upstream,
+  } = options;
+
+  let args = match upstream {
+    Some(upstream) => vec![
+      String::from("branch"),
+      f!("--set-upstream-to={}", upstream),
+      branch.clone(),
+    ],
+    None => vec![
+      String::from("branch"),
+      String::from("--unset-upstream"),
+      branch.clone(),
+    ],
+  };
+
+  run_git_action_with_vec(repo_path, vec![args])
+}
+
+fn check_new_branch_name(fn_name: &str, repo_path: &String, name: &str) -> R<()> {
+  if let Err(ES::Text(e)) = check_short_ref_name(name) {
+    return Err(ES::from(&f!("{}: {}", fn_name, e)));
+  }
+
+  if let Some((_, refs)) = STORE.get_commits_and_refs(repo_path) {
+    if find_local_branch(&refs, name).is_some() {
+      return Err(ES::from(&f!(
+        "{}: Branch {} already exists.",
+        fn_name,
+        name
+      )));
+    }
+  }
+
+  Ok(())
+}
+
+fn get_refs(fn_name: &str, repo_path: &String) -> R<Vec<RefInfo>> {
+  let (_, refs) = STORE
+    .get_commits_and_refs(repo_path)
+    .ok_or(ES::from(&f!("{}: Refs not found.", fn_name)))?;
+
+  Ok(refs)
+}
+
+fn find_local_branch<'a>(refs: &'a [RefInfo], name: &str) -> Option<&'a RefInfo> {
+  refs.iter().find(|r| {
+    r.ref_type == RefType::Branch
+      && r.location == RefLocation::Local
+      && r.short_name == name
+  })
+}
+
+fn find_sibling<'a>(ri: &RefInfo, refs: &'a [RefInfo]) -> Option<&'a RefInfo> {
+  if ri.sibling_id.is_empty() {
+    return None;
+  }
+  refs.iter().find(|r| r.id == ri.sibling_id)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::git::actions::branch::is_ancestor_with_git;
+  use crate::util::test_util::TempDir;
+
+  #[test]
+  fn test_is_ancestor_with_git() {
+    let repo = TempDir::with_repo("is_ancestor");
+    let repo_path = repo.repo_path();
+
+    repo.commit("c1", 1_600_001_000);
+    let c1 = repo.git(&["rev-parse", "HEAD"]).trim().to_string();
+    repo.commit("c2", 1_600_002_000);
+    let c2 = repo.git(&["rev-parse", "HEAD"]).trim().to_string();
+
+    assert!(is_ancestor_with_git(&repo_path, &c1, &c2).unwrap());
+    assert!(!is_ancestor_with_git(&repo_path, &c2, &c1).unwrap());
+    assert!(is_ancestor_with_git(&repo_path, &"0".repeat(40), &c2).is_err());
+  }
+}
@@ -0,0 +1,270 @@
+use ahash::AHashMap;
+use serde::Deserialize;
+use ts_rs::TS;
+
+use crate::f;
+use crate::git::actions::rebase::no_editor_env;
+use crate::git::git_types::Commit;
+use crate::git::queries::commit_calcs::get_commit_map_cloned;
+use crate::git::run_git_action::{run_git_action_with_env, run_git_action_with_vec};
+use crate::git::store::STORE;
+use crate::server::git_request::ReqOptions;
+use crate::server::request_util::{ES, R};
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct CherryPickOptions {
+  pub repo_path: String,
+  // Any order. These get applied oldest first.
+  pub commit_ids: Vec<String>,
+  // Adds "(cherry picked from commit ...)" to the message.
+  pub record_origin: bool,
+  // 1 based parent number to diff merge commits against. Required for merges.
+  pub mainline: Option<u32>,
+  pub no_commit: bool,
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct RevertOptions {
+  pub repo_path: String,
+  // Any order. These get reverted newest first.
+  pub commit_ids: Vec<String>,
+  pub mainline: Option<u32>,
+  pub no_commit: bool,
+}
+
+pub fn cherry_pick_commits(options: &CherryPickOptions) -> R<u32> {
+  let CherryPickOptions {
+    repo_path,
+    commit_ids,
+    record_origin,
+    mainline,
+    no_commit,
+  } = options;
+
+  let mut args = vec![String::from("cherry-pick")];
+
+  if *record_origin {
+    args.push(String::from("-x"));
+  }
+
+  let ids = sequence_args(
+    "cherry_pick_commits",
+    repo_path,
+    commit_ids,
+    *mainline,
+    *no_commit,
+    true,
+    &mut args,
+  )?;
+  args.extend(ids);
+
+  Ok(run_git_action_with_vec(repo_path, vec![args]))
+}
+
+pub fn revert_commits(options: &RevertOptions) -> R<u32> {
+  let RevertOptions {
+    repo_path,
+    commit_ids,
+    mainline,
+    no_commit,
+  } = options;
+
+  let mut args = vec![String::from("revert"), String::from("--no-edit")];
+
+  let ids = sequence_args(
+    "revert_commits",
+    repo_path,
+    commit_ids,
+    *mainline,
+    *no_commit,
+    false,
+    &mut args,
+  )?;
+  args.extend(ids);
+
+  Ok(run_git_action_with_vec(repo_path, vec![args]))
+}
+
+pub fn cherry_pick_continue(options: &ReqOptions) -> u32 {
+  run_sequencer_command(&options.repo_path, "cherry-pick", "--continue")
+}
+
+pub fn cherry_pick_abort(options: &ReqOptions) -> u32 {
+  run_sequencer_command(&options.repo_path, "cherry-pick", "--abort")
+}
+
+pub fn revert_continue(options: &ReqOptions) -> u32 {
+  run_sequencer_command(&options.repo_path, "revert", "--continue")
+}
+
+pub fn revert_abort(options: &ReqOptions) -> u32 {
+  run_sequencer_command(&options.repo_path, "revert", "--abort")
+}
+
+// --continue opens an editor for the message of a commit that stopped with a conflict.
+fn run_sequencer_command(repo_path: &str, command: &str, flag: &str) -> u32 {
+  run_git_action_with_env(
+    repo_path,
+    vec![vec![command.to_string(), flag.to_string()]],
+    no_editor_env(),
+  )
+}
+
+// Pushes the shared flags onto args and returns the commit ids in the order git should
+// apply them.
+fn sequence_args(
+  fn_name: &str,
+  repo_path: &str,
+  commit_ids: &[String],
+  mainline: Option<u32>,
+  no_commit: bool,
+  oldest_first: bool,
+  args: &mut Vec<String>,
+) -> R<Vec<String>> {
+  if commit_ids.is_empty() {
+    return Err(ES::from(&f!("{}: No commits given.", fn_name)));
+  }
+
+  let (commits, _) = STORE
+    .get_commits_and_refs(&repo_path.to_string())
+    .ok_or(ES::from(&f!("{}: Commits not found.", fn_name)))?;
+  let commit_map = get_commit_map_cloned(&commits);
+
+  let commits = order_commits(fn_name, commit_ids, &commit_map, oldest_first)?;
+
+  if commits.iter().any(|c| c.is_merge) {
+    let mainline = mainline.ok_or(ES::from(&f!(
+      "{}: A parent number is required for merge commits.",
+      fn_name
+    )))?;
+
+    // Older versions of git refuse "-m" for commits that aren't merges.
+    if commits.iter().any(|c| !c.is_merge) && !STORE.get_git_version().at_least(2, 21) {
+      return Err(ES::from(&f!(
+        "{}: Can't mix merge and non-merge commits with this version of git.",
+        fn_name
+      )));
+    }
+
+    if let Some(c) = commits
+      .iter()
+      .find(|c| c.is_merge && (mainline == 0 || c.parent_ids.len() < mainline as usize))
+    {
+      return Err(ES::from(&f!(
+        "{}: Commit {} doesn't have parent {}.",
+        fn_name,
+        c.id,
+        mainline
+      )));
+    }
+
+    args.push(String::from("-m"));
+    args.push(mainline.to_string());
+  }
+
+  if no_commit {
+    args.push(String::from("--no-commit"));
+  }
+
+  Ok(commits.into_iter().map(|c| c.id.clone()).collect())
+}
+
+// Commits have a higher index the older they are.
+fn order_commits<'a>(
+  fn_name: &str,
+  commit_ids: &[String],
+  commit_map: &'a AHashMap<String, Commit>,
+  oldest_first: bool,
+) -> R<Vec<&'a Commit>> {
+  let mut commits = commit_ids
+    .iter()
+    .map(|id| {
+      commit_map
+        .get(id)
+        .ok_or(ES::from(&f!("{}: Commit {} isn't loaded.", fn_name, id)))
+    })
+    .collect::<R<Vec<&Commit>>>()?;
+
+  commits.sort_by_key(|c| c.index);
+  commits.dedup_by_key(|c| c.index);
+
+  if oldest_first {
+    commits.reverse();
+  }
+
+  Ok(commits)
+}
+
+#[cfg(test)]
+mod tests {
+  use ahash::AHashMap;
+
+  use crate::git::actions::cherry_pick::{order_commits, sequence_args};
+  use crate::git::git_types::{make_commit, Commit};
+  use crate::git::store::STORE;
+  use crate::util::test_util::{ID_A as A, ID_B as B, ID_C as C};
+
+  const D: &str = "dddddddddddddddddddddddddddddddddddddddd";
+
+  // Newest first, like the loaded commits. D merges B into C.
+  fn make_commits() -> Vec<Commit> {
+    [(D, vec![C, B]), (C, vec![A]), (B, vec![A]), (A, vec![])]
+      .into_iter()
+      .enumerate()
+      .map(|(index, (id, parents))| Commit {
+        index,
+        ..make_commit(id, &parents)
+      })
+      .collect()
+  }
+
+  fn ids(commits: &[&Commit]) -> Vec<String> {
+    commits.iter().map(|c| c.id.clone()).collect()
+  }
+
+  #[test]
+  fn test_order_commits() {
+    let map: AHashMap<String, Commit> = make_commits()
+      .into_iter()
+      .map(|c| (c.id.clone(), c))
+      .collect();
+    let given = vec![B.to_string(), D.to_string(), A.to_string(), B.to_string()];
+
+    let oldest_first = order_commits("test", &given, &map, true).unwrap();
+    assert_eq!(ids(&oldest_first), vec![A, B, D]);
+
+    let newest_first = order_commits("test", &given, &map, false).unwrap();
+    assert_eq!(ids(&newest_first), vec![D, B, A]);
+
+    let missing = vec!["0".repeat(40)];
+    assert!(order_commits("test", &missing, &map, true).is_err());
+  }
+
+  #[test]
+  fn test_mainline() {
+    let repo_path = String::from("/gitfiend_test_cherry_pick_mainline");
+    STORE.insert_commits(&repo_path, &make_commits(), &Vec::new());
+
+    let run = |ids: &[&str], mainline: Option<u32>| {
+      let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+      let mut args = Vec::new();
+
+      sequence_args("test", &repo_path, &ids, mainline, false, true, &mut args)
+        .map(|_| args)
+    };
+
+    assert_eq!(run(&[C], None).unwrap(), Vec::<String>::new());
+    assert_eq!(run(&[D], Some(2)).unwrap(), vec!["-m", "2"]);
+
+    // A merge needs a parent, and it has to exist.
+    assert!(run(&[D], None).is_err());
+    assert!(run(&[D], Some(0)).is_err());
+    assert!(run(&[D], Some(3)).is_err());
+
+    assert!(run(&[], None).is_err());
+  }
+}
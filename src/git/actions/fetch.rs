@@ -1,5 +1,18 @@
-use crate::git::run_git_action::{run_git_action, RunGitActionOptions};
+use ahash::AHashMap;
+use serde::Deserialize;
+use ts_rs::TS;
+
+use crate::git::git_types::RefInfo;
+use crate::git::queries::refs::remote_refs::{
+  diff_remote_refs, load_remote_refs, RemoteRefChanges,
+};
+use crate::git::run_git_action::{
+  run_git_action, run_git_action_with_vec, PollOptions, RunGitActionOptions,
+};
+use crate::global;
 use crate::server::git_request::ReqOptions;
+use crate::server::request_util::{ES, R};
+use crate::util::global::Global;
 
 pub fn fetch_all(options: &ReqOptions) -> u32 {
   run_git_action(RunGitActionOptions {
@@ -7,3 +20,122 @@ pub fn fetch_all(options: &ReqOptions) -> u32 {
     commands: [vec!["fetch", "--all", "--prune"]],
   })
 }
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct FetchRemoteOptions {
+  pub repo_path: String,
+  pub remote: String,
+  // E.g. "main" or "refs/heads/a:refs/remotes/origin/a". Uses the remote's configured
+  // refspecs if None.
+  pub refspec: Option<String>,
+  pub prune: bool,
+}
+
+#[derive(Clone)]
+struct FetchSnapshot {
+  repo_path: String,
+  remote: String,
+  refs: Vec<RefInfo>,
+}
+
+// Remote refs from before each fetch_remote, keyed by action id.
+static FETCH_SNAPSHOTS: Global<AHashMap<u32, FetchSnapshot>> = global!(AHashMap::new());
+// Snapshots nobody loads the changes for, e.g. after a failed fetch, are dropped oldest
+// first past this.
+const MAX_FETCH_SNAPSHOTS: usize = 20;
+
+pub fn fetch_remote(options: &FetchRemoteOptions) -> R<u32> {
+  let FetchRemoteOptions {
+    repo_path,
+    remote,
+    refspec,
+    prune,
+  } = options;
+
+  // Git would take these as options, e.g. "--upload-pack=<cmd>".
+  if remote.starts_with('-') || refspec.as_ref().is_some_and(|r| r.starts_with('-')) {
+    return Err(ES::from(
+      "fetch_remote: Remote and refspec can't start with '-'.",
+    ));
+  }
+
+  let before = load_remote_refs(repo_path, remote)?;
+
+  let mut args = vec![String::from("fetch")];
+  if *prune {
+    args.push(String::from("--prune"));
+  }
+  args.push(String::from("--"));
+  args.push(remote.clone());
+  if let Some(refspec) = refspec {
+    args.push(refspec.clone());
+  }
+
+  let id = run_git_action_with_vec(repo_path, vec![args]);
+
+  add_snapshot(
+    id,
+    FetchSnapshot {
+      repo_path: repo_path.clone(),
+      remote: remote.clone(),
+      refs: before,
+    },
+  );
+
+  Ok(id)
+}
+
+fn add_snapshot(id: u32, snapshot: FetchSnapshot) {
+  if let Ok(mut snapshots) = FETCH_SNAPSHOTS.data.write() {
+    snapshots.insert(id, snapshot);
+
+    while snapshots.len() > MAX_FETCH_SNAPSHOTS {
+      // Action ids go up, so the smallest is the oldest.
+      let Some(oldest) = snapshots.keys().min().copied() else {
+        break;
+      };
+      snapshots.remove(&oldest);
+    }
+  }
+}
+
+// Call once the fetch_remote action is done. Can only be called once per action.
+pub fn load_fetch_changes(options: &PollOptions) -> R<RemoteRefChanges> {
+  let snapshot = FETCH_SNAPSHOTS
+    .remove(&options.action_id)
+    .ok_or(ES::from("load_fetch_changes: Fetch not found."))?;
+
+  let after = load_remote_refs(&snapshot.repo_path, &snapshot.remote)?;
+
+  Ok(diff_remote_refs(&snapshot.refs, &after))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::git::actions::fetch::{
+    add_snapshot, FetchSnapshot, FETCH_SNAPSHOTS, MAX_FETCH_SNAPSHOTS,
+  };
+
+  #[test]
+  fn test_snapshots_are_capped() {
+    for id in 1..=(MAX_FETCH_SNAPSHOTS as u32 + 5) {
+      add_snapshot(
+        id,
+        FetchSnapshot {
+          repo_path: String::from("/repo"),
+          remote: String::from("origin"),
+          refs: Vec::new(),
+        },
+      );
+    }
+
+    assert_eq!(
+      FETCH_SNAPSHOTS.data.read().unwrap().len(),
+      MAX_FETCH_SNAPSHOTS
+    );
+    assert!(FETCH_SNAPSHOTS.get_by_key(&5).is_none());
+    assert!(FETCH_SNAPSHOTS.get_by_key(&6).is_some());
+  }
+}
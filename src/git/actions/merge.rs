@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::git::git_types::RefInfo;
+use crate::git::run_git_action::{run_git_action, RunGitActionOptions};
+use crate::server::git_request::ReqOptions;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum MergeMode {
+  // Fast-forward if possible, otherwise create a merge commit.
+  Default,
+  FastForwardOnly,
+  NoFastForward,
+  // Stages the combined changes without committing or recording a merge.
+  Squash,
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct MergeBranchOptions {
+  pub repo_path: String,
+  pub ref_info: RefInfo,
+  pub mode: MergeMode,
+}
+
+pub fn merge_branch(options: &MergeBranchOptions) -> u32 {
+  let MergeBranchOptions {
+    repo_path,
+    ref_info,
+    mode,
+  } = options;
+
+  let mode_arg = match mode {
+    MergeMode::Default => "--ff",
+    MergeMode::FastForwardOnly => "--ff-only",
+    MergeMode::NoFastForward => "--no-ff",
+    MergeMode::Squash => "--squash",
+  };
+
+  // The full name avoids ambiguity between e.g. a tag and a branch with the same name.
+  // Git still writes "Merge branch 'x'" in the message.
+  run_git_action(RunGitActionOptions {
+    repo_path,
+    commands: [vec!["merge", mode_arg, "--no-edit", &ref_info.full_name]],
+  })
+}
+
+pub fn merge_abort(options: &ReqOptions) -> u32 {
+  run_git_action(RunGitActionOptions {
+    repo_path: &options.repo_path,
+    commands: [vec!["merge", "--abort"]],
+  })
+}
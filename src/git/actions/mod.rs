@@ -1,8 +1,16 @@
 pub(crate) mod add;
+pub(crate) mod branch;
+pub(crate) mod cherry_pick;
 pub(crate) mod clone;
 pub(crate) mod command;
 pub(crate) mod create_repo;
 pub(crate) mod credentials;
 pub(crate) mod fake_action;
 pub(crate) mod fetch;
+pub(crate) mod merge;
+pub(crate) mod rebase;
+pub(crate) mod remote;
 pub(crate) mod stash;
+pub(crate) mod submodule;
+pub(crate) mod tag;
+pub(crate) mod worktree;
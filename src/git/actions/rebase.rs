@@ -0,0 +1,400 @@
+use std::collections::HashSet;
+use std::fs::{create_dir_all, remove_dir_all, write};
+use std::path::{Path, PathBuf};
+
+use ahash::AHashMap;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::f;
+use crate::git::git_types::Commit;
+use crate::git::queries::commit_calcs::{
+  find_commit_ancestors, get_commit_ids_between_commit_ids, get_commit_map_cloned,
+};
+use crate::git::queries::commits::commit_ids_between_commits_fallback;
+use crate::git::queries::refs::head_info::calc_head_info;
+use crate::git::queries::unpushed_commits::get_un_pushed_commits;
+use crate::git::run_git_action::run_git_action_then;
+use crate::git::store::STORE;
+use crate::server::git_request::ReqOptions;
+use crate::server::request_util::{ES, R};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum RebaseOp {
+  Pick,
+  Reword,
+  Edit,
+  Squash,
+  Fixup,
+  Drop,
+}
+
+#[derive(Debug, Clone, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct RebaseTodoItem {
+  pub commit_id: String,
+  pub op: RebaseOp,
+  // Replaces the commit message for Reword and Squash. Ignored for other ops.
+  pub message: Option<String>,
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct InteractiveRebaseOptions {
+  pub repo_path: String,
+  // Oldest first, the same order as git-rebase-todo.
+  pub todo: Vec<RebaseTodoItem>,
+}
+
+/*
+Runs "git rebase -i" without any editor. We write the todo list ourselves and have
+GIT_SEQUENCE_EDITOR copy it over the one git generates.
+
+The todo must contain every commit between the parent of the oldest listed commit and
+HEAD, otherwise git would silently drop the missing ones.
+ */
+pub fn interactive_rebase(options: &InteractiveRebaseOptions) -> R<u32> {
+  let InteractiveRebaseOptions { repo_path, todo } = options;
+
+  let first = todo
+    .first()
+    .ok_or(ES::from("interactive_rebase: Empty todo list."))?;
+
+  if first.op == RebaseOp::Squash || first.op == RebaseOp::Fixup {
+    return Err(ES::from(
+      "interactive_rebase: The first commit can't be squashed or fixed up.",
+    ));
+  }
+
+  let req = ReqOptions {
+    repo_path: repo_path.clone(),
+  };
+
+  let un_pushed = get_un_pushed_commits(&req);
+
+  if let Some(item) = todo.iter().find(|item| {
+    !un_pushed.this_branch.contains(&item.commit_id)
+      && !un_pushed.all_branches.contains(&item.commit_id)
+  }) {
+    return Err(ES::from(&f!(
+      "interactive_rebase: Commit {} has already been pushed.",
+      item.commit_id
+    )));
+  }
+
+  let (commits, _) = STORE
+    .get_commits_and_refs(repo_path)
+    .ok_or(ES::from("interactive_rebase: Commits not found."))?;
+  let commit_map = get_commit_map_cloned(&commits);
+
+  let oldest = find_oldest_commit(todo, &commit_map)?;
+  let base_id = oldest.parent_ids.first().cloned();
+
+  let head = calc_head_info(&req)?.commit;
+  let in_range = get_ids_to_rebase(repo_path, &head, base_id.as_deref(), &commit_map)?;
+
+  if let Some(c) = in_range
+    .iter()
+    .filter_map(|id| commit_map.get(id))
+    .find(|c| c.is_merge)
+  {
+    return Err(ES::from(&f!(
+      "interactive_rebase: Can't rebase merge commit {}.",
+      c.id
+    )));
+  }
+
+  let listed: HashSet<&str> = todo.iter().map(|item| item.commit_id.as_str()).collect();
+
+  if listed.len() != todo.len()
+    || in_range.len() != listed.len()
+    || in_range.iter().any(|id| !listed.contains(id.as_str()))
+  {
+    return Err(ES::from(
+      "interactive_rebase: Todo list doesn't match the commits between base and HEAD.",
+    ));
+  }
+
+  let git_path = get_git_path(repo_path);
+  let dir = git_path.join("gitfiend-rebase");
+
+  let _ = remove_dir_all(&dir);
+  create_dir_all(&dir)?;
+
+  let todo_path = dir.join("git-rebase-todo");
+  write(&todo_path, make_todo_text(todo, &dir)?)?;
+
+  let mut args = vec![String::from("rebase"), String::from("-i")];
+
+  match base_id {
+    Some(id) => args.push(id),
+    None => args.push(String::from("--root")),
+  }
+
+  let mut env = no_editor_env();
+  env.push((
+    String::from("GIT_SEQUENCE_EDITOR"),
+    f!("cp {}", quote_shell_arg(&path_to_string(&todo_path)?)),
+  ));
+
+  Ok(run_git_action_then(
+    repo_path,
+    vec![args],
+    env,
+    remove_rebase_dir(git_path),
+  ))
+}
+
+pub fn rebase_continue(options: &ReqOptions) -> u32 {
+  run_rebase_command(&options.repo_path, "--continue")
+}
+
+pub fn rebase_skip(options: &ReqOptions) -> u32 {
+  run_rebase_command(&options.repo_path, "--skip")
+}
+
+pub fn rebase_abort(options: &ReqOptions) -> u32 {
+  run_rebase_command(&options.repo_path, "--abort")
+}
+
+fn run_rebase_command(repo_path: &str, flag: &str) -> u32 {
+  run_git_action_then(
+    repo_path,
+    vec![vec![String::from("rebase"), flag.to_string()]],
+    no_editor_env(),
+    remove_rebase_dir(get_git_path(repo_path)),
+  )
+}
+
+fn get_git_path(repo_path: &str) -> PathBuf {
+  match STORE.get_repo_path(repo_path) {
+    Ok(p) => p.git_path,
+    Err(_) => Path::new(repo_path).join(".git"),
+  }
+}
+
+// Exec lines read the message files, so they have to outlive a rebase that stops for a
+// conflict or edit. Removed once git has finished or aborted.
+fn remove_rebase_dir(git_path: PathBuf) -> impl FnOnce() + Send + 'static {
+  move || {
+    if !git_path.join("rebase-merge").exists() {
+      let _ = remove_dir_all(git_path.join("gitfiend-rebase"));
+    }
+  }
+}
+
+// Squash and conflicted reword steps open an editor for the message. "true" accepts
+// whatever git has prepared. Also used to continue cherry-picks and reverts.
+pub(crate) fn no_editor_env() -> Vec<(String, String)> {
+  vec![(String::from("GIT_EDITOR"), String::from("true"))]
+}
+
+// Commits have a higher index the older they are.
+fn find_oldest_commit<'a>(
+  todo: &[RebaseTodoItem],
+  commit_map: &'a AHashMap<String, Commit>,
+) -> R<&'a Commit> {
+  let commits = todo
+    .iter()
+    .map(|item| {
+      commit_map.get(&item.commit_id).ok_or(ES::from(&f!(
+        "interactive_rebase: Commit {} isn't loaded.",
+        item.commit_id
+      )))
+    })
+    .collect::<R<Vec<&Commit>>>()?;
+
+  commits
+    .into_iter()
+    .max_by_key(|c| c.index)
+    .ok_or(ES::from("interactive_rebase: Empty todo list."))
+}
+
+fn get_ids_to_rebase(
+  repo_path: &str,
+  head: &Commit,
+  base_id: Option<&str>,
+  commit_map: &AHashMap<String, Commit>,
+) -> R<Vec<String>> {
+  match base_id {
+    Some(base_id) => {
+      if let Some(ids) =
+        get_commit_ids_between_commit_ids(&head.id, &base_id.to_string(), commit_map)
+      {
+        return Ok(ids);
+      }
+
+      commit_ids_between_commits_fallback(repo_path, base_id, &head.id)
+    }
+    None => {
+      let mut ids: Vec<String> = find_commit_ancestors(head, commit_map)
+        .into_iter()
+        .map(|id| id.to_string())
+        .collect();
+      ids.push(head.id.clone());
+
+      Ok(ids)
+    }
+  }
+}
+
+fn make_todo_text(todo: &[RebaseTodoItem], message_dir: &Path) -> R<String> {
+  let mut lines: Vec<String> = Vec::new();
+
+  for (i, item) in todo.iter().enumerate() {
+    let RebaseTodoItem {
+      commit_id,
+      op,
+      message,
+    } = item;
+
+    let command = match op {
+      RebaseOp::Pick => "pick",
+      RebaseOp::Reword => {
+        if message.is_some() {
+          "pick"
+        } else {
+          "reword"
+        }
+      }
+      RebaseOp::Edit => "edit",
+      RebaseOp::Squash => "squash",
+      RebaseOp::Fixup => "fixup",
+      RebaseOp::Drop => "drop",
+    };
+
+    lines.push(f!("{} {}", command, commit_id));
+
+    if let Some(message) = message {
+      if *op == RebaseOp::Reword || *op == RebaseOp::Squash {
+        let message_path = message_dir.join(f!("message-{}", i));
+        write(&message_path, message)?;
+
+        lines.push(f!(
+          "exec git commit --amend --only --allow-empty -F {}",
+          quote_shell_arg(&path_to_string(&message_path)?)
+        ));
+      }
+    }
+  }
+
+  Ok(lines.join("\n") + "\n")
+}
+
+fn path_to_string(path: &Path) -> R<String> {
+  Ok(
+    path
+      .to_str()
+      .ok_or(ES::from(
+        "interactive_rebase: Couldn't convert path to str.",
+      ))?
+      .replace('\\', "/"),
+  )
+}
+
+// Git runs editors and exec lines with sh, including on Windows.
+fn quote_shell_arg(arg: &str) -> String {
+  f!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::git::action_state::ACTIONS;
+  use crate::git::actions::rebase::{
+    interactive_rebase, make_todo_text, quote_shell_arg, InteractiveRebaseOptions,
+    RebaseOp, RebaseTodoItem,
+  };
+  use crate::git::queries::commits::load_commits_from_git;
+  use crate::git::store::STORE;
+  use crate::util::test_util::TempDir;
+  use std::env::temp_dir;
+  use std::thread;
+  use std::time::{Duration, Instant};
+
+  #[test]
+  fn test_quote_shell_arg() {
+    assert_eq!(quote_shell_arg("/a/b c"), "'/a/b c'");
+    assert_eq!(quote_shell_arg("it's"), "'it'\\''s'");
+  }
+
+  #[test]
+  fn test_make_todo_text() {
+    let todo = vec![
+      RebaseTodoItem {
+        commit_id: String::from("aaa"),
+        op: RebaseOp::Pick,
+        message: None,
+      },
+      RebaseTodoItem {
+        commit_id: String::from("bbb"),
+        op: RebaseOp::Fixup,
+        message: None,
+      },
+      RebaseTodoItem {
+        commit_id: String::from("ccc"),
+        op: RebaseOp::Drop,
+        message: Some(String::from("Ignored")),
+      },
+    ];
+
+    let text = make_todo_text(&todo, &temp_dir()).unwrap();
+
+    assert_eq!(text, "pick aaa\nfixup bbb\ndrop ccc\n");
+  }
+
+  #[test]
+  fn test_interactive_rebase() {
+    let repo = TempDir::with_repo("interactive_rebase");
+    let repo_path = repo.repo_path();
+
+    for (i, message) in ["c1", "c2", "c3", "c4", "c5"].iter().enumerate() {
+      repo.commit(message, 1_600_001_000 + i as u64 * 1000);
+    }
+
+    let (commits, refs) = load_commits_from_git(&repo_path, 100, true).unwrap();
+    STORE.insert_commits(&repo_path, &commits, &refs);
+
+    let id = |rev: &str| repo.git(&["rev-parse", rev]).trim().to_string();
+    let item = |rev: &str, op: RebaseOp, message: Option<&str>| RebaseTodoItem {
+      commit_id: id(rev),
+      op,
+      message: message.map(String::from),
+    };
+
+    // c2 to c5 are HEAD~3 to HEAD.
+    let action_id = interactive_rebase(&InteractiveRebaseOptions {
+      repo_path: repo_path.clone(),
+      todo: vec![
+        item("HEAD~1", RebaseOp::Pick, None),
+        item("HEAD~3", RebaseOp::Reword, Some("Reworded")),
+        item("HEAD~2", RebaseOp::Pick, None),
+        item("HEAD", RebaseOp::Squash, Some("Squashed")),
+      ],
+    })
+    .unwrap();
+
+    let start = Instant::now();
+    let action = loop {
+      let action = ACTIONS.get_by_key(&action_id).unwrap();
+      if action.done {
+        break action;
+      }
+      assert!(start.elapsed() < Duration::from_secs(30));
+      thread::sleep(Duration::from_millis(20));
+    };
+
+    assert!(action.error.is_none(), "{}", action.stderr.join("\n"));
+    assert_eq!(
+      repo.git(&["log", "--format=%s"]),
+      "Squashed\nReworded\nc4\nc1\n"
+    );
+    assert_eq!(
+      repo.git(&["show", "--format=", "--name-only", "HEAD"]),
+      "c3\nc5\n"
+    );
+    assert!(!repo.path.join(".git/gitfiend-rebase").exists());
+  }
+}
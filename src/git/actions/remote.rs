@@ -0,0 +1,258 @@
+use serde::Deserialize;
+use ts_rs::TS;
+
+use crate::f;
+use crate::git::queries::refs::ref_name::check_ref_format;
+use crate::git::repo_state::check_repo_state_now;
+use crate::git::run_git_action::{run_git_action, RunGitActionOptions};
+use crate::git::store::CONFIG;
+use crate::server::request_util::{ES, R};
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct AddRemoteOptions {
+  pub repo_path: String,
+  pub name: String,
+  pub url: String,
+  // Fetch the new remote straight away.
+  pub fetch: bool,
+}
+
+pub fn add_remote(options: &AddRemoteOptions) -> R<u32> {
+  let AddRemoteOptions {
+    repo_path,
+    name,
+    url,
+    fetch,
+  } = options;
+
+  check_new_remote_name("add_remote", repo_path, name)?;
+  prefix_err("add_remote", check_url(url))?;
+
+  let mut args = vec!["remote", "add"];
+  if *fetch {
+    args.push("-f");
+  }
+  args.extend([name.as_str(), url.as_str()]);
+
+  Ok(run_git_action(RunGitActionOptions {
+    repo_path,
+    commands: [args],
+  }))
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct RemoteOptions {
+  pub repo_path: String,
+  pub name: String,
+}
+
+// Also deletes the remote tracking branches and config that refer to it.
+pub fn remove_remote(options: &RemoteOptions) -> u32 {
+  run_git_action(RunGitActionOptions {
+    repo_path: &options.repo_path,
+    commands: [vec!["remote", "remove", &options.name]],
+  })
+}
+
+// Deletes remote tracking branches that no longer exist on the remote, without fetching.
+pub fn prune_remote(options: &RemoteOptions) -> u32 {
+  run_git_action(RunGitActionOptions {
+    repo_path: &options.repo_path,
+    commands: [vec!["remote", "prune", &options.name]],
+  })
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct RenameRemoteOptions {
+  pub repo_path: String,
+  pub old_name: String,
+  pub new_name: String,
+}
+
+pub fn rename_remote(options: &RenameRemoteOptions) -> R<u32> {
+  let RenameRemoteOptions {
+    repo_path,
+    old_name,
+    new_name,
+  } = options;
+
+  check_new_remote_name("rename_remote", repo_path, new_name)?;
+
+  Ok(run_git_action(RunGitActionOptions {
+    repo_path,
+    commands: [vec!["remote", "rename", old_name, new_name]],
+  }))
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct SetRemoteUrlOptions {
+  pub repo_path: String,
+  pub name: String,
+  pub url: String,
+  // Set the push url instead. Fetching keeps using the main url.
+  pub push: bool,
+}
+
+pub fn set_remote_url(options: &SetRemoteUrlOptions) -> R<u32> {
+  let SetRemoteUrlOptions {
+    repo_path,
+    name,
+    url,
+    push,
+  } = options;
+
+  prefix_err("set_remote_url", check_url(url))?;
+
+  let mut args = vec!["remote", "set-url"];
+  if *push {
+    args.push("--push");
+  }
+  args.extend([name.as_str(), url.as_str()]);
+
+  Ok(run_git_action(RunGitActionOptions {
+    repo_path,
+    commands: [args],
+  }))
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct RemoteUrlOptions {
+  pub url: String,
+}
+
+// For the UI to validate as the user types.
+pub fn check_remote_url(options: &RemoteUrlOptions) -> R<()> {
+  check_url(&options.url)
+}
+
+/*
+Accepts what git clone/fetch would: a URL with a known scheme, scp-like syntax
+("user@host:path") or a local path. Relative paths need to start with "./" or "../" so
+typos aren't taken as paths. Doesn't check that the remote exists.
+ */
+pub(crate) fn check_url(url: &str) -> R<()> {
+  if url.trim().is_empty() {
+    return Err(ES::from("Url can't be empty."));
+  }
+  if url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+    return Err(ES::from("Url can't contain spaces."));
+  }
+
+  if let Some((scheme, rest)) = url.split_once("://") {
+    const SCHEMES: [&str; 8] = [
+      "http", "https", "ssh", "git", "file", "ftp", "ftps", "git+ssh",
+    ];
+
+    if !SCHEMES.contains(&scheme) {
+      return Err(ES::from(&f!("Unsupported url scheme \"{}\".", scheme)));
+    }
+    if rest.is_empty() || (scheme != "file" && rest.starts_with('/')) {
+      return Err(ES::from("Url is missing a host."));
+    }
+    return Ok(());
+  }
+
+  if is_local_path(url) {
+    return Ok(());
+  }
+
+  // scp-like. Git treats it as a local path if there's a slash before the colon.
+  if let Some((host, path)) = url.split_once(':') {
+    let host = host.rsplit('@').next().unwrap_or(host);
+
+    if !host.is_empty() && !host.contains('/') && !path.is_empty() {
+      return Ok(());
+    }
+  }
+
+  Err(ES::from("Url isn't a valid remote url or path."))
+}
+
+fn is_local_path(url: &str) -> bool {
+  let bytes = url.as_bytes();
+  let windows_drive = bytes.len() > 2
+    && bytes[0].is_ascii_alphabetic()
+    && bytes[1] == b':'
+    && (bytes[2] == b'\\' || bytes[2] == b'/');
+
+  url.starts_with('/')
+    || url.starts_with("./")
+    || url.starts_with("../")
+    || url.starts_with('~')
+    || url.starts_with("\\\\")
+    || windows_drive
+}
+
+// Git checks remote names are valid in "refs/remotes/<name>/".
+fn check_new_remote_name(fn_name: &str, repo_path: &str, name: &str) -> R<()> {
+  if name.starts_with('-') || name.contains('/') {
+    return Err(ES::from(&f!("{}: Invalid remote name.", fn_name)));
+  }
+  prefix_err(fn_name, check_ref_format(name))?;
+
+  // Drops the cached config if git has written to it since, e.g. after add_remote.
+  check_repo_state_now(repo_path);
+
+  if let Some(config) = CONFIG.get_repo(repo_path) {
+    if config.remotes.contains_key(name) {
+      return Err(ES::from(&f!(
+        "{}: Remote {} already exists.",
+        fn_name,
+        name
+      )));
+    }
+  }
+
+  Ok(())
+}
+
+fn prefix_err(fn_name: &str, result: R<()>) -> R<()> {
+  result.map_err(|ES::Text(e)| ES::from(&f!("{}: {}", fn_name, e)))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::git::actions::remote::check_url;
+
+  #[test]
+  fn test_check_url_valid() {
+    for url in [
+      "https://github.com/user/repo.git",
+      "ssh://git@host:22/repo.git",
+      "git@github.com:user/repo.git",
+      "host:repo",
+      "file:///home/user/repo",
+      "/home/user/repo",
+      "../repo",
+      "C:\\Users\\me\\repo",
+      "C:/Users/me/repo",
+    ] {
+      assert!(check_url(url).is_ok(), "{}", url);
+    }
+  }
+
+  #[test]
+  fn test_check_url_invalid() {
+    for url in [
+      "",
+      "https://",
+      "https:///path",
+      "htp://github.com/repo",
+      "github.com/user repo",
+      "repo",
+      "a/b:c",
+    ] {
+      assert!(check_url(url).is_err(), "{:?}", url);
+    }
+  }
+}
@@ -1,8 +1,18 @@
+use std::fs::{remove_file, write};
+use std::path::PathBuf;
+
 use serde::Deserialize;
 use ts_rs::TS;
 
-use crate::git::run_git_action::{run_git_action, RunGitActionOptions};
+use crate::f;
+use crate::git::run_git::{run_git_with_input, RunGitOptions};
+use crate::git::run_git_action::{
+  failed_action, run_git_action, run_git_action_with_undo, run_git_action_with_vec,
+  GitStep, RunGitActionOptions,
+};
+use crate::git::store::STORE;
 use crate::server::git_request::ReqOptions;
+use crate::server::request_util::{ES, R};
 
 pub fn stash_changes(options: &ReqOptions) -> u32 {
   run_git_action(RunGitActionOptions {
@@ -19,27 +29,251 @@ pub struct StashStagedOptions {
   pub head_commit_id: String,
 }
 
+/*
+Stashes only what's staged. Unstaged and untracked changes are left alone.
+
+Git 2.35 added "stash push --staged". For older versions we build the stash commit
+ourselves from the recorded HEAD and index tree, reverse apply the staged diff, then store
+it. None of that moves HEAD, and if a step fails the worktree and index are restored.
+ */
 pub fn stash_staged(options: &StashStagedOptions) -> u32 {
   let StashStagedOptions {
     repo_path,
     head_commit_id,
   } = options;
 
-  run_git_action(RunGitActionOptions {
+  if STORE.get_git_version().at_least(2, 35) {
+    return run_git_action(RunGitActionOptions {
+      repo_path,
+      commands: [vec!["stash", "push", "--staged"]],
+    });
+  }
+
+  match stash_staged_steps(repo_path, head_commit_id) {
+    Ok((steps, patch_path)) => run_git_action_with_undo(repo_path, steps, move || {
+      let _ = remove_file(patch_path);
+    }),
+    Err(ES::Text(message)) => failed_action(&message),
+  }
+}
+
+// Only reads the repo and writes objects. Nothing in the index or worktree changes until
+// the returned steps run. Also returns the patch file the steps read, for removing after.
+fn stash_staged_steps(
+  repo_path: &str,
+  head_commit_id: &str,
+) -> R<(Vec<GitStep>, PathBuf)> {
+  let head = run_git_text(repo_path, ["rev-parse", "HEAD"])?;
+  if head != head_commit_id {
+    return Err(ES::from(
+      "stash_staged: HEAD has moved. Refresh and try again.",
+    ));
+  }
+
+  let index_tree = run_git_text(repo_path, ["write-tree"])?;
+  let head_tree = run_git_text(repo_path, ["rev-parse", "HEAD^{tree}"])?;
+  if index_tree == head_tree {
+    return Err(ES::from("stash_staged: Nothing is staged."));
+  }
+
+  // Fails when HEAD is detached.
+  let branch = run_git_text(repo_path, ["symbolic-ref", "--short", "-q", "HEAD"])
+    .unwrap_or_else(|_| String::from("(no branch)"));
+  let summary = run_git_text(repo_path, ["log", "-1", "--format=%h %s", "HEAD"])?;
+
+  // Same shape as a stash git would make: a commit of the index, and the stash commit
+  // on top with HEAD and the index commit as parents. Both have the staged tree.
+  let index_commit = run_git_text(
+    repo_path,
+    [
+      "commit-tree",
+      &index_tree,
+      "-p",
+      &head,
+      "-m",
+      &f!("index on {}: {}", branch, summary),
+    ],
+  )?;
+  let stash_message = f!("WIP on {}: {}", branch, summary);
+  let stash_commit = run_git_text(
+    repo_path,
+    [
+      "commit-tree",
+      &index_tree,
+      "-p",
+      &head,
+      "-p",
+      &index_commit,
+      "-m",
+      &stash_message,
+    ],
+  )?;
+
+  let patch = run_git_with_input(
+    RunGitOptions {
+      repo_path,
+      args: [
+        "diff",
+        "--cached",
+        "--binary",
+        "--no-color",
+        "--no-ext-diff",
+      ],
+    },
+    &[],
+  )?;
+  if patch.is_empty() {
+    return Err(ES::from("stash_staged: Failed to get staged changes."));
+  }
+
+  let repo = STORE.get_repo_path(repo_path)?;
+  let patch_file = repo.git_path.join("gitfiend-stash-staged.patch");
+  let patch_path = patch_file
+    .to_str()
+    .ok_or(ES::from("stash_staged: Couldn't convert path to str."))?
+    .to_string();
+  write(&patch_file, patch)?;
+
+  let steps = vec![
+    // Worktree and index separately. "apply --index" refuses files that also have
+    // unstaged changes.
+    GitStep {
+      command: vec![
+        String::from("apply"),
+        String::from("-R"),
+        patch_path.clone(),
+      ],
+      undo: vec![vec![String::from("apply"), patch_path.clone()]],
+    },
+    GitStep {
+      command: vec![
+        String::from("apply"),
+        String::from("-R"),
+        String::from("--cached"),
+        patch_path,
+      ],
+      undo: vec![vec![String::from("read-tree"), index_tree]],
+    },
+    GitStep {
+      command: vec![
+        String::from("stash"),
+        String::from("store"),
+        String::from("-m"),
+        stash_message,
+        stash_commit,
+      ],
+      undo: Vec::new(),
+    },
+  ];
+
+  Ok((steps, patch_file))
+}
+
+// Fails if git exits with an error, rather than giving back empty output.
+fn run_git_text<const N: usize>(repo_path: &str, args: [&str; N]) -> R<String> {
+  let out = run_git_with_input(RunGitOptions { repo_path, args }, &[])?;
+
+  Ok(String::from_utf8_lossy(&out).trim().to_string())
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct StashPushOptions {
+  pub repo_path: String,
+  pub message: Option<String>,
+  // Only stash these paths. Everything if empty.
+  pub paths: Vec<String>,
+  pub include_untracked: bool,
+}
+
+pub fn stash_push(options: &StashPushOptions) -> u32 {
+  let StashPushOptions {
     repo_path,
-    commands: [
+    message,
+    paths,
+    include_untracked,
+  } = options;
+
+  let mut args = vec![String::from("stash"), String::from("push")];
+
+  if *include_untracked {
+    args.push(String::from("--include-untracked"));
+  }
+
+  if let Some(message) = message {
+    args.push(String::from("-m"));
+    args.push(message.clone());
+  }
+
+  if !paths.is_empty() {
+    args.push(String::from("--"));
+    args.extend(paths.iter().cloned());
+  }
+
+  run_git_action_with_vec(repo_path, vec![args])
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct StashApplyOptions {
+  pub repo_path: String,
+  // E.g. "refs/stash@{0}".
+  pub stash_id: String,
+  // Also restore what was staged. Fails if that conflicts.
+  pub restore_index: bool,
+}
+
+// If this conflicts, the action errors and load_conflicted_files lists the files.
+pub fn apply_stash(options: &StashApplyOptions) -> u32 {
+  run_git_action_with_vec(&options.repo_path, vec![apply_stash_args(options)])
+}
+
+// Like "stash pop", the stash is only dropped if applying succeeded without conflicts.
+pub fn pop_stash(options: &StashApplyOptions) -> u32 {
+  run_git_action_with_vec(
+    &options.repo_path,
+    vec![
+      apply_stash_args(options),
       vec![
-        "commit",
-        "-m",
-        "TEMP_COMMIT: If you are seeing this commit there has been an error while stashing.",
+        String::from("stash"),
+        String::from("drop"),
+        options.stash_id.clone(),
       ],
-      vec!["add", "--all"],
-      vec!["stash", "push"],
-      vec!["reset", "--soft", head_commit_id],
-      vec!["add", "--all"],
-      vec!["stash", "push"],
-      vec!["stash", "pop", "stash@{1}"],
-      vec!["reset"],
     ],
+  )
+}
+
+fn apply_stash_args(options: &StashApplyOptions) -> Vec<String> {
+  let mut args = vec![String::from("stash"), String::from("apply")];
+
+  if options.restore_index {
+    args.push(String::from("--index"));
+  }
+
+  args.push(options.stash_id.clone());
+  args
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct StashIdOptions {
+  pub repo_path: String,
+  pub stash_id: String,
+}
+
+pub fn drop_stash(options: &StashIdOptions) -> u32 {
+  run_git_action(RunGitActionOptions {
+    repo_path: &options.repo_path,
+    commands: [vec!["stash", "drop", &options.stash_id]],
+  })
+}
+
+pub fn clear_stashes(options: &ReqOptions) -> u32 {
+  run_git_action(RunGitActionOptions {
+    repo_path: &options.repo_path,
+    commands: [vec!["stash", "clear"]],
   })
 }
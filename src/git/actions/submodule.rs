@@ -0,0 +1,109 @@
+use serde::Deserialize;
+use ts_rs::TS;
+
+use crate::git::run_git_action::run_git_action_with_vec;
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct SubmoduleOptions {
+  pub repo_path: String,
+  // Relative to repo_path. All submodules if empty.
+  pub paths: Vec<String>,
+  // Also apply to nested submodules.
+  pub recursive: bool,
+}
+
+pub fn init_submodules(options: &SubmoduleOptions) -> u32 {
+  // "submodule init" has no --recursive. Nested ones get initialised by update --init.
+  run_submodule_command(&options.repo_path, &options.paths, vec!["init"])
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct UpdateSubmodulesOptions {
+  pub repo_path: String,
+  pub paths: Vec<String>,
+  pub recursive: bool,
+  // Initialise any that aren't yet.
+  pub init: bool,
+}
+
+// Checks out the recorded commits. Clone progress for each submodule ends up in the
+// action's stderr.
+pub fn update_submodules(options: &UpdateSubmodulesOptions) -> u32 {
+  let UpdateSubmodulesOptions {
+    repo_path,
+    paths,
+    recursive,
+    init,
+  } = options;
+
+  let mut args = vec!["update", "--progress"];
+  if *init {
+    args.push("--init");
+  }
+  if *recursive {
+    args.push("--recursive");
+  }
+
+  run_submodule_command(repo_path, paths, args)
+}
+
+// Copies remote urls from .gitmodules into the config, e.g. after they've changed upstream.
+pub fn sync_submodules(options: &SubmoduleOptions) -> u32 {
+  let mut args = vec!["sync"];
+  if options.recursive {
+    args.push("--recursive");
+  }
+
+  run_submodule_command(&options.repo_path, &options.paths, args)
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct DeinitSubmodulesOptions {
+  pub repo_path: String,
+  // All submodules if empty.
+  pub paths: Vec<String>,
+  // Deinit even if there are local changes.
+  pub force: bool,
+}
+
+pub fn deinit_submodules(options: &DeinitSubmodulesOptions) -> u32 {
+  let DeinitSubmodulesOptions {
+    repo_path,
+    paths,
+    force,
+  } = options;
+
+  let mut args = vec![String::from("submodule"), String::from("deinit")];
+
+  if *force {
+    args.push(String::from("--force"));
+  }
+
+  // Deinit requires paths or --all to avoid doing everything by accident.
+  if paths.is_empty() {
+    args.push(String::from("--all"));
+  } else {
+    args.push(String::from("--"));
+    args.extend(paths.iter().cloned());
+  }
+
+  run_git_action_with_vec(repo_path, vec![args])
+}
+
+fn run_submodule_command(repo_path: &str, paths: &[String], command: Vec<&str>) -> u32 {
+  let mut args = vec![String::from("submodule")];
+  args.extend(command.into_iter().map(|a| a.to_string()));
+
+  if !paths.is_empty() {
+    args.push(String::from("--"));
+    args.extend(paths.iter().cloned());
+  }
+
+  run_git_action_with_vec(repo_path, vec![args])
+}
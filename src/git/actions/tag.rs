@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::f;
+use crate::git::queries::refs::ref_name::check_short_ref_name;
+use crate::git::run_git_action::run_git_action_with_vec;
+use crate::server::request_util::{ES, R};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum TagKind {
+  Lightweight,
+  Annotated,
+  // Annotated and signed with the configured gpg or ssh key.
+  Signed,
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct CreateTagOptions {
+  pub repo_path: String,
+  pub name: String,
+  pub commit_id: String,
+  pub kind: TagKind,
+  // Required for annotated and signed tags.
+  pub message: Option<String>,
+}
+
+pub fn create_tag(options: &CreateTagOptions) -> R<u32> {
+  let CreateTagOptions {
+    repo_path,
+    name,
+    commit_id,
+    kind,
+    message,
+  } = options;
+
+  if let Err(ES::Text(e)) = check_short_ref_name(name) {
+    return Err(ES::from(&f!("create_tag: {}", e)));
+  }
+
+  let mut args = vec![String::from("tag")];
+
+  match kind {
+    TagKind::Lightweight => {}
+    TagKind::Annotated | TagKind::Signed => {
+      let message = message
+        .as_ref()
+        .filter(|m| !m.trim().is_empty())
+        .ok_or(ES::from(
+          "create_tag: A message is required for annotated tags.",
+        ))?;
+
+      args.push(String::from(if *kind == TagKind::Signed {
+        "-s"
+      } else {
+        "-a"
+      }));
+      args.push(String::from("-m"));
+      args.push(message.clone());
+    }
+  }
+
+  args.push(name.clone());
+  args.push(commit_id.clone());
+
+  Ok(run_git_action_with_vec(repo_path, vec![args]))
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct DeleteTagOptions {
+  pub repo_path: String,
+  pub name: String,
+  // Also delete the tag from this remote.
+  pub remote: Option<String>,
+}
+
+pub fn delete_tag(options: &DeleteTagOptions) -> u32 {
+  let DeleteTagOptions {
+    repo_path,
+    name,
+    remote,
+  } = options;
+
+  let mut commands = vec![vec![String::from("tag"), String::from("-d"), name.clone()]];
+
+  if let Some(remote) = remote {
+    commands.push(vec![
+      String::from("push"),
+      remote.clone(),
+      String::from("--delete"),
+      tag_ref(name),
+    ]);
+  }
+
+  run_git_action_with_vec(repo_path, commands)
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct PushTagsOptions {
+  pub repo_path: String,
+  pub remote: String,
+  pub names: Vec<String>,
+}
+
+pub fn push_tags(options: &PushTagsOptions) -> R<u32> {
+  let PushTagsOptions {
+    repo_path,
+    remote,
+    names,
+  } = options;
+
+  if names.is_empty() {
+    return Err(ES::from("push_tags: No tags given."));
+  }
+
+  let mut args = vec![String::from("push"), remote.clone()];
+  args.extend(names.iter().map(|name| tag_ref(name)));
+
+  Ok(run_git_action_with_vec(repo_path, vec![args]))
+}
+
+// Full ref so git doesn't confuse the tag with a branch of the same name.
+fn tag_ref(name: &str) -> String {
+  f!("refs/tags/{}", name)
+}
@@ -0,0 +1,134 @@
+use serde::Deserialize;
+use ts_rs::TS;
+
+use crate::f;
+use crate::git::queries::refs::ref_name::check_short_ref_name;
+use crate::git::run_git_action::{
+  run_git_action, run_git_action_with_vec, RunGitActionOptions,
+};
+use crate::server::git_request::ReqOptions;
+use crate::server::request_util::{ES, R};
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct AddWorktreeOptions {
+  pub repo_path: String,
+  // Where to create the worktree. Must not exist or be empty.
+  pub path: String,
+  // Branch, tag or commit id to check out.
+  pub commit_ish: String,
+  // Create this branch at commit_ish and check it out.
+  pub new_branch: Option<String>,
+  // Check out commit_ish without a branch.
+  pub detach: bool,
+}
+
+pub fn add_worktree(options: &AddWorktreeOptions) -> R<u32> {
+  let AddWorktreeOptions {
+    repo_path,
+    path,
+    commit_ish,
+    new_branch,
+    detach,
+  } = options;
+
+  let mut args = vec![String::from("worktree"), String::from("add")];
+
+  if let Some(branch) = new_branch {
+    if let Err(ES::Text(e)) = check_short_ref_name(branch) {
+      return Err(ES::from(&f!("add_worktree: {}", e)));
+    }
+    args.push(String::from("-b"));
+    args.push(branch.clone());
+  } else if *detach {
+    args.push(String::from("--detach"));
+  }
+
+  args.push(path.clone());
+  args.push(commit_ish.clone());
+
+  Ok(run_git_action_with_vec(repo_path, vec![args]))
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct RemoveWorktreeOptions {
+  pub repo_path: String,
+  pub path: String,
+  // Remove even with uncommitted changes.
+  pub force: bool,
+}
+
+pub fn remove_worktree(options: &RemoveWorktreeOptions) -> u32 {
+  let RemoveWorktreeOptions {
+    repo_path,
+    path,
+    force,
+  } = options;
+
+  let mut args = vec!["worktree", "remove"];
+  if *force {
+    args.push("--force");
+  }
+  args.push(path);
+
+  run_git_action(RunGitActionOptions {
+    repo_path,
+    commands: [args],
+  })
+}
+
+// Cleans up info for worktrees whose directory has been deleted.
+pub fn prune_worktrees(options: &ReqOptions) -> u32 {
+  run_git_action(RunGitActionOptions {
+    repo_path: &options.repo_path,
+    commands: [vec!["worktree", "prune"]],
+  })
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct LockWorktreeOptions {
+  pub repo_path: String,
+  pub path: String,
+  pub reason: Option<String>,
+}
+
+// Stops prune from removing a worktree, e.g. one on a removable drive.
+pub fn lock_worktree(options: &LockWorktreeOptions) -> u32 {
+  let LockWorktreeOptions {
+    repo_path,
+    path,
+    reason,
+  } = options;
+
+  let mut args = vec!["worktree", "lock"];
+  if let Some(reason) = reason {
+    args.push("--reason");
+    args.push(reason);
+  }
+  args.push(path);
+
+  run_git_action(RunGitActionOptions {
+    repo_path,
+    commands: [args],
+  })
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct WorktreePathOptions {
+  pub repo_path: String,
+  pub path: String,
+}
+
+pub fn unlock_worktree(options: &WorktreePathOptions) -> u32 {
+  run_git_action(RunGitActionOptions {
+    repo_path: &options.repo_path,
+    commands: [vec!["worktree", "unlock", &options.path]],
+  })
+}
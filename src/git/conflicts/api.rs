@@ -11,7 +11,9 @@ use crate::git::conflicts::conflicted_file::{
 };
 use crate::git::git_types::WipPatch;
 use crate::git::queries::refs::P_REF_NAME;
+use crate::git::run_git::{run_git_err, RunGitOptions};
 use crate::parser::parse_all;
+use crate::server::git_request::ReqOptions;
 use crate::server::request_util::R;
 
 #[derive(Debug, Deserialize, TS)]
@@ -33,6 +35,23 @@ pub fn load_conflicted_file(options: &LoadConflictOptions) -> R<ConflictedFile>
   Ok(conflicted_lines(lines))
 }
 
+// Paths with unresolved conflicts, e.g. after a stash apply or cherry-pick stops.
+pub fn load_conflicted_files(options: &ReqOptions) -> R<Vec<String>> {
+  let out = run_git_err(RunGitOptions {
+    repo_path: &options.repo_path,
+    args: ["diff", "--name-only", "--diff-filter=U", "-z"],
+  })?
+  .stdout;
+
+  Ok(
+    out
+      .split('\0')
+      .filter(|path| !path.is_empty())
+      .map(|path| path.to_string())
+      .collect(),
+  )
+}
+
 const CONFLICT_START: &str = "<<<<<<<";
 const CONFLICT_MIDDLE: &str = "=======";
 const CONFLICT_END: &str = ">>>>>>>";
@@ -61,6 +61,28 @@ pub struct Commit {
   pub num_skipped: u32,
 }
 
+// For tests that build commit lists by hand.
+#[cfg(test)]
+pub(crate) fn make_commit(id: &str, parent_ids: &[&str]) -> Commit {
+  Commit {
+    author: String::new(),
+    email: String::new(),
+    date: DateResult {
+      ms: 0,
+      adjustment: 0,
+    },
+    id: id.to_string(),
+    index: 0,
+    parent_ids: parent_ids.iter().map(|p| p.to_string()).collect(),
+    is_merge: parent_ids.len() > 1,
+    message: format!("Commit {}", id),
+    stash_id: String::new(),
+    refs: Vec::new(),
+    filtered: false,
+    num_skipped: 0,
+  }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[serde(rename_all = "camelCase")]
 #[ts(export)]
@@ -120,6 +142,10 @@ pub struct Patch {
   pub patch_type: PatchType,
   pub id: String,
   pub is_image: bool,
+  // Tracked with filter=lfs. Only set by queries that return patches to the UI, the
+  // patch cache leaves it false.
+  #[serde(default)]
+  pub is_lfs: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -198,4 +224,6 @@ pub struct WipPatch {
   pub conflicted: bool,
   pub id: String,
   pub is_image: bool,
+  #[serde(default)]
+  pub is_lfs: bool,
 }
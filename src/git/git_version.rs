@@ -59,6 +59,10 @@ impl GitVersion {
   pub fn valid(&self) -> bool {
     self.major > 0
   }
+
+  pub fn at_least(&self, major: u32, minor: u32) -> bool {
+    self.major > major || (self.major == major && self.minor >= minor)
+  }
 }
 
 fn parse_version(text: &str) -> Option<GitVersion> {
@@ -161,6 +165,20 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_at_least() {
+    let version = GitVersion {
+      major: 2,
+      minor: 32,
+      patch: 1,
+    };
+
+    assert!(version.at_least(2, 32));
+    assert!(version.at_least(1, 40));
+    assert!(!version.at_least(2, 38));
+    assert!(!version.at_least(3, 0));
+  }
+
   #[test]
   fn test_p_windows_version() {
     let result = parse_version("git version 2.37.3.windows.1");
@@ -0,0 +1,391 @@
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use ahash::AHashMap;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::git::git_settings::GIT_PATH;
+use crate::git::git_types::DateResult;
+use crate::git::queries::commits_parsers::P_DATE;
+use crate::git::run_git::{run_git_err, RunGitOptions};
+use crate::parser::standard_parsers::{ANY_WORD, UNSIGNED_INT};
+use crate::parser::{parse_all, Parser};
+use crate::server::request_util::{ES, R};
+use crate::util::global::Global;
+use crate::{and, character, dprintln, f, global, map2};
+
+#[derive(Debug, Clone, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct BlameOptions {
+  pub repo_path: String,
+  pub file_path: String,
+  // None blames the working copy, uncommitted lines get an id of all zeros.
+  pub commit_id: Option<String>,
+  pub ignore_whitespace: bool,
+  // E.g. ".git-blame-ignore-revs" for commits that only reformatted code.
+  pub ignore_revs_file: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct BlameRange {
+  pub commit_id: String,
+  // 1-based line in the blamed file.
+  pub start_line: u32,
+  pub num_lines: u32,
+  // Where the lines were in the commit that added them.
+  pub original_start_line: u32,
+  pub original_file: String,
+  pub author: String,
+  pub author_email: String,
+  pub date: Option<DateResult>,
+  pub summary: String,
+  // The commit is a root commit, or the range was limited.
+  pub boundary: bool,
+}
+
+// Git only prints the commit details the first time a commit appears.
+#[derive(Debug, Clone, Default)]
+struct BlameCommit {
+  author: String,
+  author_email: String,
+  author_time: String,
+  author_tz: String,
+  summary: String,
+  boundary: bool,
+}
+
+struct RangeHeader {
+  commit_id: String,
+  original_start_line: u32,
+  start_line: u32,
+  num_lines: u32,
+}
+
+// "<commit id> <original line> <final line> <num lines>"
+const P_RANGE_HEADER: Parser<RangeHeader> = map2!(
+  and!(
+    ANY_WORD,
+    character!(' '),
+    UNSIGNED_INT,
+    character!(' '),
+    UNSIGNED_INT,
+    character!(' '),
+    UNSIGNED_INT
+  ),
+  res,
+  RangeHeader {
+    commit_id: res.0,
+    original_start_line: res.2.parse().unwrap_or_default(),
+    start_line: res.4.parse().unwrap_or_default(),
+    num_lines: res.6.parse().unwrap_or_default(),
+  }
+);
+
+/*
+Parses "git blame --incremental" output one line at a time, so we can give results
+before git has finished. Each range is a header line followed by "key value" lines
+ending with "filename <path>".
+ */
+#[derive(Default)]
+struct BlameParser {
+  commits: AHashMap<String, BlameCommit>,
+  current: Option<RangeHeader>,
+}
+
+impl BlameParser {
+  fn push_line(&mut self, line: &str) -> Option<BlameRange> {
+    let Some(header) = &self.current else {
+      self.current = parse_all(P_RANGE_HEADER, line);
+      return None;
+    };
+
+    let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+    let commit = self.commits.entry(header.commit_id.clone()).or_default();
+
+    match key {
+      "author" => commit.author = value.to_string(),
+      "author-mail" => {
+        commit.author_email = value
+          .trim_start_matches('<')
+          .trim_end_matches('>')
+          .to_string()
+      }
+      "author-time" => commit.author_time = value.to_string(),
+      "author-tz" => commit.author_tz = value.to_string(),
+      "summary" => commit.summary = value.to_string(),
+      "boundary" => commit.boundary = true,
+      "filename" => {
+        let header = self.current.take()?;
+        let commit = self.commits.get(&header.commit_id)?;
+
+        return Some(BlameRange {
+          start_line: header.start_line,
+          num_lines: header.num_lines,
+          original_start_line: header.original_start_line,
+          original_file: value.to_string(),
+          author: commit.author.clone(),
+          author_email: commit.author_email.clone(),
+          date: parse_all(P_DATE, &f!("{} {}", commit.author_time, commit.author_tz)),
+          summary: commit.summary.clone(),
+          boundary: commit.boundary,
+          commit_id: header.commit_id,
+        });
+      }
+      _ => {}
+    }
+
+    None
+  }
+}
+
+fn blame_args(options: &BlameOptions) -> Vec<String> {
+  let BlameOptions {
+    file_path,
+    commit_id,
+    ignore_whitespace,
+    ignore_revs_file,
+    ..
+  } = options;
+
+  let mut args = vec![String::from("blame"), String::from("--incremental")];
+
+  if *ignore_whitespace {
+    args.push(String::from("-w"));
+  }
+  if let Some(file) = ignore_revs_file {
+    args.push(String::from("--ignore-revs-file"));
+    args.push(file.clone());
+  }
+  if let Some(id) = commit_id {
+    args.push(id.clone());
+  }
+  args.push(String::from("--"));
+  args.push(file_path.clone());
+
+  args
+}
+
+// Waits for the whole blame. Use start_blame for big files.
+pub fn load_blame(options: &BlameOptions) -> R<Vec<BlameRange>> {
+  let out = run_git_err(RunGitOptions {
+    repo_path: &options.repo_path,
+    args: blame_args(options),
+  })?;
+
+  if out.stdout.is_empty() && !out.stderr.is_empty() {
+    return Err(ES::from(&f!("load_blame: {}", out.stderr.trim())));
+  }
+
+  let mut parser = BlameParser::default();
+  let mut ranges: Vec<BlameRange> = out
+    .stdout
+    .lines()
+    .filter_map(|l| parser.push_line(l))
+    .collect();
+
+  ranges.sort_by_key(|r| r.start_line);
+
+  Ok(ranges)
+}
+
+#[derive(Debug, Clone, Default)]
+struct BlameJob {
+  ranges: Vec<BlameRange>,
+  completed: bool,
+  error: Option<String>,
+}
+
+static BLAMES: Global<AHashMap<u32, BlameJob>> = global!(AHashMap::new());
+static CURRENT_BLAME: Global<u32> = global!(0);
+
+/*
+Like start_diff_search, this returns an id straight away and the results are fetched
+with poll_blame. Starting a new blame cancels the previous one.
+ */
+pub fn start_blame(options: &BlameOptions) -> u32 {
+  let blame_id = CURRENT_BLAME.get().unwrap_or(0) + 1;
+  CURRENT_BLAME.set(blame_id);
+
+  BLAMES.insert(blame_id, BlameJob::default());
+
+  let o = options.clone();
+
+  thread::spawn(move || {
+    let error = run_blame(&o, blame_id).err().map(|e| match e {
+      ES::Text(text) => text,
+    });
+
+    if let Ok(mut blames) = BLAMES.data.write() {
+      if let Some(job) = blames.get_mut(&blame_id) {
+        job.completed = true;
+        job.error = error;
+      }
+    }
+  });
+
+  blame_id
+}
+
+fn run_blame(options: &BlameOptions, blame_id: u32) -> R<()> {
+  let mut cmd = Command::new(GIT_PATH.as_path())
+    .args(blame_args(options))
+    .current_dir(&options.repo_path)
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()?;
+
+  let stdout = cmd
+    .stdout
+    .take()
+    .ok_or(ES::from("start_blame: Failed to read output."))?;
+  let mut stderr = cmd
+    .stderr
+    .take()
+    .ok_or(ES::from("start_blame: Failed to read errors."))?;
+
+  // Read alongside stdout, git would block if the stderr pipe filled up.
+  let stderr_thread = thread::spawn(move || {
+    let mut text = String::new();
+    let _ = stderr.read_to_string(&mut text);
+    text
+  });
+
+  let mut reader = BufReader::new(stdout);
+  let mut parser = BlameParser::default();
+  let mut line = Vec::new();
+
+  while reader.read_until(b'\n', &mut line)? > 0 {
+    if CURRENT_BLAME.get() != Some(blame_id) {
+      dprintln!("Killing blame {blame_id}");
+      let _ = cmd.kill();
+      let _ = cmd.wait();
+      BLAMES.remove(&blame_id);
+
+      return Ok(());
+    }
+
+    let text = String::from_utf8_lossy(&line);
+
+    if let Some(range) = parser.push_line(text.trim_end_matches(['\n', '\r'])) {
+      if let Ok(mut blames) = BLAMES.data.write() {
+        if let Some(job) = blames.get_mut(&blame_id) {
+          job.ranges.push(range);
+        }
+      }
+    }
+
+    line.clear();
+  }
+
+  let status = cmd.wait()?;
+  let stderr = stderr_thread.join().unwrap_or_default();
+
+  if !status.success() {
+    return Err(ES::from(&f!("start_blame: {}", stderr.trim())));
+  }
+
+  Ok(())
+}
+
+#[derive(Debug, Clone, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct PollBlameOptions {
+  pub blame_id: u32,
+  // Number of ranges already received.
+  pub from_index: usize,
+}
+
+#[derive(Debug, Clone, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct PollBlameResult {
+  pub blame_id: u32,
+  pub complete: bool,
+  // In the order git found them, not line order.
+  pub ranges: Vec<BlameRange>,
+  pub error: Option<String>,
+}
+
+pub fn poll_blame(options: &PollBlameOptions) -> PollBlameResult {
+  let PollBlameOptions {
+    blame_id,
+    from_index,
+  } = options;
+
+  let Some(job) = BLAMES.get_by_key(blame_id) else {
+    // Cancelled or already finished and polled.
+    return PollBlameResult {
+      blame_id: *blame_id,
+      complete: true,
+      ranges: Vec::new(),
+      error: None,
+    };
+  };
+
+  if job.completed {
+    BLAMES.remove(blame_id);
+  }
+
+  PollBlameResult {
+    blame_id: *blame_id,
+    complete: job.completed,
+    ranges: job.ranges.into_iter().skip(*from_index).collect(),
+    error: job.error,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::git::queries::blame::BlameParser;
+
+  #[test]
+  fn test_blame_parser() {
+    let out = "6e0aea5 1 1 2
+author Sam
+author-mail <sam@b>
+author-time 1792378851
+author-tz +0100
+committer Sam
+committer-mail <sam@b>
+committer-time 1792378851
+committer-tz +0100
+summary init
+boundary
+filename old name.txt
+53738d6 3 3 1
+author Al
+author-mail <al@b>
+author-time 1792378887
+author-tz +0000
+committer Al
+committer-mail <al@b>
+committer-time 1792378887
+committer-tz +0000
+summary second
+previous 6e0aea5 old name.txt
+filename f.txt
+6e0aea5 5 4 1
+filename old name.txt";
+
+    let mut parser = BlameParser::default();
+    let ranges: Vec<_> = out.lines().filter_map(|l| parser.push_line(l)).collect();
+
+    assert_eq!(ranges.len(), 3);
+    assert_eq!(ranges[0].num_lines, 2);
+    assert_eq!(ranges[0].original_file, "old name.txt");
+    assert_eq!(ranges[0].author_email, "sam@b");
+    assert_eq!(ranges[0].date.as_ref().unwrap().adjustment, 100);
+    assert!(ranges[0].boundary);
+    assert_eq!(ranges[1].summary, "second");
+    assert!(!ranges[1].boundary);
+    assert_eq!(ranges[2].start_line, 4);
+    assert_eq!(ranges[2].original_start_line, 5);
+    assert_eq!(ranges[2].author, "Sam");
+  }
+}
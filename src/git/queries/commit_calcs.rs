@@ -69,16 +69,18 @@ fn find_commit_descendants_inner(
 
 // How many commits ahead is a. The order matters.
 pub fn count_commits_between_commit_ids(
+  repo_path: &str,
   a_id: &String,
   b_id: &String,
   commits: &AHashMap<String, Commit>,
 ) -> u32 {
   let key = format!("{}{}", a_id, b_id);
 
-  if let Ok(count) = REF_DIFFS.read() {
-    if let Some(count) = count.get(&key) {
-      return *count;
-    }
+  if let Some(count) = REF_DIFFS
+    .with_repo(repo_path, |diffs| diffs.get(&key).copied())
+    .flatten()
+  {
+    return count;
   }
 
   if let Some(a) = commits.get(a_id) {
@@ -101,9 +103,7 @@ pub fn count_commits_between_commit_ids(
         }
       }
 
-      if let Ok(mut diffs) = REF_DIFFS.write() {
-        diffs.insert(key, num);
-      }
+      REF_DIFFS.update_repo(repo_path, |diffs| diffs.insert(key, num));
 
       return num;
     }
@@ -182,12 +182,11 @@ mod tests {
 
   #[test]
   fn test_ref_diffs() {
-    if let Ok(mut diffs) = REF_DIFFS.write() {
-      diffs.insert("OMG".to_string(), 1);
-    }
-    // REF_DIFFS.insert("OMG".to_string(), 1);
+    REF_DIFFS.update_repo("repo", |diffs| diffs.insert("OMG".to_string(), 1));
 
-    assert!(REF_DIFFS.read().unwrap().get("OMG").is_some());
-    // assert!(REF_DIFFS.get_diff("OMG").is_some());
+    assert!(REF_DIFFS
+      .with_repo("repo", |diffs| diffs.get("OMG").is_some())
+      .unwrap());
+    assert!(REF_DIFFS.with_repo("other repo", |_| ()).is_none());
   }
 }
@@ -0,0 +1,271 @@
+use ahash::AHashMap;
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::git::git_types::{Commit, RefInfo};
+use crate::git::queries::commits::{load_commits_and_refs, ReqCommitsOptions2};
+use crate::server::request_util::R;
+
+// Holds a lane for the row being laid out, so it isn't handed out twice.
+const RESERVED: &str = "";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct GraphEdge {
+  pub child_index: usize,
+  // None when the parent isn't in the list (not loaded yet, or filtered out). The
+  // lane isn't kept for it, so only draw a stub.
+  pub parent_index: Option<usize>,
+  pub child_column: u32,
+  // The column the edge runs down between the two rows.
+  pub lane: u32,
+  pub parent_column: Option<u32>,
+  // Not the first parent, so it's the merged in side.
+  pub merge_parent: bool,
+  // Filtered out commits were skipped somewhere along this edge.
+  pub through_gap: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct CommitGraph {
+  // Column of each commit, in the same order as the commits.
+  pub columns: Vec<u32>,
+  pub edges: Vec<GraphEdge>,
+  pub num_columns: u32,
+}
+
+// Same as load_commits_and_refs, plus the graph layout for the returned commits.
+pub fn load_commits_refs_and_graph(
+  options: &ReqCommitsOptions2,
+) -> R<(Vec<Commit>, Vec<RefInfo>, CommitGraph)> {
+  let (commits, refs) = load_commits_and_refs(options)?;
+  let graph = calc_commit_graph(&commits);
+
+  Ok((commits, refs, graph))
+}
+
+/*
+Goes down the commits (newest first) keeping a list of lanes, each waiting for the
+commit id it will lead to. A commit takes the leftmost lane waiting for it, or the
+first free one. Its first parent carries on in the same lane, other parents get a
+lane each unless one is already waiting for them.
+
+Each row only looks at its own lanes, so this is O(commits * lanes).
+ */
+pub fn calc_commit_graph(commits: &[Commit]) -> CommitGraph {
+  let index_map: AHashMap<&str, usize> = commits
+    .iter()
+    .enumerate()
+    .map(|(i, c)| (c.id.as_str(), i))
+    .collect();
+
+  // skipped_before[i] is the number of filtered out commits above row i.
+  let mut skipped_before: Vec<u64> = Vec::with_capacity(commits.len() + 1);
+  let mut total = 0;
+  for c in commits {
+    total += c.num_skipped as u64;
+    skipped_before.push(total);
+  }
+
+  let mut lanes: Vec<Option<&str>> = Vec::new();
+  // Commit id to edges waiting for it.
+  let mut pending: AHashMap<&str, Vec<usize>> = AHashMap::new();
+  let mut columns: Vec<u32> = Vec::with_capacity(commits.len());
+  let mut edges: Vec<GraphEdge> = Vec::with_capacity(commits.len());
+  let mut num_columns = 0;
+
+  for (index, commit) in commits.iter().enumerate() {
+    let id = commit.id.as_str();
+
+    let waiting: Vec<usize> = lanes
+      .iter()
+      .enumerate()
+      .filter(|(_, l)| **l == Some(id))
+      .map(|(i, _)| i)
+      .collect();
+
+    let column = match waiting.first() {
+      Some(c) => *c,
+      None => take_free_lane(&mut lanes),
+    };
+
+    for lane in waiting {
+      lanes[lane] = None;
+    }
+    lanes[column] = Some(RESERVED);
+
+    if let Some(edge_ids) = pending.remove(id) {
+      for e in edge_ids {
+        let edge = &mut edges[e];
+        edge.parent_index = Some(index);
+        edge.parent_column = Some(column as u32);
+        edge.through_gap = skipped_before[index] > skipped_before[edge.child_index];
+      }
+    }
+
+    // Stashes also have index and untracked commits as parents, which we don't show.
+    let parent_ids = if commit.stash_id.is_empty() {
+      &commit.parent_ids[..]
+    } else {
+      &commit.parent_ids[..commit.parent_ids.len().min(1)]
+    };
+
+    for (i, parent_id) in parent_ids.iter().enumerate() {
+      let parent_id = parent_id.as_str();
+      let parent_loaded = index_map.get(parent_id).is_some_and(|p| *p > index);
+
+      // Several lanes can wait for the same commit, they join when we get to it.
+      let lane = if i == 0 {
+        column
+      } else {
+        match lanes.iter().position(|l| *l == Some(parent_id)) {
+          Some(lane) => lane,
+          None => take_free_lane(&mut lanes),
+        }
+      };
+
+      if parent_loaded {
+        lanes[lane] = Some(parent_id);
+        pending.entry(parent_id).or_default().push(edges.len());
+      }
+
+      edges.push(GraphEdge {
+        child_index: index,
+        parent_index: None,
+        child_column: column as u32,
+        lane: lane as u32,
+        parent_column: None,
+        merge_parent: i > 0,
+        through_gap: false,
+      });
+    }
+
+    for lane in lanes.iter_mut() {
+      if *lane == Some(RESERVED) {
+        *lane = None;
+      }
+    }
+
+    columns.push(column as u32);
+    num_columns = num_columns.max(lanes.len() as u32);
+
+    while lanes.last() == Some(&None) {
+      lanes.pop();
+    }
+  }
+
+  CommitGraph {
+    columns,
+    edges,
+    num_columns,
+  }
+}
+
+// Marks the first free lane as taken and returns it.
+fn take_free_lane(lanes: &mut Vec<Option<&str>>) -> usize {
+  let lane = match lanes.iter().position(|l| l.is_none()) {
+    Some(lane) => lane,
+    None => {
+      lanes.push(None);
+      lanes.len() - 1
+    }
+  };
+
+  lanes[lane] = Some(RESERVED);
+
+  lane
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::git::git_types::{make_commit, Commit};
+  use crate::git::queries::commit_graph::calc_commit_graph;
+
+  /*
+  m      0
+  | \
+  | b    1
+  a |    2
+  |/
+  r      3
+   */
+  #[test]
+  fn test_merge_layout() {
+    let commits = vec![
+      make_commit("m", &["a", "b"]),
+      make_commit("b", &["r"]),
+      make_commit("a", &["r"]),
+      make_commit("r", &[]),
+    ];
+
+    let graph = calc_commit_graph(&commits);
+
+    assert_eq!(graph.columns, vec![0, 1, 0, 0]);
+    assert_eq!(graph.num_columns, 2);
+    assert_eq!(graph.edges.len(), 4);
+
+    let merge_edge = &graph.edges[1];
+    assert!(merge_edge.merge_parent);
+    assert_eq!(merge_edge.parent_index, Some(1));
+    assert_eq!(merge_edge.lane, 1);
+
+    // b joins back into the lane for r.
+    let b_edge = &graph.edges[2];
+    assert_eq!(b_edge.child_column, 1);
+    assert_eq!(b_edge.parent_index, Some(3));
+    assert_eq!(b_edge.parent_column, Some(0));
+  }
+
+  #[test]
+  fn test_unloaded_parent_and_gaps() {
+    let mut commits = vec![
+      make_commit("s", &["a", "i"]),
+      make_commit("a", &["x"]),
+      make_commit("b", &["missing"]),
+      make_commit("x", &[]),
+    ];
+    commits[0].stash_id = String::from("refs/stash@{0}");
+    commits[3].num_skipped = 2;
+
+    let graph = calc_commit_graph(&commits);
+
+    // Only the stash's first parent is shown.
+    assert_eq!(graph.edges.iter().filter(|e| e.child_index == 0).count(), 1);
+    // b gets its own lane, freed again as its parent isn't loaded.
+    assert_eq!(graph.columns, vec![0, 0, 1, 0]);
+
+    let b_edge = graph.edges.iter().find(|e| e.child_index == 2).unwrap();
+    assert_eq!(b_edge.parent_index, None);
+
+    let a_edge = graph.edges.iter().find(|e| e.child_index == 1).unwrap();
+    assert_eq!(a_edge.parent_index, Some(3));
+    assert!(a_edge.through_gap);
+  }
+
+  #[test]
+  fn test_large_graph() {
+    let ids: Vec<String> = (0..100_000).map(|i| i.to_string()).collect();
+
+    // Mostly linear history with a side branch merged every 10 commits.
+    let commits: Vec<Commit> = (0..ids.len())
+      .map(|i| {
+        let next = ids.get(i + 1).map(|s| s.as_str());
+        let side = ids.get(i + 5).map(|s| s.as_str());
+
+        match (next, side) {
+          (Some(n), Some(s)) if i % 10 == 0 => make_commit(&ids[i], &[n, s]),
+          (Some(n), _) => make_commit(&ids[i], &[n]),
+          _ => make_commit(&ids[i], &[]),
+        }
+      })
+      .collect();
+
+    let graph = calc_commit_graph(&commits);
+
+    assert_eq!(graph.columns.len(), commits.len());
+    assert!(graph.num_columns <= 2);
+  }
+}
@@ -0,0 +1,257 @@
+use ahash::AHashMap;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::git::run_git::{run_git_err, RunGitOptions};
+use crate::git::store::SIGNATURES;
+use crate::server::request_util::R;
+
+const MAX_IDS_PER_CALL: usize = 500;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, TS)]
+#[ts(export)]
+pub enum SignatureStatus {
+  Good,
+  // Good signature from a key we don't trust.
+  GoodUnknownValidity,
+  GoodExpiredSignature,
+  GoodExpiredKey,
+  GoodRevokedKey,
+  Bad,
+  // Signed, but the key is missing or verification isn't set up (e.g. no
+  // gpg.ssh.allowedSignersFile).
+  CannotCheck,
+  Unsigned,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, TS)]
+#[ts(export)]
+pub enum SignatureFormat {
+  Gpg,
+  Ssh,
+  X509,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct CommitSignature {
+  pub commit_id: String,
+  pub status: SignatureStatus,
+  pub format: Option<SignatureFormat>,
+  // GPG user id or the principal from the ssh allowed signers file.
+  pub signer: Option<String>,
+  // GPG key id or ssh key fingerprint.
+  pub key: Option<String>,
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct CommitSignaturesOptions {
+  pub repo_path: String,
+  // Checking signatures is slow, so only ask for visible commits.
+  pub commit_ids: Vec<String>,
+}
+
+pub fn load_commit_signatures(
+  options: &CommitSignaturesOptions,
+) -> R<Vec<CommitSignature>> {
+  let CommitSignaturesOptions {
+    repo_path,
+    commit_ids,
+  } = options;
+
+  let mut signatures: AHashMap<String, CommitSignature> = AHashMap::new();
+  let mut to_check: Vec<&str> = Vec::new();
+
+  SIGNATURES.with_repo(repo_path, |cached| {
+    for id in commit_ids {
+      if let Some(signature) = cached.get(id) {
+        signatures.insert(id.clone(), signature.clone());
+      }
+    }
+  });
+
+  for id in commit_ids {
+    if !signatures.contains_key(id) {
+      to_check.push(id);
+    }
+  }
+
+  for ids in to_check.chunks(MAX_IDS_PER_CALL) {
+    let checked = check_signatures(repo_path, ids)?;
+
+    SIGNATURES.update_repo(repo_path, |cached| {
+      for signature in checked.iter() {
+        if signature.status != SignatureStatus::CannotCheck {
+          cached.insert(signature.commit_id.clone(), signature.clone());
+        }
+      }
+    });
+
+    for signature in checked {
+      signatures.insert(signature.commit_id.clone(), signature);
+    }
+  }
+
+  Ok(
+    commit_ids
+      .iter()
+      .filter_map(|id| signatures.remove(id))
+      .collect(),
+  )
+}
+
+fn check_signatures(repo_path: &str, ids: &[&str]) -> R<Vec<CommitSignature>> {
+  let mut args = vec![
+    "log",
+    "--no-walk=unsorted",
+    "--format=%H%x00%G?%x00%GS%x00%GK%x00",
+  ];
+  args.extend(ids);
+
+  let out = run_git_err(RunGitOptions { repo_path, args })?.stdout;
+
+  // %G? gives "N" for ssh signed commits when there's no allowed signers file, so we
+  // also look at the raw headers to see what's signed.
+  let mut args = vec!["log", "--no-walk=unsorted", "--pretty=raw"];
+  args.extend(ids);
+
+  let raw = run_git_err(RunGitOptions { repo_path, args })?.stdout;
+  let formats = parse_signature_formats(&raw);
+
+  Ok(
+    out
+      .lines()
+      .filter_map(|line| {
+        let fields: Vec<&str> = line.split('\0').collect();
+
+        if fields.len() < 4 {
+          return None;
+        }
+
+        let commit_id = fields[0].to_string();
+        let format = formats.get(&commit_id).cloned();
+
+        Some(CommitSignature {
+          status: map_status(fields[1], format.is_some()),
+          format,
+          signer: non_empty(fields[2]),
+          key: non_empty(fields[3]),
+          commit_id,
+        })
+      })
+      .collect(),
+  )
+}
+
+fn map_status(code: &str, has_signature: bool) -> SignatureStatus {
+  match code {
+    "G" => SignatureStatus::Good,
+    "U" => SignatureStatus::GoodUnknownValidity,
+    "X" => SignatureStatus::GoodExpiredSignature,
+    "Y" => SignatureStatus::GoodExpiredKey,
+    "R" => SignatureStatus::GoodRevokedKey,
+    "B" => SignatureStatus::Bad,
+    "E" => SignatureStatus::CannotCheck,
+    _ if has_signature => SignatureStatus::CannotCheck,
+    _ => SignatureStatus::Unsigned,
+  }
+}
+
+/*
+Raw commits look like:
+commit <id>
+tree <id>
+author ...
+gpgsig -----BEGIN SSH SIGNATURE-----
+ <signature lines start with a space>
+
+    <message lines are indented>
+ */
+fn parse_signature_formats(raw: &str) -> AHashMap<String, SignatureFormat> {
+  let mut formats = AHashMap::new();
+  let mut commit_id: Option<&str> = None;
+
+  for line in raw.lines() {
+    if let Some(id) = line.strip_prefix("commit ") {
+      commit_id = Some(id.trim());
+    } else if let Some(rest) = line
+      .strip_prefix("gpgsig ")
+      .or(line.strip_prefix("gpgsig-sha256 "))
+    {
+      if let Some(id) = commit_id {
+        let format = if rest.contains("SSH SIGNATURE") {
+          SignatureFormat::Ssh
+        } else if rest.contains("SIGNED MESSAGE") {
+          SignatureFormat::X509
+        } else {
+          SignatureFormat::Gpg
+        };
+
+        formats.insert(id.to_string(), format);
+      }
+    }
+  }
+
+  formats
+}
+
+fn non_empty(s: &str) -> Option<String> {
+  if s.is_empty() {
+    None
+  } else {
+    Some(s.to_string())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::git::queries::commit_signatures::{
+    map_status, parse_signature_formats, SignatureFormat, SignatureStatus,
+  };
+
+  #[test]
+  fn test_parse_signature_formats() {
+    let raw = "commit 831e8e
+tree 3570a2
+author a <a@b> 1792380436 +0000
+committer a <a@b> 1792380436 +0000
+gpgsig -----BEGIN SSH SIGNATURE-----
+ U1NIU0lHAAAAAQAAADMAAAALc3NoLWVkMjU1MTkAAAAgB6ELh4zdnOIY5HxErTZl
+ -----END SSH SIGNATURE-----
+
+    gpgsig in the message doesn't count
+
+commit 53738d
+tree 3570a2
+author a <a@b> 1792378887 +0000
+committer a <a@b> 1792378887 +0000
+gpgsig -----BEGIN PGP SIGNATURE-----
+ iQEzBAABCAAdFiEE
+ -----END PGP SIGNATURE-----
+
+    pgp
+
+commit 4d8ca3
+tree 3570a2
+author a <a@b> 1792378887 +0000
+committer a <a@b> 1792378887 +0000
+
+    unsigned
+";
+    let formats = parse_signature_formats(raw);
+
+    assert_eq!(formats.len(), 2);
+    assert_eq!(formats.get("831e8e"), Some(&SignatureFormat::Ssh));
+    assert_eq!(formats.get("53738d"), Some(&SignatureFormat::Gpg));
+  }
+
+  #[test]
+  fn test_map_status() {
+    assert_eq!(map_status("G", true), SignatureStatus::Good);
+    assert_eq!(map_status("N", true), SignatureStatus::CannotCheck);
+    assert_eq!(map_status("N", false), SignatureStatus::Unsigned);
+  }
+}
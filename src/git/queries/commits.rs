@@ -4,12 +4,13 @@ use crate::git::queries::commit_calcs::{
   get_commit_map_cloned,
 };
 use crate::git::queries::commit_filters::{apply_commit_filters, CommitFilter};
-use crate::git::queries::commits_parsers::{
-  PRETTY_FORMATTED, P_COMMITS, P_COMMIT_ROW, P_ID_LIST,
-};
+use crate::git::queries::commits_cache::{load_commits_from_disk, write_commits_cache};
+use crate::git::queries::commits_parsers::{P_COMMITS, P_COMMIT_ROW, P_ID_LIST};
 use crate::git::queries::refs::head_info::{calc_head_info, HeadInfo};
+use crate::git::queries::refs::native_refs::{load_native_refs, log_ref_args};
 use crate::git::queries::refs::{finish_properties_on_refs, get_ref_info_from_commits};
 use crate::git::queries::stashes::load_stashes;
+use crate::git::repo_state::commits_changed;
 use crate::git::run_git::{run_git_err, RunGitOptions};
 use crate::git::store::{PathString, STORE};
 use crate::parser::parse_all_err;
@@ -21,7 +22,7 @@ use ahash::AHashMap;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::HashSet;
-use std::thread;
+use std::{slice, thread};
 use ts_rs::TS;
 
 #[derive(Debug, Deserialize, Serialize, TS)]
@@ -33,35 +34,30 @@ pub struct TopCommitOptions {
 }
 
 pub fn load_top_commit_for_branch(options: &TopCommitOptions) -> R<CommitInfo> {
+  load_single_commit(&options.repo_path, &options.branch_name)
+}
+
+pub fn load_head_commit(options: &ReqOptions) -> R<CommitInfo> {
+  load_single_commit(&options.repo_path, "HEAD")
+}
+
+fn load_single_commit(repo_path: &str, rev: &str) -> R<CommitInfo> {
+  let native_refs = load_native_refs(repo_path).ok();
+  let [decorate, format] = log_ref_args(&native_refs);
+
   let out = run_git_err(RunGitOptions {
-    args: [
-      "log",
-      &options.branch_name,
-      "--decorate=full",
-      PRETTY_FORMATTED,
-      "-n1",
-      "--date=raw",
-    ],
-    repo_path: &options.repo_path,
+    args: ["log", rev, decorate, format, "-n1", "--date=raw"],
+    repo_path,
   })?
   .stdout;
 
-  parse_all_err(P_COMMIT_ROW, out.as_str())
-}
+  let mut commit = parse_all_err(P_COMMIT_ROW, out.as_str())?;
 
-pub fn load_head_commit(options: &ReqOptions) -> R<CommitInfo> {
-  let out = run_git_err(RunGitOptions {
-    args: [
-      "log",
-      "--decorate=full",
-      PRETTY_FORMATTED,
-      "-n1",
-      "--date=raw",
-    ],
-    repo_path: &options.repo_path,
-  })?;
+  if let Some(native_refs) = native_refs {
+    native_refs.attach_to_commits(repo_path, slice::from_mut(&mut commit));
+  }
 
-  parse_all_err(P_COMMIT_ROW, out.stdout.as_str())
+  Ok(commit)
 }
 
 #[derive(Debug, Clone, Deserialize, TS)]
@@ -116,18 +112,33 @@ pub fn convert_commit(commit_info: CommitInfo) -> Commit {
   }
 }
 
-fn load_commits_unfiltered(
+pub(crate) fn load_commits_unfiltered(
   repo_path: &PathString,
   num_commits: u32,
   cache_only: bool,
   skip_stashes: bool,
 ) -> R<(Vec<Commit>, Vec<RefInfo>)> {
-  if cache_only {
-    if let Some(commits) = STORE.get_commits_and_refs(repo_path) {
-      return Ok(commits);
+  match STORE.get_commits_and_refs(repo_path) {
+    Some(commits) if cache_only && !commits_changed(repo_path) => return Ok(commits),
+    // First load since launch, only fetch what's changed since the disk cache.
+    None => {
+      if let Some(Ok((commits, refs, _))) =
+        load_commits_from_disk(repo_path, num_commits, skip_stashes)
+      {
+        return Ok((commits, refs));
+      }
     }
+    _ => {}
   }
 
+  load_commits_from_git(repo_path, num_commits, skip_stashes)
+}
+
+pub(crate) fn load_commits_from_git(
+  repo_path: &PathString,
+  num_commits: u32,
+  skip_stashes: bool,
+) -> R<(Vec<Commit>, Vec<RefInfo>)> {
   let mut commits = if skip_stashes {
     load_commits(repo_path, num_commits)?
   } else {
@@ -145,13 +156,7 @@ fn load_commits_unfiltered(
       commits.append(&mut stashes);
     }
 
-    commits.sort_by(|a, b| {
-      if !b.stash_id.is_empty() || !a.stash_id.is_empty() {
-        b.date.ms.partial_cmp(&a.date.ms).unwrap_or(Ordering::Equal)
-      } else {
-        Ordering::Equal
-      }
-    });
+    commits.sort_by(|a, b| stash_order(a.date.ms, &a.stash_id, b.date.ms, &b.stash_id));
 
     commits
   };
@@ -164,19 +169,37 @@ fn load_commits_unfiltered(
   let commits = get_commits_from_info(commits);
 
   STORE.insert_commits(repo_path, &commits, &refs);
+  write_commits_cache(repo_path, &commits, &refs);
 
   Ok((commits, refs))
 }
 
+// Places stashes by date, without moving other commits relative to each other.
+pub(crate) fn stash_order(
+  a_ms: usize,
+  a_stash: &str,
+  b_ms: usize,
+  b_stash: &str,
+) -> Ordering {
+  if !b_stash.is_empty() || !a_stash.is_empty() {
+    b_ms.partial_cmp(&a_ms).unwrap_or(Ordering::Equal)
+  } else {
+    Ordering::Equal
+  }
+}
+
 pub fn load_commits(repo_path: &PathString, num: u32) -> R<Vec<CommitInfo>> {
+  let native_refs = load_native_refs(repo_path).ok();
+  let [decorate, format] = log_ref_args(&native_refs);
+
   let out = run_git_err(RunGitOptions {
     args: [
       "log",
       "--branches",
       "--tags",
       "--remotes",
-      "--decorate=full",
-      PRETTY_FORMATTED,
+      decorate,
+      format,
       format!("-n{}", num).as_str(),
       "--date=raw",
     ],
@@ -184,9 +207,15 @@ pub fn load_commits(repo_path: &PathString, num: u32) -> R<Vec<CommitInfo>> {
   })?
   .stdout;
 
-  time_result!(format!("parse commits. Length {}", out.len()), {
+  let mut commits = time_result!(format!("parse commits. Length {}", out.len()), {
     parse_all_err(P_COMMITS, &out)
-  })
+  })?;
+
+  if let Some(native_refs) = native_refs {
+    native_refs.attach_to_commits(repo_path, &mut commits);
+  }
+
+  Ok(commits)
 }
 
 #[derive(Debug, Deserialize, TS)]
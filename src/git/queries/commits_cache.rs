@@ -0,0 +1,351 @@
+use std::fs::{create_dir_all, read, remove_file, rename, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::UNIX_EPOCH;
+use std::{io, process, thread};
+
+use ahash::{AHashMap, AHashSet};
+
+use crate::git::git_types::{Commit, DateResult, RefInfo};
+use crate::git::queries::commits_incremental::{update_commits, CommitsUpdate};
+use crate::git::queries::patches::cache::{generate_cache_id, get_cache_sub_dir};
+use crate::git::store::{PathString, STORE};
+use crate::global;
+use crate::server::request_util::R;
+use crate::util::bin_io::{checksum, BinReader, BinWriter};
+use crate::util::global::Global;
+
+/*
+Parsed commits are kept on disk between launches, so opening a big repo only needs
+the commits reachable from ref tips that have moved since. Stashes, refs and indexes
+aren't stored, they're cheap to get again.
+ */
+
+const MAGIC: &[u8; 4] = b"GFCC";
+const VERSION: u32 = 1;
+
+static NEXT_TMP_FILE: AtomicU32 = AtomicU32::new(0);
+
+struct CachedCommits {
+  tip_ids: AHashSet<String>,
+  packed_refs_mtime: u64,
+  commits: Vec<Commit>,
+}
+
+// Tips and number of commits last written per repo, so unchanged commits aren't written
+// again on every refresh.
+static LAST_WRITTEN: Global<AHashMap<PathString, (Vec<String>, usize)>> =
+  global!(AHashMap::new());
+
+/*
+Returns None if there's no usable cache. packed-refs is rewritten when refs are
+packed or pruned, so if it's changed since the cache was written we can't trust
+the cached tips to still describe the commits.
+ */
+pub(crate) fn load_commits_from_disk(
+  repo_path: &PathString,
+  num_commits: u32,
+  skip_stashes: bool,
+) -> Option<R<CommitsUpdate>> {
+  let cached = read_commits_cache(repo_path)?;
+
+  if cached.packed_refs_mtime != packed_refs_mtime(repo_path) {
+    return None;
+  }
+
+  let mut tips: Vec<String> = cached.tip_ids.iter().cloned().collect();
+  tips.sort();
+  LAST_WRITTEN.insert(repo_path.clone(), (tips, cached.commits.len()));
+
+  Some(update_commits(
+    repo_path,
+    num_commits,
+    skip_stashes,
+    &cached.commits,
+    &[],
+    &cached.tip_ids,
+  ))
+}
+
+pub(crate) fn write_commits_cache(
+  repo_path: &PathString,
+  commits: &[Commit],
+  refs: &[RefInfo],
+) {
+  let Some(path) = get_cache_file(repo_path) else {
+    return;
+  };
+
+  let mut tips: Vec<String> = refs.iter().map(|r| r.commit_id.clone()).collect();
+  tips.sort();
+  tips.dedup();
+
+  let commits: Vec<&Commit> = commits.iter().filter(|c| c.stash_id.is_empty()).collect();
+  let key = (tips, commits.len());
+
+  if LAST_WRITTEN.get_by_key(repo_path).as_ref() == Some(&key) {
+    return;
+  }
+
+  let data = encode_commits(&key.0, packed_refs_mtime(repo_path), &commits);
+
+  LAST_WRITTEN.insert(repo_path.clone(), key);
+
+  thread::spawn(move || {
+    if let Some(dir) = path.parent() {
+      let _ = create_dir_all(dir);
+    }
+    let _ = write_atomic(&path, &data);
+  });
+}
+
+// A reader, or a crash part way through, never sees half a file. Each write gets its
+// own temp file as writes can overlap.
+fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+  let tmp_path = path.with_extension(format!(
+    "{}.{}.tmp",
+    process::id(),
+    NEXT_TMP_FILE.fetch_add(1, Ordering::Relaxed)
+  ));
+
+  let result = File::create(&tmp_path)
+    .and_then(|mut file| file.write_all(data))
+    .and_then(|_| rename(&tmp_path, path));
+
+  if result.is_err() {
+    let _ = remove_file(&tmp_path);
+  }
+
+  result
+}
+
+fn read_commits_cache(repo_path: &str) -> Option<CachedCommits> {
+  let data = read(get_cache_file(repo_path)?).ok()?;
+
+  decode_commits(&data)
+}
+
+fn get_cache_file(repo_path: &str) -> Option<PathBuf> {
+  Some(
+    get_cache_sub_dir("commits")?.join(format!("{}.bin", generate_cache_id(repo_path))),
+  )
+}
+
+fn packed_refs_mtime(repo_path: &str) -> u64 {
+  let git_dir = match STORE.get_repo_path(repo_path) {
+    Ok(p) => p.common_dir,
+    Err(_) => Path::new(repo_path).join(".git"),
+  };
+
+  git_dir
+    .join("packed-refs")
+    .metadata()
+    .and_then(|m| m.modified())
+    .ok()
+    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+    .map(|d| d.as_millis() as u64)
+    .unwrap_or(0)
+}
+
+/*
+Layout: magic, version, checksum of the rest, packed-refs mtime, tip ids, then a table
+of unique authors and the commits, which refer to authors by index.
+ */
+fn encode_commits(
+  tip_ids: &[String],
+  packed_refs_mtime: u64,
+  commits: &[&Commit],
+) -> Vec<u8> {
+  let mut w = BinWriter::new();
+
+  w.u64(packed_refs_mtime);
+  w.u32(tip_ids.len() as u32);
+  for id in tip_ids {
+    w.hex_id(id);
+  }
+
+  let mut authors: Vec<(&str, &str)> = Vec::new();
+  let mut author_indexes: AHashMap<(&str, &str), u32> = AHashMap::new();
+
+  let commit_authors: Vec<u32> = commits
+    .iter()
+    .map(|c| {
+      let key = (c.author.as_str(), c.email.as_str());
+
+      *author_indexes.entry(key).or_insert_with(|| {
+        authors.push(key);
+        (authors.len() - 1) as u32
+      })
+    })
+    .collect();
+
+  w.u32(authors.len() as u32);
+  for (author, email) in &authors {
+    w.str(author);
+    w.str(email);
+  }
+
+  w.u32(commits.len() as u32);
+  for (c, author) in commits.iter().zip(commit_authors) {
+    w.hex_id(&c.id);
+    w.u32(author);
+    w.u64(c.date.ms as u64);
+    w.i32(c.date.adjustment);
+    w.u8(c.parent_ids.len() as u8);
+    for id in &c.parent_ids {
+      w.hex_id(id);
+    }
+    w.bool(c.is_merge);
+    w.str(&c.message);
+  }
+
+  let mut out = BinWriter::new();
+  out.buf.extend_from_slice(MAGIC);
+  out.u32(VERSION);
+  out.u64(checksum(&w.buf));
+  out.buf.extend_from_slice(&w.buf);
+
+  out.buf
+}
+
+fn decode_commits(data: &[u8]) -> Option<CachedCommits> {
+  let mut r = BinReader::new(data);
+
+  if r.bytes(MAGIC.len())? != MAGIC || r.u32()? != VERSION {
+    return None;
+  }
+
+  let sum = r.u64()?;
+  let payload = &data[r.pos()..];
+
+  if checksum(payload) != sum {
+    return None;
+  }
+
+  let mut r = BinReader::new(payload);
+  let packed_refs_mtime = r.u64()?;
+
+  let num_tips = r.u32()?;
+  let mut tip_ids = AHashSet::new();
+  for _ in 0..num_tips {
+    tip_ids.insert(r.hex_id()?);
+  }
+
+  let num_authors = r.u32()? as usize;
+  let mut authors = Vec::new();
+  for _ in 0..num_authors {
+    authors.push((r.str()?, r.str()?));
+  }
+
+  let num_commits = r.u32()? as usize;
+  let mut commits = Vec::with_capacity(num_commits.min(data.len()));
+  for index in 0..num_commits {
+    let id = r.hex_id()?;
+    let (author, email) = authors.get(r.u32()? as usize)?.clone();
+    let date = DateResult {
+      ms: r.u64()? as usize,
+      adjustment: r.i32()?,
+    };
+    let num_parents = r.u8()?;
+    let mut parent_ids = Vec::new();
+    for _ in 0..num_parents {
+      parent_ids.push(r.hex_id()?);
+    }
+
+    commits.push(Commit {
+      author,
+      email,
+      date,
+      id,
+      index,
+      parent_ids,
+      is_merge: r.bool()?,
+      message: r.str()?,
+      stash_id: String::new(),
+      refs: Vec::new(),
+      filtered: false,
+      num_skipped: 0,
+    });
+  }
+
+  if !r.at_end() {
+    return None;
+  }
+
+  Some(CachedCommits {
+    tip_ids,
+    packed_refs_mtime,
+    commits,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs::{read, read_dir};
+
+  use crate::git::git_types::{make_commit, Commit, DateResult};
+  use crate::git::queries::commits_cache::{
+    decode_commits, encode_commits, write_atomic,
+  };
+  use crate::util::test_util::TempDir;
+
+  fn authored(id: &str, parent_ids: &[&str], author: &str) -> Commit {
+    Commit {
+      author: author.to_string(),
+      email: format!("{}@example.com", author),
+      date: DateResult {
+        ms: 1_650_000_000_000,
+        adjustment: -60,
+      },
+      ..make_commit(id, parent_ids)
+    }
+  }
+
+  #[test]
+  fn test_round_trip() {
+    let a = authored("aa11", &["bb22", "cc33"], "Ann");
+    let b = authored("bb22", &["cc33"], "Bob");
+    let c = authored("cc33", &[], "Ann");
+    let tips = vec![String::from("aa11")];
+
+    let data = encode_commits(&tips, 42, &[&a, &b, &c]);
+    let cached = decode_commits(&data).unwrap();
+
+    assert_eq!(cached.packed_refs_mtime, 42);
+    assert!(cached.tip_ids.contains("aa11"));
+    assert_eq!(cached.commits.len(), 3);
+    assert_eq!(cached.commits[0].parent_ids, a.parent_ids);
+    assert!(cached.commits[0].is_merge);
+    assert_eq!(cached.commits[1].author, "Bob");
+    assert_eq!(cached.commits[2].email, "Ann@example.com");
+    assert_eq!(cached.commits[2].index, 2);
+    assert_eq!(cached.commits[2].date, c.date);
+    assert_eq!(cached.commits[1].message, b.message);
+  }
+
+  #[test]
+  fn test_corrupted() {
+    let a = authored("aa11", &[], "Ann");
+    let mut data = encode_commits(&[], 0, &[&a]);
+
+    let last = data.len() - 1;
+    data[last] ^= 1;
+    assert!(decode_commits(&data).is_none());
+
+    assert!(decode_commits(&data[..10]).is_none());
+  }
+
+  #[test]
+  fn test_write_atomic() {
+    let dir = TempDir::new("commits_cache_write");
+    let path = dir.path.join("cache.bin");
+
+    write_atomic(&path, b"first").unwrap();
+    write_atomic(&path, b"second").unwrap();
+
+    assert_eq!(read(&path).unwrap(), b"second");
+    // No temp files left behind.
+    assert_eq!(read_dir(&dir.path).unwrap().count(), 1);
+  }
+}
@@ -0,0 +1,425 @@
+use ahash::{AHashMap, AHashSet};
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::git::git_types::{Commit, CommitInfo, RefInfo};
+use crate::git::queries::commit_filters::apply_commit_filters;
+use crate::git::queries::commits::{
+  convert_commit, load_commits_from_git, stash_order, ReqCommitsOptions2,
+};
+use crate::git::queries::commits_cache::{load_commits_from_disk, write_commits_cache};
+use crate::git::queries::commits_parsers::P_COMMITS;
+use crate::git::queries::refs::native_refs::{load_native_refs, log_ref_args};
+use crate::git::queries::refs::{finish_properties_on_refs, get_ref_info_from_commits};
+use crate::git::queries::stashes::load_stashes;
+use crate::git::repo_state::check_repo_state;
+use crate::git::run_git::{run_git_err, run_git_with_input, RunGitOptions};
+use crate::git::store::{PathString, STORE};
+use crate::parser::parse_all_err;
+use crate::server::request_util::R;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct CommitChanges {
+  pub added: Vec<String>,
+  pub removed: Vec<String>,
+  pub refs_changed: bool,
+  // The cache couldn't be updated in place, e.g. after a force push or deleting an
+  // unmerged branch.
+  pub full_reload: bool,
+}
+
+pub(crate) type CommitsUpdate = (Vec<Commit>, Vec<RefInfo>, CommitChanges);
+
+/*
+Like load_commits_and_refs, but reuses the cached commits. Only commits reachable from
+new ref tips and not from the old ones are loaded from git. Falls back to a full load
+when commits may have become unreachable.
+ */
+pub fn load_commits_and_refs_incremental(
+  options: &ReqCommitsOptions2,
+) -> R<(Vec<Commit>, Vec<RefInfo>, CommitChanges)> {
+  let ReqCommitsOptions2 {
+    repo_path,
+    num_commits,
+    filters,
+    skip_stashes,
+    ..
+  } = options;
+
+  let (commits, refs, changes) =
+    load_commits_unfiltered_incremental(repo_path, *num_commits, *skip_stashes)?;
+
+  Ok((
+    apply_commit_filters(repo_path, commits, &refs, filters),
+    refs,
+    changes,
+  ))
+}
+
+fn load_commits_unfiltered_incremental(
+  repo_path: &PathString,
+  num_commits: u32,
+  skip_stashes: bool,
+) -> R<CommitsUpdate> {
+  // Drops what was worked out from the old refs. The old commits are what we diff with.
+  check_repo_state(repo_path);
+
+  let Some((old_commits, old_refs)) = STORE.get_commits_and_refs(repo_path) else {
+    if let Some(Ok(result)) = load_commits_from_disk(repo_path, num_commits, skip_stashes)
+    {
+      return Ok(result);
+    }
+    return full_reload(repo_path, num_commits, skip_stashes, &[]);
+  };
+
+  let old_tip_ids = old_refs.iter().map(|r| r.commit_id.clone()).collect();
+
+  match update_commits(
+    repo_path,
+    num_commits,
+    skip_stashes,
+    &old_commits,
+    &old_refs,
+    &old_tip_ids,
+  ) {
+    Ok(update) => Ok(update),
+    // Otherwise whatever broke the update would break every one after it.
+    Err(_) => full_reload(repo_path, num_commits, skip_stashes, &old_commits),
+  }
+}
+
+// Brings old_commits up to date, given the ref tips they were loaded from.
+pub(crate) fn update_commits(
+  repo_path: &PathString,
+  num_commits: u32,
+  skip_stashes: bool,
+  old_commits: &[Commit],
+  old_refs: &[RefInfo],
+  old_tip_ids: &AHashSet<String>,
+) -> R<CommitsUpdate> {
+  let tips = load_ref_tips(repo_path)?;
+
+  let old_tip_ids: AHashSet<&str> = old_tip_ids.iter().map(|id| id.as_str()).collect();
+  let new_tip_ids: AHashSet<&str> = tips.iter().map(|c| c.id.as_str()).collect();
+
+  let new_commits = if new_tip_ids.is_subset(&old_tip_ids) {
+    Vec::new()
+  } else {
+    load_new_commits(repo_path, num_commits, &old_tip_ids)?
+  };
+
+  // Too many to join up with the cached commits.
+  if new_commits.len() >= num_commits as usize {
+    return full_reload(repo_path, num_commits, skip_stashes, old_commits);
+  }
+
+  // Keep any extra pages from load_more_commits.
+  let num_old = old_commits.iter().filter(|c| c.stash_id.is_empty()).count();
+  let num_to_keep = num_old.max(num_commits as usize);
+  let has_new_commits = !new_commits.is_empty();
+
+  let mut commits: Vec<Commit> = new_commits
+    .into_iter()
+    .map(convert_commit)
+    .chain(
+      old_commits
+        .iter()
+        .filter(|c| c.stash_id.is_empty())
+        .cloned(),
+    )
+    .collect();
+
+  // New commits can belong anywhere, e.g. a fetched branch with older dates.
+  if has_new_commits {
+    match order_like_git(repo_path, commits, num_to_keep)? {
+      Some(ordered) => commits = ordered,
+      None => return full_reload(repo_path, num_commits, skip_stashes, old_commits),
+    }
+  }
+
+  let parent_ids: AHashSet<&str> = commits
+    .iter()
+    .flat_map(|c| c.parent_ids.iter().map(|p| p.as_str()))
+    .collect();
+
+  // A tip that's gone and isn't anyone's parent may have taken commits with it.
+  if old_tip_ids
+    .iter()
+    .any(|id| !new_tip_ids.contains(id) && !parent_ids.contains(id))
+  {
+    return full_reload(repo_path, num_commits, skip_stashes, old_commits);
+  }
+
+  let num_stashes = if skip_stashes {
+    0
+  } else {
+    let stashes = load_stashes(repo_path)?;
+    let num = stashes.len();
+
+    commits.extend(stashes.into_iter().map(convert_commit));
+    commits.sort_by(|a, b| stash_order(a.date.ms, &a.stash_id, b.date.ms, &b.stash_id));
+
+    num
+  };
+
+  commits.truncate(num_to_keep + num_stashes);
+
+  let ids: AHashSet<&str> = commits.iter().map(|c| c.id.as_str()).collect();
+  let tips: Vec<CommitInfo> = tips
+    .into_iter()
+    .filter(|c| ids.contains(c.id.as_str()))
+    .collect();
+
+  let ref_ids: AHashMap<&str, Vec<String>> = tips
+    .iter()
+    .map(|c| {
+      (
+        c.id.as_str(),
+        c.refs
+          .iter()
+          .filter(|r| !r.full_name.contains("HEAD"))
+          .map(|r| r.id.clone())
+          .collect(),
+      )
+    })
+    .collect();
+
+  for (i, c) in commits.iter_mut().enumerate() {
+    c.index = i;
+    c.refs = ref_ids.get(c.id.as_str()).cloned().unwrap_or_default();
+  }
+
+  let refs = finish_properties_on_refs(get_ref_info_from_commits(&tips), repo_path);
+
+  let mut changes = diff_commits(old_commits, &commits);
+  changes.refs_changed = refs_changed(old_refs, &refs);
+
+  STORE.insert_commits(repo_path, &commits, &refs);
+  write_commits_cache(repo_path, &commits, &refs);
+
+  Ok((commits, refs, changes))
+}
+
+pub(crate) fn full_reload(
+  repo_path: &PathString,
+  num_commits: u32,
+  skip_stashes: bool,
+  old_commits: &[Commit],
+) -> R<CommitsUpdate> {
+  let (commits, refs) = load_commits_from_git(repo_path, num_commits, skip_stashes)?;
+
+  let mut changes = diff_commits(old_commits, &commits);
+  changes.refs_changed = true;
+  changes.full_reload = true;
+
+  Ok((commits, refs, changes))
+}
+
+// Just the commits refs point to, with their decorations.
+fn load_ref_tips(repo_path: &str) -> R<Vec<CommitInfo>> {
+  let native_refs = load_native_refs(repo_path).ok();
+  let [decorate, format] = log_ref_args(&native_refs);
+
+  let out = run_git_err(RunGitOptions {
+    repo_path,
+    args: [
+      "log",
+      "--no-walk=unsorted",
+      "--branches",
+      "--tags",
+      "--remotes",
+      decorate,
+      format,
+      "--date=raw",
+    ],
+  })?
+  .stdout;
+
+  let mut tips = parse_all_err(P_COMMITS, &out)?;
+
+  if let Some(native_refs) = native_refs {
+    native_refs.attach_to_commits(repo_path, &mut tips);
+  }
+
+  Ok(tips)
+}
+
+/*
+Puts commits in the order "git log" would give them, so an update matches a full load.
+Git orders by committer date, which we don't keep, so we ask for just the ids. None if
+git lists a commit we don't have.
+ */
+fn order_like_git(
+  repo_path: &str,
+  commits: Vec<Commit>,
+  num: usize,
+) -> R<Option<Vec<Commit>>> {
+  let out = run_git_err(RunGitOptions {
+    repo_path,
+    args: [
+      "rev-list",
+      "--branches",
+      "--tags",
+      "--remotes",
+      &format!("-n{}", num),
+    ],
+  })?
+  .stdout;
+
+  if out.trim().is_empty() {
+    return Ok(None);
+  }
+
+  let mut by_id: AHashMap<String, Commit> =
+    commits.into_iter().map(|c| (c.id.clone(), c)).collect();
+
+  Ok(out.lines().map(|id| by_id.remove(id.trim())).collect())
+}
+
+// There can be a lot of tags, so the old tips are passed on stdin. Old tips can be gone
+// from the object store after a prune and gc, git would stop with "bad object" for those.
+fn load_new_commits(
+  repo_path: &str,
+  num_commits: u32,
+  old_tip_ids: &AHashSet<&str>,
+) -> R<Vec<CommitInfo>> {
+  let input: String = old_tip_ids.iter().map(|id| format!("^{}\n", id)).collect();
+  let native_refs = load_native_refs(repo_path).ok();
+  let [decorate, format] = log_ref_args(&native_refs);
+
+  let out = run_git_with_input(
+    RunGitOptions {
+      repo_path,
+      args: [
+        "log",
+        "--branches",
+        "--tags",
+        "--remotes",
+        decorate,
+        format,
+        &format!("-n{}", num_commits),
+        "--date=raw",
+        "--ignore-missing",
+        "--stdin",
+      ],
+    },
+    input.as_bytes(),
+  )?;
+
+  let mut commits = parse_all_err(P_COMMITS, &String::from_utf8_lossy(&out))?;
+
+  if let Some(native_refs) = native_refs {
+    native_refs.attach_to_commits(repo_path, &mut commits);
+  }
+
+  Ok(commits)
+}
+
+fn diff_commits(old: &[Commit], new: &[Commit]) -> CommitChanges {
+  let old_ids: AHashSet<&str> = old.iter().map(|c| c.id.as_str()).collect();
+  let new_ids: AHashSet<&str> = new.iter().map(|c| c.id.as_str()).collect();
+
+  CommitChanges {
+    added: new
+      .iter()
+      .filter(|c| !old_ids.contains(c.id.as_str()))
+      .map(|c| c.id.clone())
+      .collect(),
+    removed: old
+      .iter()
+      .filter(|c| !new_ids.contains(c.id.as_str()))
+      .map(|c| c.id.clone())
+      .collect(),
+    refs_changed: false,
+    full_reload: false,
+  }
+}
+
+fn refs_changed(old: &[RefInfo], new: &[RefInfo]) -> bool {
+  let old: AHashSet<(&str, &str)> = old
+    .iter()
+    .map(|r| (r.id.as_str(), r.commit_id.as_str()))
+    .collect();
+
+  old.len() != new.len()
+    || new
+      .iter()
+      .any(|r| !old.contains(&(r.id.as_str(), r.commit_id.as_str())))
+}
+
+#[cfg(test)]
+mod tests {
+  use ahash::AHashSet;
+
+  use crate::git::git_types::{make_commit, Commit};
+  use crate::git::queries::commits::load_commits_from_git;
+  use crate::git::queries::commits_incremental::{diff_commits, update_commits};
+  use crate::util::test_util::{TempDir, ID_A};
+
+  #[test]
+  fn test_diff_commits() {
+    let old = vec![make_commit("b", &[]), make_commit("a", &[])];
+    let new = vec![make_commit("c", &[]), make_commit("b", &[])];
+
+    let changes = diff_commits(&old, &new);
+
+    assert_eq!(changes.added, vec!["c"]);
+    assert_eq!(changes.removed, vec!["a"]);
+  }
+
+  fn ids(commits: &[Commit]) -> Vec<&str> {
+    commits.iter().map(|c| c.id.as_str()).collect()
+  }
+
+  #[test]
+  fn test_update_matches_full_load() {
+    let repo = TempDir::with_repo("incremental");
+    let repo_path = repo.repo_path();
+
+    repo.commit("c1", 1_600_001_000);
+    repo.commit("c2", 1_600_003_000);
+    repo.commit("c3", 1_600_005_000);
+
+    let (old_commits, old_refs) = load_commits_from_git(&repo_path, 100, true).unwrap();
+    let old_tips: AHashSet<String> =
+      old_refs.iter().map(|r| r.commit_id.clone()).collect();
+
+    // Like fetching a branch that was worked on a while ago.
+    repo.git(&["switch", "-q", "-c", "old-work", "HEAD~2"]);
+    repo.commit("o1", 1_600_002_000);
+    repo.commit("o2", 1_600_002_500);
+
+    let (updated, _, changes) =
+      update_commits(&repo_path, 100, true, &old_commits, &old_refs, &old_tips).unwrap();
+    let (full, _) = load_commits_from_git(&repo_path, 100, true).unwrap();
+
+    assert!(!changes.full_reload);
+    assert_eq!(changes.added.len(), 2);
+    assert_eq!(ids(&updated), ids(&full));
+    assert_eq!(updated[2].message.trim(), "o2");
+  }
+
+  #[test]
+  fn test_update_with_pruned_tip() {
+    let repo = TempDir::with_repo("incremental_pruned");
+    let repo_path = repo.repo_path();
+
+    repo.commit("c1", 1_600_001_000);
+
+    let (old_commits, old_refs) = load_commits_from_git(&repo_path, 100, true).unwrap();
+    let mut old_tips: AHashSet<String> =
+      old_refs.iter().map(|r| r.commit_id.clone()).collect();
+    // A tip that's no longer in the object store.
+    old_tips.insert(ID_A.to_string());
+
+    repo.commit("c2", 1_600_002_000);
+
+    let (updated, _, changes) =
+      update_commits(&repo_path, 100, true, &old_commits, &old_refs, &old_tips).unwrap();
+
+    assert_eq!(changes.added.len(), 1);
+    assert_eq!(updated.len(), 2);
+  }
+}
@@ -0,0 +1,169 @@
+use ahash::AHashSet;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::f;
+use crate::git::git_types::{Commit, RefInfo};
+use crate::git::queries::commits::{convert_commit, stash_order};
+use crate::git::queries::commits_parsers::P_COMMITS;
+use crate::git::queries::refs::native_refs::{load_native_refs, log_ref_args};
+use crate::git::queries::refs::{finish_properties_on_refs, get_ref_info_from_commits};
+use crate::git::run_git::{run_git_with_input, RunGitOptions};
+use crate::git::store::STORE;
+use crate::parser::parse_all_err;
+use crate::server::request_util::{ES, R};
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct LoadMoreCommitsOptions {
+  pub repo_path: String,
+  // Id of the last commit the client has (ignoring stashes). Guards against the
+  // cache having been reloaded since.
+  pub after_commit_id: String,
+  pub num_commits: u32,
+}
+
+#[derive(Debug, Clone, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct CommitsPage {
+  // Replace commits from this index on. Stashes older than the previous page's last
+  // commit move into this page, so it can be before the end of what the client has.
+  pub start_index: usize,
+  pub commits: Vec<Commit>,
+  // All refs, including ones on commits in this page.
+  pub refs: Vec<RefInfo>,
+  pub has_more: bool,
+}
+
+/*
+Loads the next page of history after the cached commits and appends it to the cache.
+The cache isn't always a prefix of "git log" (incremental loads merge new commits in),
+so we can't skip by count. Instead we continue from the parents of the cached commits
+that aren't loaded yet. Unfiltered only, filters need the whole list.
+ */
+pub fn load_more_commits(options: &LoadMoreCommitsOptions) -> R<CommitsPage> {
+  let LoadMoreCommitsOptions {
+    repo_path,
+    after_commit_id,
+    num_commits,
+  } = options;
+
+  let (mut commits, refs) = STORE
+    .get_commits_and_refs(repo_path)
+    .ok_or(ES::from("load_more_commits: Commits not loaded."))?;
+
+  let last = commits
+    .iter()
+    .rposition(|c| c.stash_id.is_empty())
+    .ok_or(ES::from("load_more_commits: No commits to continue from."))?;
+
+  if commits[last].id != *after_commit_id {
+    return Err(ES::from(
+      "load_more_commits: Commits have changed. Reload them first.",
+    ));
+  }
+
+  let boundary = find_boundary(&commits);
+
+  // "git log --stdin" with no input would log HEAD.
+  if boundary.is_empty() {
+    return Ok(CommitsPage {
+      start_index: last + 1,
+      commits: commits.split_off(last + 1),
+      refs,
+      has_more: false,
+    });
+  }
+
+  let native_refs = load_native_refs(repo_path).ok();
+  let [decorate, format] = log_ref_args(&native_refs);
+  let input: String = boundary.iter().map(|id| f!("{}\n", id)).collect();
+
+  let out = run_git_with_input(
+    RunGitOptions {
+      repo_path,
+      args: [
+        "log",
+        decorate,
+        format,
+        &f!("-n{}", num_commits),
+        "--date=raw",
+        "--stdin",
+      ],
+    },
+    input.as_bytes(),
+  )?;
+
+  let mut new_commits = parse_all_err(P_COMMITS, &String::from_utf8_lossy(&out))?;
+  let has_more = new_commits.len() == *num_commits as usize;
+
+  // A commit with a skewed date can be loaded before its descendants.
+  let loaded: AHashSet<&str> = commits.iter().map(|c| c.id.as_str()).collect();
+  new_commits.retain(|c| !loaded.contains(c.id.as_str()));
+
+  if let Some(native_refs) = native_refs {
+    native_refs.attach_to_commits(repo_path, &mut new_commits);
+  }
+
+  // Refs on the new commits, e.g. older tags and merged branches.
+  let mut all_refs: Vec<RefInfo> = refs;
+  all_refs.extend(get_ref_info_from_commits(&new_commits));
+  let refs = finish_properties_on_refs(all_refs, repo_path);
+
+  let start_index = last + 1;
+  let mut page: Vec<Commit> = commits.split_off(start_index);
+  page.extend(new_commits.into_iter().map(convert_commit));
+  page.sort_by(|a, b| stash_order(a.date.ms, &a.stash_id, b.date.ms, &b.stash_id));
+
+  for (i, c) in page.iter_mut().enumerate() {
+    c.index = start_index + i;
+  }
+
+  commits.extend(page.iter().cloned());
+  STORE.insert_commits(repo_path, &commits, &refs);
+
+  Ok(CommitsPage {
+    start_index,
+    commits: page,
+    refs,
+    has_more,
+  })
+}
+
+// Parents of loaded commits that aren't loaded themselves.
+fn find_boundary(commits: &[Commit]) -> Vec<String> {
+  let loaded: AHashSet<&str> = commits.iter().map(|c| c.id.as_str()).collect();
+  let mut seen: AHashSet<&str> = AHashSet::new();
+
+  commits
+    .iter()
+    .filter(|c| c.stash_id.is_empty())
+    .flat_map(|c| c.parent_ids.iter())
+    .filter(|id| !loaded.contains(id.as_str()) && seen.insert(id.as_str()))
+    .cloned()
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::git::git_types::{make_commit, Commit};
+  use crate::git::queries::commits_paging::find_boundary;
+
+  #[test]
+  fn test_find_boundary() {
+    let mut stash = make_commit("s", &["a", "i"]);
+    stash.stash_id = String::from("stash@{0}");
+
+    let commits: Vec<Commit> = vec![
+      stash,
+      make_commit("m", &["a", "b"]),
+      make_commit("a", &["c"]),
+      make_commit("b", &["c", "d"]),
+    ];
+
+    // The stash's index commit isn't history we page through.
+    assert_eq!(find_boundary(&commits), vec!["c", "d"]);
+  }
+}
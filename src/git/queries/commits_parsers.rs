@@ -10,6 +10,9 @@ use crate::{and, character, many, map, or, rep_parser_sep, take_char_while, unti
 const END: &str = "4a41380f-a4e8-4251-9ca2-bf55186ed32a";
 pub const PRETTY_FORMATTED: &str =
   "--pretty=format:%an; %ae; %ad; %H; %P; %B4a41380f-a4e8-4251-9ca2-bf55186ed32a; %d";
+// For when we read the refs ourselves. Still parsed by P_COMMIT_ROW.
+pub const PRETTY_FORMATTED_NO_REFS: &str =
+  "--pretty=format:%an; %ae; %ad; %H; %P; %B4a41380f-a4e8-4251-9ca2-bf55186ed32a; ";
 
 pub const SEP_CHAR: char = ';';
 
@@ -23,7 +26,7 @@ const P_SEP: Parser<char> = map!(and!(WS, character!(SEP_CHAR), WS), |_res: (
 
 const P_EMAIL: Parser<String> = or!(P_GROUP, WS);
 
-const P_DATE: Parser<DateResult> = map!(and!(UNSIGNED_INT, WS, SIGNED_INT), |res: (
+pub(crate) const P_DATE: Parser<DateResult> = map!(and!(UNSIGNED_INT, WS, SIGNED_INT), |res: (
   String,
   String,
   String
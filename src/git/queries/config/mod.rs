@@ -5,6 +5,7 @@ use crate::git::store::{CONFIG, STORE};
 use crate::parser::{parse_all_err, run_parser, ParseOptions};
 use crate::server::git_request::ReqOptions;
 use crate::server::request_util::R;
+use crate::util::repo_cache::{str_size, CacheSize};
 use config_output_parser::{P_CONFIG, P_REMOTE_NAME};
 use serde::Serialize;
 use std::collections::HashMap;
@@ -23,6 +24,16 @@ pub struct GitConfig {
   pub submodules: HashMap<String, String>,
 }
 
+impl CacheSize for GitConfig {
+  fn cache_size(&self) -> usize {
+    [&self.entries, &self.remotes, &self.submodules]
+      .iter()
+      .flat_map(|map| map.iter())
+      .map(|(k, v)| str_size(k) + str_size(v))
+      .sum()
+  }
+}
+
 impl GitConfig {
   pub fn new() -> GitConfig {
     GitConfig {
@@ -63,7 +74,7 @@ pub fn load_full_config(options: &ReqOptions) -> R<GitConfig> {
   let ReqOptions { repo_path } = options;
 
   let repo = STORE.get_repo_path(repo_path)?;
-  let config_path = repo.git_path.join("config");
+  let config_path = repo.common_dir.join("config");
 
   let result_text = if let Ok(text) = read_to_string(config_path) {
     make_config_log(&text)
@@ -119,7 +130,7 @@ pub fn load_full_config(options: &ReqOptions) -> R<GitConfig> {
     submodules,
   };
 
-  CONFIG.insert(repo_path.clone(), config.clone());
+  CONFIG.insert_repo(repo_path, config.clone());
 
   Ok(config)
 }
@@ -0,0 +1,138 @@
+use ahash::AHashMap;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::git::git_types::{CommitInfo, Patch};
+use crate::git::queries::commits_parsers::{PRETTY_FORMATTED, P_COMMITS};
+use crate::git::queries::patches::patch_parsers::P_MANY_PATCHES_WITH_COMMIT_IDS;
+use crate::git::run_git::{run_git_err, RunGitOptions};
+use crate::parser::parse_all_err;
+use crate::server::request_util::R;
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct FileHistoryOptions {
+  pub repo_path: String,
+  pub file_path: String,
+  // Where to start looking back from. Defaults to HEAD.
+  pub commit_id: Option<String>,
+  pub max_count: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct FileHistoryEntry {
+  pub commit: CommitInfo,
+  // The change to the file in this commit. old_file differs from new_file for renames.
+  pub patch: Patch,
+}
+
+/*
+Unlike CommitFilter::File, this isn't limited to the loaded commits and follows the
+file back through renames. Newest first.
+ */
+pub fn load_file_history(options: &FileHistoryOptions) -> R<Vec<FileHistoryEntry>> {
+  let FileHistoryOptions {
+    repo_path,
+    file_path,
+    commit_id,
+    max_count,
+  } = options;
+
+  let mut rev_args = vec![String::from("--follow"), String::from("-M")];
+  if let Some(n) = max_count {
+    rev_args.push(format!("-n{}", n));
+  }
+  if let Some(id) = commit_id {
+    rev_args.push(id.clone());
+  }
+  rev_args.push(String::from("--"));
+  rev_args.push(file_path.clone());
+
+  let out = run_git_err(RunGitOptions {
+    repo_path,
+    args: [
+      vec![
+        String::from("log"),
+        String::from("--pretty=format:%H,"),
+        String::from("--name-status"),
+        String::from("-z"),
+      ],
+      rev_args.clone(),
+    ]
+    .concat(),
+  })?
+  .stdout;
+
+  let mut patches: AHashMap<String, Patch> =
+    parse_all_err(P_MANY_PATCHES_WITH_COMMIT_IDS, &out)?
+      .into_iter()
+      .filter_map(|(id, patches)| Some((id, patches.into_iter().next()?)))
+      .collect();
+
+  // Same revision args, so the same commits. Loaded separately as the patch parser
+  // expects only an id before the patches.
+  let out = run_git_err(RunGitOptions {
+    repo_path,
+    args: [
+      vec![
+        String::from("log"),
+        String::from("--decorate=full"),
+        String::from(PRETTY_FORMATTED),
+        String::from("--date=raw"),
+      ],
+      rev_args,
+    ]
+    .concat(),
+  })?
+  .stdout;
+
+  let commits = parse_all_err(P_COMMITS, &out)?;
+
+  Ok(
+    commits
+      .into_iter()
+      .filter_map(|commit| {
+        // Merges without a change of their own have no patch.
+        let patch = patches.remove(&commit.id)?;
+
+        Some(FileHistoryEntry { commit, patch })
+      })
+      .collect(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::git::git_types::PatchType;
+  use crate::git::queries::patches::patch_parsers::P_MANY_PATCHES_WITH_COMMIT_IDS;
+  use crate::parser::parse_all;
+
+  #[test]
+  fn test_follow_output() {
+    let out = [
+      "092bf3,\nM",
+      "b.txt",
+      "",
+      "52e535,\nR100",
+      "a.txt",
+      "b.txt",
+      "",
+      "d91fe3,\nA",
+      "a.txt",
+      "",
+    ]
+    .join("\0");
+
+    let patches = parse_all(P_MANY_PATCHES_WITH_COMMIT_IDS, &out).unwrap();
+
+    assert_eq!(patches.len(), 3);
+    assert_eq!(patches[1].0, "52e535");
+    assert_eq!(patches[1].1[0].patch_type, PatchType::R);
+    assert_eq!(patches[1].1[0].old_file, "a.txt");
+    assert_eq!(patches[1].1[0].new_file, "b.txt");
+    assert_eq!(patches[2].1[0].commit_id, "d91fe3");
+  }
+}
@@ -40,6 +40,10 @@ const P_RENAME_HEADER: Parser<String> = map2!(
 
 const P_INDEX_LINE: Parser<IgnoredLine> = and!(word!("index"), UNTIL_LINE_END);
 
+// "git log -L" leaves out the index line.
+const P_OPTIONAL_INDEX_LINE: Parser<IgnoredLine> =
+  or!(P_INDEX_LINE, map2!(WS, __, ("", String::new())));
+
 const P_OLD_FILE: Parser<IgnoredLine> = and!(word!("---"), UNTIL_LINE_END);
 
 const P_NEW_FILE: Parser<IgnoredLine> = and!(word!("+++"), UNTIL_LINE_END);
@@ -59,7 +63,12 @@ const P_FILE_INFO: Parser<FileInfo> = or!(
 );
 
 const P_DIFF_HEADER: Parser<FileInfo> = map2!(
-  and!(P_DIFF_LINE, P_OPTIONAL_HEADER, P_INDEX_LINE, P_FILE_INFO),
+  and!(
+    P_DIFF_LINE,
+    P_OPTIONAL_HEADER,
+    P_OPTIONAL_INDEX_LINE,
+    P_FILE_INFO
+  ),
   res,
   res.3
 );
@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 use std::fs;
 use ts_rs::TS;
 
+use crate::git::queries::lfs::smudge_lfs;
 use crate::git::run_git::{run_git_buffer, RunGitOptions};
 
 #[derive(Debug, Deserialize, Serialize, TS)]
@@ -26,6 +27,10 @@ pub fn load_commit_image(options: &ReqImageOptions) -> bool {
     repo_path,
     args: ["show", &format!("{}:{}", commit_id, original_image_path)],
   }) {
+    // Lfs tracked images are stored as a pointer. Falls back to writing the pointer if
+    // the real file can't be fetched.
+    let buffer = smudge_lfs(repo_path, original_image_path, &buffer).unwrap_or(buffer);
+
     return fs::write(temp_image_path, buffer).is_ok();
   }
   false
@@ -0,0 +1,274 @@
+use ahash::AHashSet;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::git::git_types::Patch;
+use crate::git::run_git::{run_git_err, run_git_with_input, RunGitOptions};
+use crate::server::git_request::ReqOptions;
+use crate::server::request_util::{ES, R};
+
+// Pointer files are small text files, the spec says under 1024 bytes.
+const MAX_POINTER_SIZE: usize = 1024;
+
+const POINTER_VERSIONS: [&str; 2] = [
+  "version https://git-lfs.github.com/spec/v1",
+  // Pre 1.0 name for git lfs.
+  "version https://hawser.github.com/spec/v1",
+];
+
+// Passing every path as an arg could hit the command line length limit.
+const CHECK_ATTR_CHUNK_SIZE: usize = 500;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LfsPointer {
+  pub oid: String,
+  pub size: u64,
+}
+
+/*
+Pointer format:
+version https://git-lfs.github.com/spec/v1
+oid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393
+size 12345
+ */
+pub fn parse_lfs_pointer(bytes: &[u8]) -> Option<LfsPointer> {
+  if bytes.len() > MAX_POINTER_SIZE {
+    return None;
+  }
+
+  let text = std::str::from_utf8(bytes).ok()?;
+  let mut lines = text.lines();
+
+  let version = lines.next()?;
+  if !POINTER_VERSIONS.contains(&version) {
+    return None;
+  }
+
+  let mut oid: Option<String> = None;
+  let mut size: Option<u64> = None;
+
+  for line in lines {
+    if let Some((key, value)) = line.split_once(' ') {
+      match key {
+        "oid" => oid = Some(value.strip_prefix("sha256:")?.to_string()),
+        "size" => size = value.parse().ok(),
+        _ => {}
+      }
+    }
+  }
+
+  Some(LfsPointer {
+    oid: oid?,
+    size: size?,
+  })
+}
+
+// Paths with filter=lfs in .gitattributes. Uses the attributes currently checked out.
+pub(crate) fn find_lfs_paths(repo_path: &str, paths: &[&str]) -> AHashSet<String> {
+  let mut lfs_paths = AHashSet::new();
+
+  for chunk in paths.chunks(CHECK_ATTR_CHUNK_SIZE) {
+    let mut args = vec!["check-attr", "-z", "filter", "--"];
+    args.extend(chunk);
+
+    if let Ok(out) = run_git_err(RunGitOptions { repo_path, args }) {
+      lfs_paths.extend(parse_check_attr(&out.stdout));
+    }
+  }
+
+  lfs_paths
+}
+
+pub(crate) fn mark_lfs_patches(repo_path: &str, patches: &mut [Patch]) {
+  let paths: Vec<&str> = patches.iter().map(|p| p.new_file.as_str()).collect();
+  let lfs_paths = find_lfs_paths(repo_path, &paths);
+
+  for patch in patches.iter_mut() {
+    patch.is_lfs = lfs_paths.contains(&patch.new_file);
+  }
+}
+
+// Output is "<path>\0<attribute>\0<value>\0" repeated.
+fn parse_check_attr(out: &str) -> Vec<String> {
+  let fields: Vec<&str> = out.split('\0').collect();
+
+  fields
+    .chunks_exact(3)
+    .filter(|f| f[2] == "lfs")
+    .map(|f| f[0].to_string())
+    .collect()
+}
+
+// Returns None if content isn't a pointer, or git lfs isn't installed or can't get
+// the object.
+pub(crate) fn smudge_lfs(repo_path: &str, path: &str, content: &[u8]) -> Option<Vec<u8>> {
+  parse_lfs_pointer(content)?;
+
+  run_git_with_input(
+    RunGitOptions {
+      repo_path,
+      args: ["lfs", "smudge", "--", path],
+    },
+    content,
+  )
+  .ok()
+}
+
+#[derive(Debug, Clone, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct LfsLock {
+  pub id: String,
+  pub path: String,
+  pub owner: Option<String>,
+  // RFC 3339, as given by the server.
+  pub locked_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LfsLockJson {
+  id: String,
+  path: String,
+  owner: Option<LfsOwnerJson>,
+  locked_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LfsOwnerJson {
+  name: String,
+}
+
+// Asks the lfs server, so this can be slow.
+pub fn load_lfs_locks(options: &ReqOptions) -> R<Vec<LfsLock>> {
+  let out = run_lfs_command(&options.repo_path, &["locks", "--json"])?;
+
+  parse_lfs_locks(&out)
+}
+
+fn parse_lfs_locks(json: &str) -> R<Vec<LfsLock>> {
+  let locks: Vec<LfsLockJson> =
+    serde_json::from_str(json).map_err(|e| ES::Text(format!("load_lfs_locks: {}", e)))?;
+
+  Ok(
+    locks
+      .into_iter()
+      .map(|l| LfsLock {
+        id: l.id,
+        path: l.path,
+        owner: l.owner.map(|o| o.name),
+        locked_at: l.locked_at,
+      })
+      .collect(),
+  )
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct LfsFile {
+  pub path: String,
+  pub oid: String,
+  // False if only the pointer is checked out.
+  pub downloaded: bool,
+}
+
+pub fn load_lfs_files(options: &ReqOptions) -> R<Vec<LfsFile>> {
+  let out = run_lfs_command(&options.repo_path, &["ls-files", "--long"])?;
+
+  Ok(parse_lfs_files(&out))
+}
+
+// Lines look like "<oid> * <path>", where "*" means downloaded and "-" means pointer.
+fn parse_lfs_files(out: &str) -> Vec<LfsFile> {
+  out
+    .lines()
+    .filter_map(|line| {
+      let mut parts = line.splitn(3, ' ');
+      let oid = parts.next()?;
+      let marker = parts.next()?;
+      let path = parts.next()?;
+
+      Some(LfsFile {
+        path: path.to_string(),
+        oid: oid.to_string(),
+        downloaded: marker == "*",
+      })
+    })
+    .collect()
+}
+
+fn run_lfs_command(repo_path: &str, args: &[&str]) -> R<String> {
+  let mut all_args = vec!["lfs"];
+  all_args.extend(args);
+
+  let out = run_git_err(RunGitOptions {
+    repo_path,
+    args: all_args,
+  })?;
+
+  if out.stderr.contains("'lfs' is not a git command") {
+    return Err(ES::from("Git LFS isn't installed."));
+  }
+
+  Ok(out.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::git::queries::lfs::{
+    parse_check_attr, parse_lfs_files, parse_lfs_locks, parse_lfs_pointer, LfsPointer,
+  };
+
+  #[test]
+  fn test_parse_lfs_pointer() {
+    let pointer = "version https://git-lfs.github.com/spec/v1
+oid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393
+size 12345
+";
+
+    assert_eq!(
+      parse_lfs_pointer(pointer.as_bytes()),
+      Some(LfsPointer {
+        oid: String::from(
+          "4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393"
+        ),
+        size: 12345,
+      })
+    );
+    assert_eq!(parse_lfs_pointer(b"version 1\nsize 2\n"), None);
+    assert_eq!(parse_lfs_pointer(&[0, 159, 146, 150]), None);
+  }
+
+  #[test]
+  fn test_parse_check_attr() {
+    let out = [
+      "a.bin",
+      "filter",
+      "lfs",
+      "b c.txt",
+      "filter",
+      "unspecified",
+      "",
+    ]
+    .join("\0");
+
+    assert_eq!(parse_check_attr(&out), vec!["a.bin"]);
+  }
+
+  #[test]
+  fn test_parse_lfs_output() {
+    let files = parse_lfs_files("3a5f * big file.psd\n9b1c - video.mp4\n");
+
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0].path, "big file.psd");
+    assert!(files[0].downloaded);
+    assert!(!files[1].downloaded);
+
+    let locks = parse_lfs_locks(
+      r#"[{"id":"3","path":"a.psd","owner":{"name":"sam"},"locked_at":"2024-01-01T00:00:00Z"}]"#,
+    )
+    .unwrap();
+
+    assert_eq!(locks[0].owner, Some(String::from("sam")));
+  }
+}
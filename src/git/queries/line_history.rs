@@ -0,0 +1,192 @@
+use ahash::AHashMap;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::f;
+use crate::git::git_types::{CommitInfo, Hunk};
+use crate::git::queries::commits_parsers::{PRETTY_FORMATTED, P_COMMITS};
+use crate::git::queries::hunks::hunk_parsers::P_HUNKS;
+use crate::git::run_git::{run_git_err, RunGitOptions};
+use crate::parser::parse_all_err;
+use crate::server::request_util::{ES, R};
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct LineHistoryOptions {
+  pub repo_path: String,
+  pub file_path: String,
+  // 1-based and inclusive, as lines are in the file at commit_id.
+  pub start_line: u32,
+  pub end_line: u32,
+  // Defaults to HEAD.
+  pub commit_id: Option<String>,
+  pub max_count: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct LineHistoryEntry {
+  pub commit: CommitInfo,
+  // None when the lines were added along with the file.
+  pub old_file: Option<String>,
+  pub new_file: String,
+  // Only the hunks touching the lines being followed.
+  pub hunks: Vec<Hunk>,
+}
+
+// Newest first, following the lines back through edits and renames.
+pub fn load_line_history(options: &LineHistoryOptions) -> R<Vec<LineHistoryEntry>> {
+  let LineHistoryOptions {
+    repo_path,
+    file_path,
+    start_line,
+    end_line,
+    commit_id,
+    max_count,
+  } = options;
+
+  if *start_line == 0 || end_line < start_line {
+    return Err(ES::from("load_line_history: Invalid line range."));
+  }
+
+  let mut rev_args = Vec::new();
+  if let Some(n) = max_count {
+    rev_args.push(f!("-n{}", n));
+  }
+  if let Some(id) = commit_id {
+    rev_args.push(id.clone());
+  }
+
+  // -L doesn't allow a "--" and pathspec, the path is part of the range.
+  let out = run_git_err(RunGitOptions {
+    repo_path,
+    args: [
+      vec![
+        String::from("log"),
+        f!("-L{},{}:{}", start_line, end_line, file_path),
+        String::from("--format=%x00%H%x00"),
+        String::from("--no-color"),
+      ],
+      rev_args,
+    ]
+    .concat(),
+  })?;
+
+  if out.stdout.is_empty() && !out.stderr.is_empty() {
+    return Err(ES::from(&f!("load_line_history: {}", out.stderr.trim())));
+  }
+
+  let diffs = split_commit_diffs(&out.stdout);
+  if diffs.is_empty() {
+    return Ok(Vec::new());
+  }
+
+  let mut commits = load_commit_infos(repo_path, diffs.iter().map(|d| d.0))?;
+
+  diffs
+    .into_iter()
+    .filter_map(|(id, diff)| {
+      let commit = commits.remove(id)?;
+
+      Some(make_entry(commit, diff))
+    })
+    .collect()
+}
+
+// The format gives "\0<id>\0<diff>" for each commit.
+fn split_commit_diffs(out: &str) -> Vec<(&str, &str)> {
+  let parts: Vec<&str> = out.split('\0').skip(1).collect();
+
+  parts
+    .chunks_exact(2)
+    .map(|p| (p[0], p[1].trim_start()))
+    .filter(|(_, diff)| !diff.is_empty())
+    .collect()
+}
+
+fn make_entry(commit: CommitInfo, diff: &str) -> R<LineHistoryEntry> {
+  let mut old_file = None;
+  let mut new_file = String::new();
+
+  for line in diff.lines() {
+    if let Some(name) = line.strip_prefix("--- a/") {
+      old_file = Some(name.to_string());
+    } else if let Some(name) = line.strip_prefix("+++ b/") {
+      new_file = name.to_string();
+      break;
+    }
+  }
+
+  Ok(LineHistoryEntry {
+    commit,
+    old_file,
+    new_file,
+    hunks: parse_all_err(P_HUNKS, diff)?,
+  })
+}
+
+fn load_commit_infos<'a>(
+  repo_path: &str,
+  ids: impl Iterator<Item = &'a str>,
+) -> R<AHashMap<String, CommitInfo>> {
+  let mut args = vec![
+    "log",
+    "--no-walk=unsorted",
+    "--decorate=full",
+    PRETTY_FORMATTED,
+    "--date=raw",
+  ];
+  args.extend(ids);
+
+  let out = run_git_err(RunGitOptions { repo_path, args })?.stdout;
+
+  Ok(
+    parse_all_err(P_COMMITS, &out)?
+      .into_iter()
+      .map(|c| (c.id.clone(), c))
+      .collect(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::git::queries::hunks::hunk_parsers::P_HUNKS;
+  use crate::git::queries::line_history::split_commit_diffs;
+  use crate::parser::parse_all;
+
+  #[test]
+  fn test_split_commit_diffs() {
+    let out = "\0f5b1b7\0
+
+diff --git a/b.txt b/b.txt
+--- a/b.txt
++++ b/b.txt
+@@ -2,2 +2,2 @@
+-l2
++L2
+ l3
+\0d91fe3\0
+
+diff --git a/a.txt b/a.txt
+--- /dev/null
++++ b/a.txt
+@@ -0,0 +1,1 @@
++a
+";
+    let diffs = split_commit_diffs(out);
+
+    assert_eq!(diffs.len(), 2);
+    assert_eq!(diffs[0].0, "f5b1b7");
+    assert_eq!(diffs[1].0, "d91fe3");
+
+    let hunks = parse_all(P_HUNKS, diffs[0].1).unwrap();
+    assert_eq!(hunks.len(), 1);
+    assert_eq!(hunks[0].lines.len(), 3);
+    assert_eq!(hunks[0].new_line_range.start, 2);
+
+    let hunks = parse_all(P_HUNKS, diffs[1].1).unwrap();
+    assert_eq!(hunks[0].lines.len(), 1);
+  }
+}
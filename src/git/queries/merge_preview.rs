@@ -0,0 +1,195 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::f;
+use crate::git::git_types::RefInfo;
+use crate::git::queries::commits::{commit_is_ancestor, CommitAncestorOpts};
+use crate::git::queries::refs::head_info::calc_head_info;
+use crate::git::run_git::{run_git_err, RunGitOptions};
+use crate::git::store::STORE;
+use crate::server::git_request::ReqOptions;
+use crate::server::request_util::{ES, R};
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct PreviewMergeOptions {
+  pub repo_path: String,
+  pub ref_info: RefInfo,
+}
+
+#[derive(Debug, Clone, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct MergePreview {
+  pub already_merged: bool,
+  pub can_fast_forward: bool,
+  pub conflicted_files: Vec<String>,
+  pub messages: Vec<MergeMessage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct MergeMessage {
+  pub paths: Vec<String>,
+  // E.g. "CONFLICT (contents)", "CONFLICT (modify/delete)", "Auto-merging".
+  pub message_type: String,
+  pub text: String,
+}
+
+// Merges in memory with "git merge-tree", so the worktree and index aren't touched.
+pub fn preview_merge(options: &PreviewMergeOptions) -> R<MergePreview> {
+  let PreviewMergeOptions {
+    repo_path,
+    ref_info,
+  } = options;
+
+  if !STORE.get_git_version().at_least(2, 38) {
+    return Err(ES::from(
+      "preview_merge: Git 2.38 or newer is required to preview merges.",
+    ));
+  }
+
+  let head = calc_head_info(&ReqOptions {
+    repo_path: repo_path.clone(),
+  })?
+  .commit;
+
+  let already_merged = head.id == ref_info.commit_id
+    || commit_is_ancestor(&CommitAncestorOpts {
+      repo_path: repo_path.clone(),
+      commit_id: head.id.clone(),
+      ancestor_candidate_id: ref_info.commit_id.clone(),
+    });
+
+  let can_fast_forward = !already_merged
+    && commit_is_ancestor(&CommitAncestorOpts {
+      repo_path: repo_path.clone(),
+      commit_id: ref_info.commit_id.clone(),
+      ancestor_candidate_id: head.id.clone(),
+    });
+
+  if already_merged || can_fast_forward {
+    return Ok(MergePreview {
+      already_merged,
+      can_fast_forward,
+      conflicted_files: Vec::new(),
+      messages: Vec::new(),
+    });
+  }
+
+  let out = run_git_err(RunGitOptions {
+    repo_path,
+    args: [
+      "merge-tree",
+      "--write-tree",
+      "-z",
+      "--name-only",
+      &head.id,
+      &ref_info.commit_id,
+    ],
+  })?;
+
+  let (conflicted_files, messages) = parse_merge_tree(&out.stdout).ok_or(ES::from(
+    &f!("preview_merge: Unexpected output. {}", out.stderr),
+  ))?;
+
+  Ok(MergePreview {
+    already_merged,
+    can_fast_forward,
+    conflicted_files,
+    messages,
+  })
+}
+
+/*
+With -z and --name-only the output is:
+<tree id> NUL (<conflicted path> NUL)* NUL
+(<path count> NUL (<path> NUL){path count} <message type> NUL <message> NUL)*
+
+A clean merge with nothing to report only outputs the tree id.
+ */
+fn parse_merge_tree(out: &str) -> Option<(Vec<String>, Vec<MergeMessage>)> {
+  let mut parts = out.split('\0');
+
+  // Tree id.
+  parts.next().filter(|id| !id.is_empty())?;
+
+  let mut conflicted_files = Vec::new();
+
+  for part in parts.by_ref() {
+    if part.is_empty() {
+      break;
+    }
+    conflicted_files.push(part.to_string());
+  }
+
+  let mut messages = Vec::new();
+
+  while let Some(count) = parts.next() {
+    if count.is_empty() {
+      break;
+    }
+    let count: usize = count.parse().ok()?;
+
+    let paths: Vec<String> = parts.by_ref().take(count).map(|p| p.to_string()).collect();
+    if paths.len() != count {
+      return None;
+    }
+
+    messages.push(MergeMessage {
+      paths,
+      message_type: parts.next()?.to_string(),
+      text: parts.next()?.trim_end().to_string(),
+    });
+  }
+
+  Some((conflicted_files, messages))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::git::queries::merge_preview::{parse_merge_tree, MergeMessage};
+
+  #[test]
+  fn test_parse_merge_tree_conflicts() {
+    let out = [
+      "a85d1b6",
+      "g",
+      "f",
+      "",
+      "1",
+      "g",
+      "CONFLICT (modify/delete)",
+      "CONFLICT (modify/delete): g deleted in other and modified in HEAD.\n",
+      "1",
+      "f",
+      "Auto-merging",
+      "Auto-merging f\n",
+      "",
+    ]
+    .join("\0");
+
+    let (files, messages) = parse_merge_tree(&out).unwrap();
+
+    assert_eq!(files, vec!["g", "f"]);
+    assert_eq!(messages.len(), 2);
+    assert_eq!(
+      messages[1],
+      MergeMessage {
+        paths: vec![String::from("f")],
+        message_type: String::from("Auto-merging"),
+        text: String::from("Auto-merging f"),
+      }
+    );
+  }
+
+  #[test]
+  fn test_parse_merge_tree_clean() {
+    let (files, messages) = parse_merge_tree("a85d1b6\0").unwrap();
+
+    assert!(files.is_empty());
+    assert!(messages.is_empty());
+  }
+}
@@ -1,24 +1,39 @@
 use crate::git::git_types::RefInfo;
 use crate::git::queries::refs::P_OPTIONAL_REFS;
 
+pub(crate) mod blame;
 pub(crate) mod commits;
+pub(crate) mod commits_cache;
+pub(crate) mod commits_incremental;
+pub(crate) mod commits_paging;
 mod commits_parsers;
 mod commits_test;
 
 pub(crate) mod commit_calcs;
 mod commit_filters;
+pub(crate) mod commit_graph;
+pub(crate) mod commit_signatures;
 pub(crate) mod config;
+pub(crate) mod file_history;
 pub(crate) mod hunks;
+pub(crate) mod lfs;
+pub(crate) mod line_history;
+pub(crate) mod merge_preview;
 pub(crate) mod patches;
+pub(crate) mod rebase_state;
 pub(crate) mod refs;
 pub(crate) mod run;
 pub(crate) mod scan_workspace;
 pub(crate) mod search;
+pub(crate) mod sequencer_state;
 pub(crate) mod stashes;
 mod stashes_test;
+pub(crate) mod submodules;
 mod syntax_colouring;
+pub(crate) mod tags;
 pub(crate) mod unpushed_commits;
 pub(crate) mod wip;
 pub mod workspace;
+pub(crate) mod worktrees;
 
 pub const COMMIT_0_ID: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
@@ -2,30 +2,98 @@ extern crate directories;
 
 use std::collections::HashMap;
 use std::error::Error;
-use std::fs::{create_dir_all, remove_dir_all, File};
-use std::io::{BufReader, Read, Write};
+use std::fs::{create_dir_all, read, remove_dir_all, remove_file, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::mem::size_of;
 use std::path::{Path, PathBuf};
 
 use crate::config::{APPLICATION, ORGANISATION, QUALIFIER};
-use crate::dprintln;
+use crate::util::bin_io::{checksum, BinReader, BinWriter};
+use crate::util::global::Global;
+use crate::util::repo_cache::{str_size, CacheSize, CacheStats, RepoCache};
+use crate::{dprintln, global};
+use ahash::AHashMap;
 use directories::ProjectDirs;
 use loggers::elapsed;
 
-use crate::git::git_types::Patch;
+use crate::git::git_types::{Patch, PatchType};
+use crate::git::queries::patches::file_is_image;
 use crate::git::store::STORE;
 
+/*
+Patches are stored in a binary file per repo:
+
+  magic, version, then segments of: payload length, checksum, payload
+
+Each payload has the file paths first seen in it, then commits with their patches,
+which refer to paths by index into all the paths read so far. New commits are
+appended as a new segment, so we never rewrite the whole file for a few commits.
+A segment with a bad checksum and everything after it is ignored and overwritten
+by the next write.
+ */
+
+const MAGIC: &[u8; 4] = b"GFPC";
+const VERSION: u32 = 1;
+const HEADER_LEN: usize = 8;
+// Each patch is type, old path, new path, id flag and id suffix.
+const PATCH_LEN: usize = 14;
+
+#[derive(Clone, Default)]
+struct PatchIndex {
+  // The valid part of the file.
+  data: Vec<u8>,
+  strings: Vec<String>,
+  string_ids: AHashMap<String, u32>,
+  // Offset of each commit's patches in data.
+  commits: AHashMap<String, usize>,
+}
+
+const MB: usize = 1024 * 1024;
+
+// Mostly the cache file itself.
+static PATCH_INDEXES: Global<RepoCache<PatchIndex>> =
+  global!(RepoCache::new("patch indexes", 128 * MB));
+
 pub fn write_patches_cache(
   repo_path: &str,
   patches: &HashMap<String, Vec<Patch>>,
 ) -> Option<()> {
-  let cache_dir = get_cache_dir()?;
-  let file_name = generate_file_name(repo_path);
+  STORE.insert_patches(repo_path, patches);
 
-  let full_path = cache_dir.join(file_name);
+  let cache_file = get_cache_file(repo_path)?;
+  let mut indexes = PATCH_INDEXES.data.write().ok()?;
 
-  STORE.insert_patches(repo_path, patches);
+  if indexes.get(repo_path).is_none() {
+    indexes.insert(repo_path, read_patch_index(&cache_file));
+  }
+
+  let result = indexes.update(repo_path, |index| {
+    // After lots of rebasing most of the file is for commits we no longer show.
+    if index.commits.len() > patches.len() * 2 + 1000 {
+      *index = PatchIndex::empty();
+      return append_patches(index, patches.iter().collect(), &cache_file);
+    }
+
+    let new_patches: Vec<(&String, &Vec<Patch>)> = patches
+      .iter()
+      .filter(|(id, _)| !index.commits.contains_key(*id))
+      .collect();
+
+    if new_patches.is_empty() {
+      return Ok(());
+    }
 
-  write_patches_to_file(full_path, patches).ok()
+    append_patches(index, new_patches, &cache_file)
+  });
+
+  if let Err(e) = result {
+    dprintln!("Failed to write patches: {}", e);
+    indexes.invalidate(repo_path);
+
+    return None;
+  }
+
+  Some(())
 }
 
 pub fn load_patches_cache(repo_path: &str) -> Option<HashMap<String, Vec<Patch>>> {
@@ -33,81 +101,432 @@ pub fn load_patches_cache(repo_path: &str) -> Option<HashMap<String, Vec<Patch>>
     return Some(patches);
   }
 
-  let cache_dir = get_cache_dir()?;
-  create_dir_all(&cache_dir).ok()?;
+  let patches = with_patch_index(repo_path, |index| {
+    index
+      .commits
+      .iter()
+      .filter_map(|(id, offset)| Some((id.clone(), index.read_patches(id, *offset)?)))
+      .collect::<HashMap<String, Vec<Patch>>>()
+  })?;
 
-  let file_name = generate_file_name(repo_path);
-  let cache_file = cache_dir.join(file_name);
+  if patches.is_empty() {
+    return None;
+  }
 
-  let maybe_patches = read_patches_from_file(cache_file).ok();
+  STORE.insert_patches(repo_path, &patches);
 
-  if let Some(patches) = maybe_patches {
-    STORE.insert_patches(repo_path, &patches);
+  Some(patches)
+}
 
-    return Some(patches);
+// Only decodes the patches for this commit.
+pub fn load_cached_patches_for_commit(
+  repo_path: &str,
+  commit_id: &str,
+) -> Option<Vec<Patch>> {
+  with_patch_index(repo_path, |index| {
+    let offset = index.commits.get(commit_id)?;
+
+    index.read_patches(commit_id, *offset)
+  })?
+}
+
+fn with_patch_index<T>(repo_path: &str, f: impl FnOnce(&PatchIndex) -> T) -> Option<T> {
+  if let Some(index) = PATCH_INDEXES.data.read().ok()?.get(repo_path) {
+    return Some(f(index));
   }
 
-  None
+  let index = read_patch_index(&get_cache_file(repo_path)?);
+  let result = f(&index);
+
+  PATCH_INDEXES.insert_repo(repo_path, index);
+
+  Some(result)
+}
+
+pub fn load_patch_index_stats() -> Option<CacheStats> {
+  PATCH_INDEXES.stats()
 }
 
 fn get_cache_dir() -> Option<PathBuf> {
+  get_cache_sub_dir("patches")
+}
+
+pub(crate) fn get_cache_sub_dir(name: &str) -> Option<PathBuf> {
   if let Some(proj_dirs) = ProjectDirs::from(QUALIFIER, ORGANISATION, APPLICATION) {
     let cache_dir = proj_dirs.cache_dir();
 
-    Some(cache_dir.join("patches"))
+    Some(cache_dir.join(name))
   } else {
     None
   }
 }
 
+fn get_cache_file(repo_path: &str) -> Option<PathBuf> {
+  let cache_dir = get_cache_dir()?;
+  create_dir_all(&cache_dir).ok()?;
+
+  Some(cache_dir.join(generate_file_name(repo_path)))
+}
+
 /// This generates a file name from the repo path e.g.
-/// c:\user\something\thing -> cusersomethingthing.json
+/// c:\user\something\thing -> cusersomethingthing.bin
 fn generate_file_name(repo_path: &str) -> String {
-  let id = Path::new(&repo_path)
+  format!("{}.bin", generate_cache_id(repo_path))
+}
+
+pub(crate) fn generate_cache_id(repo_path: &str) -> String {
+  Path::new(&repo_path)
     .iter()
     .map(|p| p.to_str().unwrap_or(""))
     .collect::<Vec<&str>>()
     .join("")
-    .replace(['\\', ':', '/'], "");
-
-  format!("{}.json", id)
+    .replace(['\\', ':', '/'], "")
 }
 
+// A missing or unreadable file gives an empty index.
 #[elapsed]
-fn read_patches_from_file(
-  path: PathBuf,
-) -> Result<HashMap<String, Vec<Patch>>, Box<dyn Error>> {
-  let file = File::open(&path)?;
+fn read_patch_index(path: &Path) -> PatchIndex {
+  read(path)
+    .ok()
+    .and_then(PatchIndex::from_data)
+    .unwrap_or_else(PatchIndex::empty)
+}
+
+fn append_patches(
+  index: &mut PatchIndex,
+  patches: Vec<(&String, &Vec<Patch>)>,
+  path: &Path,
+) -> Result<(), Box<dyn Error>> {
+  let start = index.data.len();
+  let segment = index.add_segment(&patches);
+
+  if start == HEADER_LEN {
+    // Written before the binary format.
+    let _ = remove_file(path.with_extension("json"));
+  }
 
-  let mut reader = BufReader::new(file);
-  let mut text = String::new();
+  let mut file = OpenOptions::new()
+    .write(true)
+    .create(true)
+    .truncate(false)
+    .open(path)?;
 
-  reader.read_to_string(&mut text)?;
+  if start == HEADER_LEN {
+    file.set_len(0)?;
+    file.write_all(&index.data[..HEADER_LEN])?;
+  } else {
+    // Drops anything after the last good segment.
+    file.set_len(start as u64)?;
+    file.seek(SeekFrom::End(0))?;
+  }
+  file.write_all(&segment)?;
 
-  let patches = serde_json::from_str(&text)?;
+  dprintln!("Wrote {} commits to '{:?}'", patches.len(), path.to_str());
 
-  Ok(patches)
+  Ok(())
 }
 
-fn write_patches_to_file<P: AsRef<Path>>(
-  path: P,
-  patches: &HashMap<String, Vec<Patch>>,
-) -> Result<(), Box<dyn Error>> {
-  let str = serde_json::to_string(&patches)?;
+impl PatchIndex {
+  fn empty() -> Self {
+    let mut w = BinWriter::new();
+    w.buf.extend_from_slice(MAGIC);
+    w.u32(VERSION);
 
-  let mut file = File::create(&path)?;
+    PatchIndex {
+      data: w.buf,
+      ..Default::default()
+    }
+  }
 
-  file.write_all(str.as_ref())?;
+  // None if this isn't a patch cache we can read.
+  fn from_data(data: Vec<u8>) -> Option<Self> {
+    let mut r = BinReader::new(&data);
 
-  dprintln!("Wrote patches to '{:?}'", path.as_ref().to_str());
+    if r.bytes(MAGIC.len())? != MAGIC || r.u32()? != VERSION {
+      return None;
+    }
 
-  Ok(())
+    let mut index = PatchIndex::default();
+    let mut valid_len = r.pos();
+
+    while !r.at_end() {
+      if index.read_segment(&mut r).is_none() {
+        dprintln!("Patch cache is corrupt after {} bytes", valid_len);
+        break;
+      }
+      valid_len = r.pos();
+    }
+
+    index.data = data;
+    index.data.truncate(valid_len);
+
+    Some(index)
+  }
+
+  fn read_segment(&mut self, r: &mut BinReader) -> Option<()> {
+    let len = r.u32()? as usize;
+    let sum = r.u64()?;
+    let start = r.pos();
+    let payload = r.bytes(len)?;
+
+    if checksum(payload) != sum {
+      return None;
+    }
+
+    let mut p = BinReader::new(payload);
+
+    let mut strings = Vec::new();
+    for _ in 0..p.u32()? {
+      strings.push(p.str()?);
+    }
+
+    let mut commits = Vec::new();
+    for _ in 0..p.u32()? {
+      let id = p.hex_id()?;
+      commits.push((id, start + p.pos()));
+
+      let num_patches = p.u32()? as usize;
+      p.bytes(num_patches * PATCH_LEN)?;
+    }
+
+    if !p.at_end() {
+      return None;
+    }
+
+    // Only keep a segment once we know all of it is good.
+    for s in strings {
+      self.intern(&s);
+    }
+    self.commits.extend(commits);
+
+    Some(())
+  }
+
+  fn read_patches(&self, commit_id: &str, offset: usize) -> Option<Vec<Patch>> {
+    let mut r = BinReader::new(self.data.get(offset..)?);
+    let num_patches = r.u32()?;
+    let mut patches = Vec::with_capacity(num_patches as usize);
+
+    for _ in 0..num_patches {
+      let patch_type = u8_to_patch_type(r.u8()?)?;
+      let old_file = self.strings.get(r.u32()? as usize)?.clone();
+      let new_file = self.strings.get(r.u32()? as usize)?.clone();
+      let has_prefix = r.bool()?;
+      let id_part = self.strings.get(r.u32()? as usize)?;
+
+      let id = if has_prefix {
+        format!("{}{}", new_file, id_part)
+      } else {
+        id_part.clone()
+      };
+
+      patches.push(Patch {
+        commit_id: commit_id.to_string(),
+        is_image: file_is_image(&new_file),
+        old_file,
+        new_file,
+        patch_type,
+        id,
+        is_lfs: false,
+      });
+    }
+
+    Some(patches)
+  }
+
+  // Adds the patches to data and returns the bytes to append to the file.
+  fn add_segment(&mut self, patches: &[(&String, &Vec<Patch>)]) -> Vec<u8> {
+    let first_new_string = self.strings.len();
+
+    let mut body = BinWriter::new();
+    let mut offsets = Vec::new();
+
+    body.u32(patches.len() as u32);
+    for (commit_id, commit_patches) in patches {
+      body.hex_id(commit_id);
+      offsets.push((commit_id.to_string(), body.buf.len()));
+      body.u32(commit_patches.len() as u32);
+
+      for p in commit_patches.iter() {
+        // Ids are usually the new file plus the type, so we only keep the suffix.
+        let (has_prefix, id_part) = match p.id.strip_prefix(&p.new_file) {
+          Some(suffix) => (true, suffix),
+          None => (false, p.id.as_str()),
+        };
+
+        body.u8(patch_type_to_u8(&p.patch_type));
+        body.u32(self.intern(&p.old_file));
+        body.u32(self.intern(&p.new_file));
+        body.bool(has_prefix);
+        body.u32(self.intern(id_part));
+      }
+    }
+
+    let mut payload = BinWriter::new();
+    let new_strings = &self.strings[first_new_string..];
+    payload.u32(new_strings.len() as u32);
+    for s in new_strings {
+      payload.str(s);
+    }
+
+    // After the segment's length and checksum.
+    let body_start = self.data.len() + 12 + payload.buf.len();
+    for (id, offset) in offsets {
+      self.commits.insert(id, body_start + offset);
+    }
+
+    payload.buf.extend_from_slice(&body.buf);
+
+    let mut segment = BinWriter::new();
+    segment.u32(payload.buf.len() as u32);
+    segment.u64(checksum(&payload.buf));
+    segment.buf.extend_from_slice(&payload.buf);
+
+    self.data.extend_from_slice(&segment.buf);
+
+    segment.buf
+  }
+
+  fn intern(&mut self, s: &str) -> u32 {
+    if let Some(id) = self.string_ids.get(s) {
+      return *id;
+    }
+
+    let id = self.strings.len() as u32;
+    self.strings.push(s.to_string());
+    self.string_ids.insert(s.to_string(), id);
+
+    id
+  }
+}
+
+impl CacheSize for PatchIndex {
+  fn cache_size(&self) -> usize {
+    self.data.len()
+      + self
+        .strings
+        .iter()
+        .map(|s| 2 * str_size(s) + size_of::<u32>())
+        .sum::<usize>()
+      + self
+        .commits
+        .keys()
+        .map(|id| str_size(id) + size_of::<usize>())
+        .sum::<usize>()
+  }
+}
+
+fn patch_type_to_u8(patch_type: &PatchType) -> u8 {
+  match patch_type {
+    PatchType::A => 0,
+    PatchType::C => 1,
+    PatchType::B => 2,
+    PatchType::D => 3,
+    PatchType::M => 4,
+    PatchType::R => 5,
+    PatchType::T => 6,
+    PatchType::U => 7,
+    PatchType::X => 8,
+  }
+}
+
+fn u8_to_patch_type(n: u8) -> Option<PatchType> {
+  Some(match n {
+    0 => PatchType::A,
+    1 => PatchType::C,
+    2 => PatchType::B,
+    3 => PatchType::D,
+    4 => PatchType::M,
+    5 => PatchType::R,
+    6 => PatchType::T,
+    7 => PatchType::U,
+    8 => PatchType::X,
+    _ => return None,
+  })
 }
 
 pub fn clear_patch_cache() -> Option<()> {
+  PATCH_INDEXES.clear_repos();
+
   let cache_dir = get_cache_dir()?;
 
   remove_dir_all(cache_dir).ok()?;
 
   Some(())
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::git::git_types::{Patch, PatchType};
+  use crate::git::queries::patches::cache::{PatchIndex, HEADER_LEN};
+
+  fn make_patch(commit_id: &str, file: &str, patch_type: PatchType) -> Patch {
+    Patch {
+      commit_id: commit_id.to_string(),
+      old_file: file.to_string(),
+      new_file: file.to_string(),
+      id: format!("{}-{}", file, patch_type),
+      patch_type,
+      is_image: file.ends_with(".png"),
+      is_lfs: false,
+    }
+  }
+
+  #[test]
+  fn test_append_and_read() {
+    let id1 = String::from("aa11");
+    let id2 = String::from("bb22");
+    let p1 = vec![
+      make_patch("aa11", "src/main.rs", PatchType::M),
+      make_patch("aa11", "icon.png", PatchType::A),
+    ];
+    let mut renamed = make_patch("bb22", "src/lib.rs", PatchType::R);
+    renamed.old_file = String::from("src/main.rs");
+    renamed.id = String::from("src/lib.rs-R100");
+    let p2 = vec![renamed];
+
+    let mut index = PatchIndex::empty();
+    let mut file = index.data.clone();
+    file.extend(index.add_segment(&[(&id1, &p1)]));
+    file.extend(index.add_segment(&[(&id2, &p2)]));
+
+    assert_eq!(file, index.data);
+
+    let index = PatchIndex::from_data(file).unwrap();
+
+    // "src/main.rs" is only stored once.
+    assert_eq!(index.strings.len(), 6);
+
+    let read1 = index.read_patches("aa11", index.commits["aa11"]).unwrap();
+    let read2 = index.read_patches("bb22", index.commits["bb22"]).unwrap();
+
+    assert_eq!(read1, p1);
+    assert_eq!(read2, p2);
+  }
+
+  #[test]
+  fn test_corrupt_segment() {
+    let id1 = String::from("aa11");
+    let id2 = String::from("bb22");
+    let p1 = vec![make_patch("aa11", "a.txt", PatchType::A)];
+    let p2 = vec![make_patch("bb22", "b.txt", PatchType::D)];
+
+    let mut index = PatchIndex::empty();
+    index.add_segment(&[(&id1, &p1)]);
+    let good_len = index.data.len();
+    index.add_segment(&[(&id2, &p2)]);
+
+    let mut data = index.data.clone();
+    let last = data.len() - 1;
+    data[last] ^= 1;
+
+    let index = PatchIndex::from_data(data).unwrap();
+
+    assert_eq!(index.data.len(), good_len);
+    assert!(index.commits.contains_key("aa11"));
+    assert!(!index.commits.contains_key("bb22"));
+
+    assert!(PatchIndex::from_data(b"GFPC\x02\0\0\0".to_vec()).is_none());
+    assert_eq!(PatchIndex::empty().data.len(), HEADER_LEN);
+  }
+}
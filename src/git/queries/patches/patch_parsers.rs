@@ -53,6 +53,7 @@ pub fn map_data_to_patch(data: PatchData, commit_id: String) -> Patch {
     id,
     commit_id,
     is_image,
+    is_lfs: false,
   }
 }
 
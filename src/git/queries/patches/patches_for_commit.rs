@@ -1,4 +1,6 @@
 use crate::git::git_types::Patch;
+use crate::git::queries::lfs::mark_lfs_patches;
+use crate::git::queries::patches::cache::load_cached_patches_for_commit;
 use crate::git::queries::patches::patches::load_patches;
 use crate::git::store::STORE;
 use crate::server::request_util::{ES, R};
@@ -19,18 +21,25 @@ pub fn load_patches_for_commit(options: &ReqPatchesForCommitOpts) -> R<Vec<Patch
     commit_id,
   } = options;
 
-  let (commits, _) = STORE
-    .get_commits_and_refs(repo_path)
-    .ok_or(ES::from("load_patches_for_commit: Couldn't get commits."))?;
+  let mut patches = match load_cached_patches_for_commit(repo_path, commit_id) {
+    Some(patches) => patches,
+    None => {
+      let (commits, _) = STORE
+        .get_commits_and_refs(repo_path)
+        .ok_or(ES::from("load_patches_for_commit: Couldn't get commits."))?;
 
-  let all_patches = load_patches(repo_path, &commits)?;
+      let all_patches = load_patches(repo_path, &commits)?;
 
-  Ok(
-    all_patches
-      .get(commit_id)
-      .ok_or(ES::from(
-        "load_patches_for_commit: Missing patches for commit.",
-      ))?
-      .clone(),
-  )
+      all_patches
+        .get(commit_id)
+        .ok_or(ES::from(
+          "load_patches_for_commit: Missing patches for commit.",
+        ))?
+        .clone()
+    }
+  };
+
+  mark_lfs_patches(repo_path, &mut patches);
+
+  Ok(patches)
 }
@@ -0,0 +1,215 @@
+use std::fs::read_to_string;
+use std::path::Path;
+
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::git::store::STORE;
+use crate::parser::standard_parsers::{LINE_END, UNTIL_LINE_END};
+use crate::parser::{parse_all_err, Parser};
+use crate::server::git_request::ReqOptions;
+use crate::server::request_util::R;
+use crate::{and, character, many, map2, optional_take_char_while, or, take_char_while};
+
+#[derive(Debug, Clone, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct RebaseState {
+  // E.g. "refs/heads/my-branch". "detached HEAD" if we weren't on a branch.
+  pub head_name: String,
+  pub onto: String,
+  // 1 based. This is the step we are on (or stopped at).
+  pub current_step: u32,
+  pub total_steps: u32,
+  pub done: Vec<RebaseTodoLine>,
+  pub remaining: Vec<RebaseTodoLine>,
+  // Set when stopped for "edit" or a conflict.
+  pub stopped_commit_id: Option<String>,
+  pub interactive: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct RebaseTodoLine {
+  // Full command name, abbreviations are expanded. E.g. "pick", "exec".
+  pub command: String,
+  pub commit_id: Option<String>,
+  // Commit message, or the command for exec lines.
+  pub text: String,
+}
+
+// Returns None if there isn't a rebase in progress.
+pub fn load_rebase_state(options: &ReqOptions) -> R<Option<RebaseState>> {
+  let repo = STORE.get_repo_path(&options.repo_path)?;
+
+  let merge_dir = repo.git_path.join("rebase-merge");
+
+  if merge_dir.exists() {
+    let done = read_todo_file(&merge_dir.join("done"))?;
+    let remaining = read_todo_file(&merge_dir.join("git-rebase-todo"))?;
+
+    return Ok(Some(RebaseState {
+      head_name: read_trimmed(&merge_dir.join("head-name")),
+      onto: read_trimmed(&merge_dir.join("onto")),
+      current_step: read_number(&merge_dir.join("msgnum")),
+      total_steps: read_number(&merge_dir.join("end")),
+      done,
+      remaining,
+      stopped_commit_id: read_optional(&merge_dir.join("stopped-sha")),
+      interactive: merge_dir.join("interactive").exists(),
+    }));
+  }
+
+  // Older non-interactive rebases use the "apply" backend. There's no todo list.
+  let apply_dir = repo.git_path.join("rebase-apply");
+
+  if apply_dir.exists() && !apply_dir.join("applying").exists() {
+    return Ok(Some(RebaseState {
+      head_name: read_trimmed(&apply_dir.join("head-name")),
+      onto: read_trimmed(&apply_dir.join("onto")),
+      current_step: read_number(&apply_dir.join("next")),
+      total_steps: read_number(&apply_dir.join("last")),
+      done: Vec::new(),
+      remaining: Vec::new(),
+      stopped_commit_id: read_optional(&apply_dir.join("original-commit")),
+      interactive: false,
+    }));
+  }
+
+  Ok(None)
+}
+
+fn read_todo_file(path: &Path) -> R<Vec<RebaseTodoLine>> {
+  match read_to_string(path) {
+    Ok(text) => parse_all_err(P_TODO, &text),
+    Err(_) => Ok(Vec::new()),
+  }
+}
+
+fn read_trimmed(path: &Path) -> String {
+  read_optional(path).unwrap_or_default()
+}
+
+pub(crate) fn read_optional(path: &Path) -> Option<String> {
+  let text = read_to_string(path).ok()?;
+  let text = text.trim();
+
+  if text.is_empty() {
+    None
+  } else {
+    Some(text.to_string())
+  }
+}
+
+fn read_number(path: &Path) -> u32 {
+  read_trimmed(path).parse().unwrap_or(0)
+}
+
+const P_SPACES: Parser<String> =
+  optional_take_char_while!(|c: char| c == ' ' || c == '\t');
+
+const P_COMMAND_NAME: Parser<String> = take_char_while!(|c: char| !c.is_whitespace());
+
+const P_COMMENT: Parser<Option<RebaseTodoLine>> =
+  map2!(and!(P_SPACES, character!('#'), UNTIL_LINE_END), __, None);
+
+const P_BLANK_LINE: Parser<Option<RebaseTodoLine>> =
+  map2!(and!(P_SPACES, LINE_END), __, None);
+
+const P_COMMAND_LINE: Parser<Option<RebaseTodoLine>> = map2!(
+  and!(P_SPACES, P_COMMAND_NAME, P_SPACES, UNTIL_LINE_END),
+  res,
+  Some(make_todo_line(&res.1, &res.3))
+);
+
+pub(crate) const P_TODO: Parser<Vec<RebaseTodoLine>> = map2!(
+  many!(or!(P_COMMENT, P_BLANK_LINE, P_COMMAND_LINE)),
+  res,
+  res.into_iter().flatten().collect()
+);
+
+fn make_todo_line(command: &str, rest: &str) -> RebaseTodoLine {
+  let command = expand_command(command);
+
+  if !takes_commit(&command) {
+    return RebaseTodoLine {
+      command,
+      commit_id: None,
+      text: rest.trim().to_string(),
+    };
+  }
+
+  // "fixup -C <commit>" and "fixup -c <commit>" keep the fixup commit's message.
+  let rest = rest
+    .strip_prefix("-C ")
+    .or_else(|| rest.strip_prefix("-c "))
+    .unwrap_or(rest)
+    .trim();
+
+  let (commit_id, text) = rest.split_once(' ').unwrap_or((rest, ""));
+
+  RebaseTodoLine {
+    command,
+    commit_id: Some(commit_id.to_string()),
+    text: text.trim().to_string(),
+  }
+}
+
+fn expand_command(command: &str) -> String {
+  match command {
+    "p" => "pick",
+    "r" => "reword",
+    "e" => "edit",
+    "s" => "squash",
+    "f" => "fixup",
+    "x" => "exec",
+    "b" => "break",
+    "d" => "drop",
+    "l" => "label",
+    "t" => "reset",
+    "m" => "merge",
+    "u" => "update-ref",
+    other => other,
+  }
+  .to_string()
+}
+
+fn takes_commit(command: &str) -> bool {
+  matches!(
+    command,
+    "pick" | "reword" | "edit" | "squash" | "fixup" | "drop" | "revert"
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::git::queries::rebase_state::{RebaseTodoLine, P_TODO};
+  use crate::parser::parse_all;
+
+  #[test]
+  fn test_p_todo() {
+    let text = "pick 2e89669 First commit
+# A comment
+r 8a3bb1c Second commit
+
+fixup -C dd5733a Third
+exec git commit --amend --only -F '/tmp/message-1'
+";
+
+    let lines = parse_all(P_TODO, text).unwrap();
+
+    assert_eq!(lines.len(), 4);
+    assert_eq!(
+      lines[1],
+      RebaseTodoLine {
+        command: String::from("reword"),
+        commit_id: Some(String::from("8a3bb1c")),
+        text: String::from("Second commit"),
+      }
+    );
+    assert_eq!(lines[2].commit_id, Some(String::from("dd5733a")));
+    assert_eq!(lines[3].command, "exec");
+    assert_eq!(lines[3].commit_id, None);
+  }
+}
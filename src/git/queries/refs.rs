@@ -8,27 +8,33 @@ use crate::{map2, or};
 use loggers::elapsed;
 
 pub(crate) mod head_info;
+pub(crate) mod native_refs;
 pub(crate) mod ref_diffs;
+pub(crate) mod ref_name;
+pub(crate) mod remote_refs;
 
 const REF_NAME_PARSER: Parser<String> =
   take_char_while!(|c: char| { !c.is_whitespace() && c != ',' && c != '(' && c != ')' });
 
 pub const P_REF_NAME: Parser<RefInfoPart> = map!(REF_NAME_PARSER, |result: String| {
-  let cleaned = result.replace("^{}", "");
-  let parts: Vec<&str> = cleaned.split('/').collect();
-  let ref_type = get_type_from_name(&parts);
+  ref_info_part_from_name(&result.replace("^{}", ""))
+});
+
+// For a full name like "refs/remotes/origin/main".
+pub fn ref_info_part_from_name(full_name: &str) -> RefInfoPart {
+  let parts: Vec<&str> = full_name.split('/').collect();
 
   RefInfoPart {
-    id: cleaned.to_owned(),
-    ref_type,
+    id: full_name.to_owned(),
+    ref_type: get_type_from_name(&parts),
     location: get_ref_location(&parts),
     short_name: get_short_name(&parts),
-    full_name: cleaned.to_owned(),
+    full_name: full_name.to_owned(),
     remote_name: get_remote_name(&parts),
     sibling_id: String::new(),
     head: false,
   }
-});
+}
 
 const P_TAG_REF: Parser<RefInfoPart> =
   map2!(and!(word!("tag: "), P_REF_NAME), result, result.1);
@@ -150,7 +156,7 @@ pub fn finish_properties_on_refs(
   refs: Vec<RefInfo>,
   repo_path: &PathString,
 ) -> Vec<RefInfo> {
-  let config = CONFIG.get_by_key(repo_path).unwrap_or_else(GitConfig::new);
+  let config = CONFIG.get_repo(repo_path).unwrap_or_else(GitConfig::new);
 
   refs
     .iter()
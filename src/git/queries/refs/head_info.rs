@@ -1,25 +1,124 @@
+use std::path::Path;
+
 use ahash::AHashMap;
+use serde::Serialize;
+use ts_rs::TS;
 
+use crate::f;
 use crate::git::git_types::{Commit, CommitInfo, RefInfo, RefLocation};
 use crate::git::queries::commit_calcs::count_commits_between_fallback;
 use crate::git::queries::commits::{
   convert_commit, load_head_commit, load_top_commit_for_branch, TopCommitOptions,
 };
 use crate::git::queries::config::GitConfig;
+use crate::git::queries::rebase_state::read_optional;
+use crate::git::queries::refs::native_refs::{read_head, resolve_ref, HeadRef};
 use crate::git::store::{PathString, CONFIG, STORE};
 use crate::server::git_request::ReqOptions;
 use crate::server::request_util::{ES, R};
 
 #[derive(Debug, Clone)]
 pub struct HeadInfo {
-  pub ref_info: RefInfo,
+  // None when HEAD isn't on a branch.
+  pub ref_info: Option<RefInfo>,
   pub commit: Commit,
   pub remote_ref: Option<RefInfo>,
   pub remote_commit: Option<Commit>,
+  pub state: HeadState,
   // pub remote_ahead: u32,
   // pub remote_behind: u32,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, TS)]
+#[serde(rename_all = "camelCase", rename_all_fields = "camelCase")]
+#[ts(export)]
+pub enum HeadState {
+  // name is the full name, e.g. "refs/heads/main".
+  Branch {
+    name: String,
+    commit_id: String,
+  },
+  Detached {
+    commit_id: String,
+  },
+  // A new repo, or after "git switch --orphan". There's no commit yet.
+  Unborn {
+    name: String,
+  },
+  // branch is None if the rebase started from a detached HEAD.
+  Rebasing {
+    branch: Option<String>,
+    onto: Option<String>,
+    commit_id: Option<String>,
+  },
+  // original_head is the branch name or commit "git bisect reset" goes back to.
+  Bisecting {
+    original_head: String,
+    commit_id: Option<String>,
+  },
+}
+
+impl HeadState {
+  pub fn commit_id(&self) -> Option<&str> {
+    match self {
+      HeadState::Branch { commit_id, .. } | HeadState::Detached { commit_id } => {
+        Some(commit_id)
+      }
+      HeadState::Rebasing { commit_id, .. } | HeadState::Bisecting { commit_id, .. } => {
+        commit_id.as_deref()
+      }
+      HeadState::Unborn { .. } => None,
+    }
+  }
+}
+
+pub fn load_head_state(repo_path: &str) -> R<HeadState> {
+  let repo = STORE.get_repo_path(repo_path)?;
+
+  Ok(calc_head_state(read_head(repo_path)?, &repo.git_path))
+}
+
+// Rebase and bisect state is kept per worktree, next to HEAD.
+fn calc_head_state(head: HeadRef, git_path: &Path) -> HeadState {
+  let commit_id = match &head {
+    HeadRef::Branch { commit_id, .. } => commit_id.clone(),
+    HeadRef::Detached { commit_id } => Some(commit_id.clone()),
+  };
+
+  for dir in ["rebase-merge", "rebase-apply"] {
+    let dir = git_path.join(dir);
+
+    // "git am" also uses rebase-apply.
+    if dir.is_dir() && !dir.join("applying").exists() {
+      return HeadState::Rebasing {
+        // "detached HEAD" when there wasn't a branch.
+        branch: read_optional(&dir.join("head-name")).filter(|n| n.starts_with("refs/")),
+        onto: read_optional(&dir.join("onto")),
+        commit_id,
+      };
+    }
+  }
+
+  if let Some(original_head) = read_optional(&git_path.join("BISECT_START")) {
+    return HeadState::Bisecting {
+      original_head,
+      commit_id,
+    };
+  }
+
+  match head {
+    HeadRef::Branch {
+      name,
+      commit_id: Some(commit_id),
+    } => HeadState::Branch { name, commit_id },
+    HeadRef::Branch {
+      name,
+      commit_id: None,
+    } => HeadState::Unborn { name },
+    HeadRef::Detached { commit_id } => HeadState::Detached { commit_id },
+  }
+}
+
 pub fn calc_head_info(options: &ReqOptions) -> R<HeadInfo> {
   let ReqOptions { repo_path } = options;
 
@@ -27,33 +126,60 @@ pub fn calc_head_info(options: &ReqOptions) -> R<HeadInfo> {
     .get_commits_and_refs(repo_path)
     .ok_or(ES::from("calc_head_info: No commits"))?;
 
-  let head_info = calc_head_info_from_commits(commits, refs);
+  // None if we can't read HEAD ourselves, e.g. with reftable. Then we go by the refs.
+  let state = load_head_state(repo_path).ok();
 
-  if let Some(mut head_info) = head_info {
+  match &state {
+    Some(HeadState::Unborn { name }) => {
+      return Err(ES::from(&f!(
+        "calc_head_info: {} has no commits yet.",
+        name
+      )));
+    }
+    Some(detached @ HeadState::Detached { .. }) => {
+      return calc_detached_head_info(repo_path, detached.clone(), &commits);
+    }
+    _ => {}
+  }
+
+  if let Some(mut head_info) = calc_head_info_from_commits(&commits, &refs) {
     if head_info.remote_ref.is_none() {
-      if let Ok((_remote_ahead, remote_commit, _remote_behind, remote_ref)) =
-        calc_remote_fallback(repo_path, &mut head_info.ref_info)
-      {
-        return Ok(HeadInfo {
-          ref_info: head_info.ref_info.clone(),
-          commit: head_info.commit.clone(),
-          remote_ref: Some(remote_ref),
-          remote_commit: Some(remote_commit),
-          // remote_ahead,
-          // remote_behind,
-        });
+      if let Some(ref_info) = head_info.ref_info.as_mut() {
+        if let Ok((_remote_ahead, remote_commit, _remote_behind, remote_ref)) =
+          calc_remote_fallback(repo_path, ref_info)
+        {
+          head_info.remote_ref = Some(remote_ref);
+          head_info.remote_commit = Some(remote_commit);
+        }
       }
     }
+    if let Some(state) = state {
+      head_info.state = state;
+    }
     return Ok(head_info);
-  } else if let Ok((mut head_commit, i)) = calc_head_fallback(repo_path) {
+  }
+
+  // Usually detached while rebasing or bisecting.
+  if let Some(state) = state.filter(|s| !matches!(s, HeadState::Branch { .. })) {
+    return calc_detached_head_info(repo_path, state, &commits);
+  }
+
+  if let Ok((mut head_commit, i)) = calc_head_fallback(repo_path) {
     let head_ref = &mut head_commit.refs[i];
 
     if let Ok((_remote_ahead, remote_commit, _remote_behind, remote_ref)) =
       calc_remote_fallback(repo_path, head_ref)
     {
+      let head_ref = head_ref.clone();
+      let commit = convert_commit(head_commit);
+
       return Ok(HeadInfo {
-        ref_info: head_ref.clone(),
-        commit: convert_commit(head_commit),
+        state: HeadState::Branch {
+          name: head_ref.full_name.clone(),
+          commit_id: commit.id.clone(),
+        },
+        ref_info: Some(head_ref),
+        commit,
         remote_ref: Some(remote_ref),
         remote_commit: Some(remote_commit),
         // remote_ahead,
@@ -65,18 +191,40 @@ pub fn calc_head_info(options: &ReqOptions) -> R<HeadInfo> {
   Err(ES::from("calc_head_info: Failed to get head info"))
 }
 
+fn calc_detached_head_info(
+  repo_path: &str,
+  state: HeadState,
+  commits: &[Commit],
+) -> R<HeadInfo> {
+  let commit_id = state
+    .commit_id()
+    .ok_or(ES::from("calc_head_info: Head has no commit"))?;
+
+  let commit = match commits.iter().find(|c| c.id == commit_id) {
+    Some(c) => c.clone(),
+    // Checked out a commit older than the ones we've loaded.
+    None => convert_commit(load_top_commit_for_branch(&TopCommitOptions {
+      repo_path: repo_path.to_string(),
+      branch_name: commit_id.to_string(),
+    })?),
+  };
+
+  Ok(HeadInfo {
+    ref_info: None,
+    commit,
+    remote_ref: None,
+    remote_commit: None,
+    state,
+  })
+}
+
 // Returns Option intentionally.
-fn calc_head_info_from_commits(
-  commits: Vec<Commit>,
-  refs: Vec<RefInfo>,
-) -> Option<HeadInfo> {
-  let all_refs: AHashMap<String, RefInfo> =
-    refs.iter().map(|r| (r.id.clone(), r.clone())).collect();
-
-  let commit_map = commits
-    .into_iter()
-    .map(|c| (c.id.clone(), c))
-    .collect::<AHashMap<String, Commit>>();
+fn calc_head_info_from_commits(commits: &[Commit], refs: &[RefInfo]) -> Option<HeadInfo> {
+  let all_refs: AHashMap<&str, &RefInfo> =
+    refs.iter().map(|r| (r.id.as_str(), r)).collect();
+
+  let commit_map: AHashMap<&str, &Commit> =
+    commits.iter().map(|c| (c.id.as_str(), c)).collect();
 
   // let mut remote_ahead = 0;
   // let mut remote_behind = 0;
@@ -85,12 +233,12 @@ fn calc_head_info_from_commits(
     if info.head {
       let mut remote_ref: Option<&RefInfo> = None;
       let mut remote_commit: Option<&Commit> = None;
-      let commit = commit_map.get(&info.commit_id)?;
+      let commit = *commit_map.get(info.commit_id.as_str())?;
 
       if !info.sibling_id.is_empty() {
-        remote_ref = all_refs.get(&info.sibling_id);
+        remote_ref = all_refs.get(info.sibling_id.as_str()).copied();
         if let Some(remote_ref) = remote_ref {
-          remote_commit = commit_map.get(&remote_ref.commit_id);
+          remote_commit = commit_map.get(remote_ref.commit_id.as_str()).copied();
         } else {
           // TODO: It may still exist, but not be part of our commit batch.
           // Maybe this is too unlikely? A user would need to commit 1000 times without pushing
@@ -109,10 +257,14 @@ fn calc_head_info_from_commits(
       // }
 
       return Some(HeadInfo {
-        ref_info: info.clone(),
+        ref_info: Some(info.clone()),
         commit: commit.clone(),
         remote_ref: remote_ref.cloned(),
         remote_commit: remote_commit.cloned(),
+        state: HeadState::Branch {
+          name: info.full_name.clone(),
+          commit_id: commit.id.clone(),
+        },
         // remote_ahead,
         // remote_behind,
       });
@@ -142,10 +294,15 @@ pub fn calc_remote_fallback(
   repo_path: &PathString,
   head_ref: &mut RefInfo,
 ) -> R<(u32, Commit, u32, RefInfo)> {
-  let config = CONFIG.get_by_key(repo_path).unwrap_or_else(GitConfig::new);
+  let config = CONFIG.get_repo(repo_path).unwrap_or_else(GitConfig::new);
 
   let remote_tracking_branch = config.get_tracking_branch_name(&head_ref.short_name);
 
+  // Most often a branch that hasn't been pushed. No need to ask git.
+  if let Ok(None) = resolve_ref(repo_path, &remote_tracking_branch) {
+    return Err(ES::from("calc_remote_fallback: No remote branch"));
+  }
+
   let mut remote_commit = load_top_commit_for_branch(&TopCommitOptions {
     repo_path: repo_path.to_string(),
     branch_name: remote_tracking_branch,
@@ -185,3 +342,158 @@ pub fn calc_remote_fallback(
     "calc_remote_fallback: Didn't find remote ref in remote commit",
   ))
 }
+
+#[cfg(test)]
+mod tests {
+  use std::fs::{create_dir_all, write};
+
+  use crate::git::git_types::make_commit;
+  use crate::git::queries::refs::head_info::{
+    calc_detached_head_info, calc_head_state, HeadState,
+  };
+  use crate::git::queries::refs::native_refs::HeadRef;
+  use crate::util::test_util::{TempDir, ID_A as A, ID_B as B, ID_C as C};
+
+  fn branch(commit_id: Option<&str>) -> HeadRef {
+    HeadRef::Branch {
+      name: String::from("refs/heads/main"),
+      commit_id: commit_id.map(|id| id.to_string()),
+    }
+  }
+
+  fn detached() -> HeadRef {
+    HeadRef::Detached {
+      commit_id: A.to_string(),
+    }
+  }
+
+  #[test]
+  fn test_branch_detached_and_unborn() {
+    let (_dir, git_path) = TempDir::with_git_dir("head_state");
+
+    assert_eq!(
+      calc_head_state(branch(Some(A)), &git_path),
+      HeadState::Branch {
+        name: String::from("refs/heads/main"),
+        commit_id: A.to_string(),
+      }
+    );
+    assert_eq!(
+      calc_head_state(detached(), &git_path),
+      HeadState::Detached {
+        commit_id: A.to_string()
+      }
+    );
+    assert_eq!(
+      calc_head_state(branch(None), &git_path),
+      HeadState::Unborn {
+        name: String::from("refs/heads/main")
+      }
+    );
+  }
+
+  #[test]
+  fn test_rebasing() {
+    let (_dir, git_path) = TempDir::with_git_dir("head_state_rebase");
+    let merge_dir = git_path.join("rebase-merge");
+    create_dir_all(&merge_dir).unwrap();
+    write(merge_dir.join("head-name"), "refs/heads/feature\n").unwrap();
+    write(merge_dir.join("onto"), format!("{}\n", B)).unwrap();
+
+    let state = calc_head_state(detached(), &git_path);
+    assert_eq!(
+      state,
+      HeadState::Rebasing {
+        branch: Some(String::from("refs/heads/feature")),
+        onto: Some(B.to_string()),
+        commit_id: Some(A.to_string()),
+      }
+    );
+    assert_eq!(state.commit_id(), Some(A));
+
+    write(merge_dir.join("head-name"), "detached HEAD\n").unwrap();
+    assert!(matches!(
+      calc_head_state(detached(), &git_path),
+      HeadState::Rebasing { branch: None, .. }
+    ));
+  }
+
+  #[test]
+  fn test_am_isnt_rebasing() {
+    let (_dir, git_path) = TempDir::with_git_dir("head_state_am");
+    let apply_dir = git_path.join("rebase-apply");
+    create_dir_all(&apply_dir).unwrap();
+    write(apply_dir.join("applying"), "").unwrap();
+
+    assert!(matches!(
+      calc_head_state(branch(Some(A)), &git_path),
+      HeadState::Branch { .. }
+    ));
+  }
+
+  #[test]
+  fn test_bisecting() {
+    let (_dir, git_path) = TempDir::with_git_dir("head_state_bisect");
+    write(git_path.join("BISECT_START"), "main\n").unwrap();
+
+    assert_eq!(
+      calc_head_state(detached(), &git_path),
+      HeadState::Bisecting {
+        original_head: String::from("main"),
+        commit_id: Some(A.to_string()),
+      }
+    );
+  }
+
+  #[test]
+  fn test_detached_head_info() {
+    let commits = vec![make_commit(B, &[A]), make_commit(A, &[])];
+
+    let states = [
+      HeadState::Detached {
+        commit_id: A.to_string(),
+      },
+      HeadState::Rebasing {
+        branch: Some(String::from("refs/heads/feature")),
+        onto: Some(B.to_string()),
+        commit_id: Some(A.to_string()),
+      },
+      HeadState::Bisecting {
+        original_head: String::from("main"),
+        commit_id: Some(A.to_string()),
+      },
+    ];
+
+    for state in states {
+      let info = calc_detached_head_info("", state.clone(), &commits).unwrap();
+
+      assert_eq!(info.commit.id, A);
+      assert!(info.ref_info.is_none());
+      assert!(info.remote_ref.is_none());
+      assert_eq!(info.state, state);
+    }
+
+    let no_commit = HeadState::Rebasing {
+      branch: None,
+      onto: None,
+      commit_id: None,
+    };
+    assert!(calc_detached_head_info("", no_commit, &commits).is_err());
+  }
+
+  #[test]
+  fn test_detached_head_info_older_commit() {
+    let repo = TempDir::with_repo("detached_head_info");
+    repo.commit("c1", 1_600_001_000);
+    let id = repo.git(&["rev-parse", "HEAD"]).trim().to_string();
+
+    let state = HeadState::Detached {
+      commit_id: id.clone(),
+    };
+    let info =
+      calc_detached_head_info(&repo.repo_path(), state, &[make_commit(C, &[])]).unwrap();
+
+    assert_eq!(info.commit.id, id);
+    assert_eq!(info.commit.message.trim(), "c1");
+  }
+}
@@ -0,0 +1,545 @@
+use std::fs::{read_dir, read_to_string};
+use std::path::{Path, PathBuf};
+
+use ahash::{AHashMap, AHashSet};
+
+use crate::git::git_types::CommitInfo;
+use crate::git::queries::commits_parsers::{PRETTY_FORMATTED, PRETTY_FORMATTED_NO_REFS};
+use crate::git::queries::refs::{make_ref_info, ref_info_part_from_name};
+use crate::git::run_git::{run_git_with_input, RunGitOptions};
+use crate::git::store::STORE;
+use crate::global;
+use crate::server::request_util::{ES, R};
+use crate::util::global::Global;
+
+/*
+Reads refs straight from the files in the git dir, so commits can be loaded without
+"--decorate" and the head found without asking git. Repos using reftable give an error,
+callers should fall back to git for those.
+ */
+
+// The same limit git uses for chains of symbolic refs.
+const MAX_SYMREF_DEPTH: usize = 5;
+
+// Namespaces that belong to each worktree rather than the whole repo.
+const WORKTREE_NAMESPACES: [&str; 3] =
+  ["refs/bisect/", "refs/worktree/", "refs/rewritten/"];
+
+// The namespaces "git log --decorate" shows for our commits.
+const DECORATED_NAMESPACES: [&str; 3] = ["refs/heads/", "refs/remotes/", "refs/tags/"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum HeadRef {
+  // commit_id is None for a branch with no commits yet.
+  Branch {
+    name: String,
+    commit_id: Option<String>,
+  },
+  Detached {
+    commit_id: String,
+  },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct NativeRef {
+  pub name: String,
+  pub id: String,
+  // The commit an annotated tag points to, when we know it.
+  pub peeled: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct NativeRefs {
+  pub head: Option<HeadRef>,
+  pub refs: Vec<NativeRef>,
+}
+
+struct GitDirs {
+  git_path: PathBuf,
+  common_dir: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PackedRef {
+  id: String,
+  peeled: Option<String>,
+}
+
+// Tag object ids to commit ids. Objects never change, so these never go stale.
+static PEELED_TAGS: Global<AHashMap<String, String>> = global!(AHashMap::new());
+
+pub(crate) fn load_native_refs(repo_path: &str) -> R<NativeRefs> {
+  let dirs = get_git_dirs(repo_path)?;
+
+  Ok(read_native_refs(&dirs))
+}
+
+pub(crate) fn read_head(repo_path: &str) -> R<HeadRef> {
+  let dirs = get_git_dirs(repo_path)?;
+
+  read_head_ref(&dirs, &mut None).ok_or(ES::from("read_head: Failed to read HEAD."))
+}
+
+// Ok(None) if the ref doesn't exist. Follows symbolic refs.
+pub(crate) fn resolve_ref(repo_path: &str, name: &str) -> R<Option<String>> {
+  let dirs = get_git_dirs(repo_path)?;
+
+  Ok(resolve(&dirs, name, &mut None, 0))
+}
+
+// "git log" args for decorations. Not needed when we attach the refs ourselves.
+pub(crate) fn log_ref_args(native_refs: &Option<NativeRefs>) -> [&'static str; 2] {
+  match native_refs {
+    Some(_) => ["--no-decorate", PRETTY_FORMATTED_NO_REFS],
+    None => ["--decorate=full", PRETTY_FORMATTED],
+  }
+}
+
+impl NativeRefs {
+  // Gives commits the same refs "git log --decorate=full" would have.
+  pub fn attach_to_commits(&self, repo_path: &str, commits: &mut [CommitInfo]) {
+    let commit_ids: AHashSet<&str> = commits.iter().map(|c| c.id.as_str()).collect();
+    let peeled = self.peel_tags(repo_path, &commit_ids);
+
+    let head_name = match &self.head {
+      Some(HeadRef::Branch { name, .. }) => Some(name.as_str()),
+      _ => None,
+    };
+
+    let mut by_commit: AHashMap<&str, Vec<&str>> = AHashMap::new();
+
+    for r in &self.refs {
+      let commit_id = match &r.peeled {
+        Some(id) => id.as_str(),
+        None => peeled.get(&r.id).map_or(r.id.as_str(), |id| id.as_str()),
+      };
+
+      by_commit.entry(commit_id).or_default().push(&r.name);
+    }
+
+    for c in commits.iter_mut() {
+      let Some(names) = by_commit.get(c.id.as_str()) else {
+        continue;
+      };
+
+      c.refs = names
+        .iter()
+        .map(|name| {
+          let mut part = ref_info_part_from_name(name);
+          part.head = Some(*name) == head_name;
+
+          make_ref_info(part, c.id.clone(), c.date.ms)
+        })
+        .collect();
+
+      // Matches decorate, which puts the head first.
+      c.refs.sort_by_key(|r| !r.head);
+    }
+  }
+
+  /*
+  Loose tags aren't peeled on disk. The ones that don't point at a loaded commit could
+  be annotated, so we ask git once for those we haven't seen before.
+   */
+  fn peel_tags(
+    &self,
+    repo_path: &str,
+    commit_ids: &AHashSet<&str>,
+  ) -> AHashMap<String, String> {
+    let known = PEELED_TAGS
+      .data
+      .read()
+      .map(|p| p.clone())
+      .unwrap_or_default();
+
+    let unknown: Vec<&str> = self
+      .refs
+      .iter()
+      .filter(|r| {
+        r.name.starts_with("refs/tags/")
+          && r.peeled.is_none()
+          && !commit_ids.contains(r.id.as_str())
+          && !known.contains_key(&r.id)
+      })
+      .map(|r| r.id.as_str())
+      .collect();
+
+    if unknown.is_empty() {
+      return known;
+    }
+
+    let input: String = unknown
+      .iter()
+      .map(|id| format!("{}^{{commit}}\n", id))
+      .collect();
+
+    // Prints "<input> missing" for tags of trees and blobs, which we leave as they are.
+    let Ok(out) = run_git_with_input(
+      RunGitOptions {
+        repo_path,
+        args: ["cat-file", "--batch-check=%(objectname)"],
+      },
+      input.as_bytes(),
+    ) else {
+      return known;
+    };
+
+    let out = String::from_utf8_lossy(&out);
+
+    let Ok(mut peeled) = PEELED_TAGS.data.write() else {
+      return known;
+    };
+
+    for (id, line) in unknown.iter().zip(out.lines()) {
+      if is_object_id(line) {
+        peeled.insert(id.to_string(), line.to_string());
+      }
+    }
+
+    peeled.clone()
+  }
+}
+
+fn get_git_dirs(repo_path: &str) -> R<GitDirs> {
+  let repo = STORE.get_repo_path(repo_path)?;
+
+  if repo.common_dir.join("reftable").is_dir() {
+    return Err(ES::from("get_git_dirs: Repo uses reftable."));
+  }
+
+  Ok(GitDirs {
+    git_path: repo.git_path,
+    common_dir: repo.common_dir,
+  })
+}
+
+fn read_native_refs(dirs: &GitDirs) -> NativeRefs {
+  let mut packed = Some(read_packed_refs(&dirs.common_dir));
+  let head = read_head_ref(dirs, &mut packed);
+  let packed = packed.unwrap_or_default();
+
+  let mut refs: AHashMap<String, NativeRef> = AHashMap::new();
+
+  for namespace in DECORATED_NAMESPACES {
+    for (name, packed_ref) in packed.iter() {
+      if name.starts_with(namespace) {
+        refs.insert(
+          name.clone(),
+          NativeRef {
+            name: name.clone(),
+            id: packed_ref.id.clone(),
+            peeled: packed_ref.peeled.clone(),
+          },
+        );
+      }
+    }
+
+    let dir = dirs.common_dir.join(namespace);
+    let mut loose = Vec::new();
+    read_loose_refs(&dir, namespace.trim_end_matches('/'), &mut loose);
+
+    // Loose refs are newer than packed ones with the same name.
+    for (name, text) in loose {
+      if let Some(id) = parse_id(&text) {
+        refs.insert(
+          name.clone(),
+          NativeRef {
+            name,
+            id,
+            peeled: None,
+          },
+        );
+      } else {
+        // Symbolic refs like refs/remotes/origin/HEAD aren't shown by decorate.
+        refs.remove(&name);
+      }
+    }
+  }
+
+  let mut refs: Vec<NativeRef> = refs.into_values().collect();
+  refs.sort_by(|a, b| a.name.cmp(&b.name));
+
+  NativeRefs { head, refs }
+}
+
+fn read_head_ref(
+  dirs: &GitDirs,
+  packed: &mut Option<AHashMap<String, PackedRef>>,
+) -> Option<HeadRef> {
+  let text = read_to_string(dirs.git_path.join("HEAD")).ok()?;
+
+  if let Some(target) = parse_symbolic(&text) {
+    let commit_id = resolve(dirs, &target, packed, 1);
+
+    return Some(HeadRef::Branch {
+      name: target,
+      commit_id,
+    });
+  }
+
+  Some(HeadRef::Detached {
+    commit_id: parse_id(&text)?,
+  })
+}
+
+// packed-refs is only read if a loose ref isn't found.
+fn resolve(
+  dirs: &GitDirs,
+  name: &str,
+  packed: &mut Option<AHashMap<String, PackedRef>>,
+  depth: usize,
+) -> Option<String> {
+  if depth > MAX_SYMREF_DEPTH {
+    return None;
+  }
+
+  if let Ok(text) = read_to_string(ref_file(dirs, name)) {
+    return match parse_symbolic(&text) {
+      Some(target) => resolve(dirs, &target, packed, depth + 1),
+      None => parse_id(&text),
+    };
+  }
+
+  if !name.starts_with("refs/") || is_worktree_ref(name) {
+    return None;
+  }
+
+  packed
+    .get_or_insert_with(|| read_packed_refs(&dirs.common_dir))
+    .get(name)
+    .map(|r| r.id.clone())
+}
+
+/*
+Where git keeps the loose ref. Pseudo refs like HEAD and per-worktree refs are in the
+worktree's git dir, other worktrees' can be reached with "main-worktree/" and
+"worktrees/<id>/".
+ */
+fn ref_file(dirs: &GitDirs, name: &str) -> PathBuf {
+  if let Some(rest) = name.strip_prefix("main-worktree/") {
+    return dirs.common_dir.join(rest);
+  }
+  if name.starts_with("worktrees/") {
+    return dirs.common_dir.join(name);
+  }
+  if !name.starts_with("refs/") || is_worktree_ref(name) {
+    return dirs.git_path.join(name);
+  }
+
+  dirs.common_dir.join(name)
+}
+
+fn is_worktree_ref(name: &str) -> bool {
+  WORKTREE_NAMESPACES.iter().any(|n| name.starts_with(n))
+}
+
+// Branch names can contain '/', so we go through nested dirs. (name, file text).
+fn read_loose_refs(dir: &Path, prefix: &str, refs: &mut Vec<(String, String)>) {
+  let Ok(entries) = read_dir(dir) else {
+    return;
+  };
+
+  for entry in entries.flatten() {
+    let path = entry.path();
+    let file_name = entry.file_name().to_string_lossy().to_string();
+
+    if file_name.starts_with('.') || file_name.ends_with(".lock") {
+      continue;
+    }
+
+    let name = format!("{}/{}", prefix, file_name);
+
+    if path.is_dir() {
+      read_loose_refs(&path, &name, refs);
+    } else if let Ok(text) = read_to_string(&path) {
+      refs.push((name, text));
+    }
+  }
+}
+
+fn read_packed_refs(common_dir: &Path) -> AHashMap<String, PackedRef> {
+  read_to_string(common_dir.join("packed-refs"))
+    .map(|text| parse_packed_refs(&text))
+    .unwrap_or_default()
+}
+
+/*
+E.g.
+# pack-refs with: peeled fully-peeled sorted
+<id> refs/tags/v1.0
+^<id of the commit the tag points to>
+ */
+fn parse_packed_refs(text: &str) -> AHashMap<String, PackedRef> {
+  let mut refs: AHashMap<String, PackedRef> = AHashMap::new();
+  let mut last_name: Option<String> = None;
+
+  for line in text.lines() {
+    if let Some(peeled) = line.strip_prefix('^') {
+      if let Some(r) = last_name.as_ref().and_then(|n| refs.get_mut(n)) {
+        r.peeled = Some(peeled.trim().to_string());
+      }
+    } else if let Some((id, name)) = line.split_once(' ') {
+      if line.starts_with('#') || !is_object_id(id) {
+        continue;
+      }
+
+      let name = name.trim().to_string();
+      refs.insert(
+        name.clone(),
+        PackedRef {
+          id: id.to_string(),
+          peeled: None,
+        },
+      );
+      last_name = Some(name);
+    }
+  }
+
+  refs
+}
+
+// E.g. "ref: refs/heads/main"
+fn parse_symbolic(text: &str) -> Option<String> {
+  Some(text.strip_prefix("ref:")?.trim().to_string())
+}
+
+fn parse_id(text: &str) -> Option<String> {
+  let id = text.trim();
+
+  is_object_id(id).then(|| id.to_string())
+}
+
+// SHA-1 or SHA-256.
+fn is_object_id(text: &str) -> bool {
+  (text.len() == 40 || text.len() == 64) && text.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::git::queries::refs::native_refs::{
+    parse_packed_refs, read_head_ref, read_native_refs, resolve, GitDirs, HeadRef,
+  };
+  use crate::util::test_util::{write_file, TempDir, ID_A as A, ID_B as B, ID_C as C};
+
+  // Laid out like a linked worktree, so we can tell which dir a ref is read from.
+  fn make_dirs(name: &str) -> (TempDir, GitDirs) {
+    let (dir, common_dir) = TempDir::with_git_dir(name);
+
+    let dirs = GitDirs {
+      git_path: common_dir.join("worktrees").join("wt"),
+      common_dir,
+    };
+
+    (dir, dirs)
+  }
+
+  #[test]
+  fn test_parse_packed_refs() {
+    let text = format!(
+      "# pack-refs with: peeled fully-peeled sorted \n{} refs/heads/main\n{} refs/tags/v1\n^{}\n",
+      A, B, C
+    );
+    let refs = parse_packed_refs(&text);
+
+    assert_eq!(refs.len(), 2);
+    assert_eq!(refs["refs/heads/main"].id, A);
+    assert_eq!(refs["refs/heads/main"].peeled, None);
+    assert_eq!(refs["refs/tags/v1"].peeled.as_deref(), Some(C));
+  }
+
+  #[test]
+  fn test_read_native_refs() {
+    let (_dir, dirs) = make_dirs("native_refs");
+    let common = &dirs.common_dir;
+
+    write_file(
+      &dirs.git_path.join("HEAD"),
+      "ref: refs/heads/feature/nested/a\n",
+    );
+    write_file(
+      &common.join("refs/heads/feature/nested/a"),
+      &format!("{}\n", A),
+    );
+    write_file(&common.join("refs/heads/main.lock"), A);
+    write_file(
+      &common.join("refs/remotes/origin/HEAD"),
+      "ref: refs/remotes/origin/main\n",
+    );
+    write_file(&common.join("refs/bisect/bad"), B);
+    write_file(
+      &common.join("packed-refs"),
+      &format!(
+        "{} refs/heads/feature/nested/a\n{} refs/remotes/origin/main\n{} refs/tags/v1\n^{}\n",
+        C, B, C, A
+      ),
+    );
+
+    let refs = read_native_refs(&dirs);
+
+    assert_eq!(
+      refs.head,
+      Some(HeadRef::Branch {
+        name: String::from("refs/heads/feature/nested/a"),
+        commit_id: Some(A.to_string()),
+      })
+    );
+
+    let names: Vec<&str> = refs.refs.iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(
+      names,
+      vec![
+        "refs/heads/feature/nested/a",
+        "refs/remotes/origin/main",
+        "refs/tags/v1"
+      ]
+    );
+    // The loose ref wins over the packed one.
+    assert_eq!(refs.refs[0].id, A);
+    assert_eq!(refs.refs[2].peeled.as_deref(), Some(A));
+  }
+
+  #[test]
+  fn test_head_states() {
+    let (_dir, dirs) = make_dirs("native_head");
+    let head = dirs.git_path.join("HEAD");
+
+    write_file(&head, "ref: refs/heads/new\n");
+    assert_eq!(
+      read_head_ref(&dirs, &mut None),
+      Some(HeadRef::Branch {
+        name: String::from("refs/heads/new"),
+        commit_id: None,
+      })
+    );
+
+    write_file(&head, &format!("{}\n", B));
+    assert_eq!(
+      read_head_ref(&dirs, &mut None),
+      Some(HeadRef::Detached {
+        commit_id: B.to_string()
+      })
+    );
+  }
+
+  #[test]
+  fn test_resolve_worktree_refs() {
+    let (_dir, dirs) = make_dirs("native_resolve");
+
+    write_file(&dirs.git_path.join("refs/bisect/bad"), A);
+    write_file(&dirs.common_dir.join("HEAD"), &format!("{}\n", B));
+    write_file(&dirs.common_dir.join("refs/heads/a"), "ref: refs/heads/b\n");
+    write_file(&dirs.common_dir.join("refs/heads/b"), "ref: refs/heads/a\n");
+
+    assert_eq!(
+      resolve(&dirs, "refs/bisect/bad", &mut None, 0),
+      Some(A.to_string())
+    );
+    assert_eq!(
+      resolve(&dirs, "main-worktree/HEAD", &mut None, 0),
+      Some(B.to_string())
+    );
+    assert_eq!(resolve(&dirs, "HEAD", &mut None, 0), None);
+    // A loop of symbolic refs.
+    assert_eq!(resolve(&dirs, "refs/heads/a", &mut None, 0), None);
+  }
+}
@@ -32,9 +32,10 @@ pub fn calc_ref_diffs(
   } = options;
 
   let (commits, refs) = STORE.get_commits_and_refs(repo_path)?;
-  let config = CONFIG.get_by_key(repo_path).unwrap_or_else(GitConfig::new);
+  let config = CONFIG.get_repo(repo_path).unwrap_or_else(GitConfig::new);
 
   Some(calc_ref_diffs_inner(
+    repo_path,
     &commits,
     &refs,
     &config,
@@ -44,6 +45,7 @@ pub fn calc_ref_diffs(
 
 // We need to pass in head as it may not be found in provided commits in some cases.
 pub fn calc_ref_diffs_inner(
+  repo_path: &str,
   commits: &[Commit],
   refs: &[RefInfo],
   config: &GitConfig,
@@ -59,13 +61,16 @@ pub fn calc_ref_diffs_inner(
   let commit_map: AHashMap<String, Commit> =
     commits.iter().map(|c| (c.id.clone(), c.clone())).collect();
 
-  let local_ref_diffs = calc_local_ref_diffs(head_commit_id, pairs, &commit_map);
-  let remote_ref_diffs = calc_remote_ref_diffs(head_commit_id, &ref_map, &commit_map);
+  let local_ref_diffs =
+    calc_local_ref_diffs(repo_path, head_commit_id, pairs, &commit_map);
+  let remote_ref_diffs =
+    calc_remote_ref_diffs(repo_path, head_commit_id, &ref_map, &commit_map);
 
   (local_ref_diffs, remote_ref_diffs)
 }
 
 pub fn calc_remote_ref_diffs(
+  repo_path: &str,
   head_commit_id: &String,
   refs: &AHashMap<String, RefInfo>,
   commits: &AHashMap<String, Commit>,
@@ -75,13 +80,14 @@ pub fn calc_remote_ref_diffs(
     .map(|(_, info)| {
       (
         info.id.clone(),
-        calc_remote_ref_diff(head_commit_id, info, commits),
+        calc_remote_ref_diff(repo_path, head_commit_id, info, commits),
       )
     })
     .collect()
 }
 
 fn calc_remote_ref_diff(
+  repo_path: &str,
   head_commit_id: &String,
   info: &RefInfo,
   commits: &AHashMap<String, Commit>,
@@ -89,9 +95,9 @@ fn calc_remote_ref_diff(
   let ref_commit_id = &info.commit_id;
 
   let ahead_of_head =
-    count_commits_between_commit_ids(ref_commit_id, head_commit_id, commits);
+    count_commits_between_commit_ids(repo_path, ref_commit_id, head_commit_id, commits);
   let behind_head =
-    count_commits_between_commit_ids(head_commit_id, ref_commit_id, commits);
+    count_commits_between_commit_ids(repo_path, head_commit_id, ref_commit_id, commits);
 
   RefCommitDiff {
     ahead_of_head,
@@ -100,6 +106,7 @@ fn calc_remote_ref_diff(
 }
 
 fn calc_local_ref_diffs(
+  repo_path: &str,
   head_commit_id: &String,
   pairs: Vec<(RefInfo, Option<RefInfo>)>,
   commits: &AHashMap<String, Commit>,
@@ -109,13 +116,14 @@ fn calc_local_ref_diffs(
     .map(|(local, remote)| {
       (
         local.id.clone(),
-        calc_local_ref_diff(head_commit_id, local, remote, commits),
+        calc_local_ref_diff(repo_path, head_commit_id, local, remote, commits),
       )
     })
     .collect()
 }
 
 fn calc_local_ref_diff(
+  repo_path: &str,
   head_commit_id: &String,
   local: RefInfo,
   remote: Option<RefInfo>,
@@ -123,14 +131,18 @@ fn calc_local_ref_diff(
 ) -> LocalRefCommitDiff {
   let local_id = &local.commit_id;
 
-  let ahead_of_head = count_commits_between_commit_ids(local_id, head_commit_id, commits);
-  let behind_head = count_commits_between_commit_ids(head_commit_id, local_id, commits);
+  let ahead_of_head =
+    count_commits_between_commit_ids(repo_path, local_id, head_commit_id, commits);
+  let behind_head =
+    count_commits_between_commit_ids(repo_path, head_commit_id, local_id, commits);
 
   if let Some(remote) = remote {
     let remote_id = &remote.commit_id;
 
-    let ahead_of_remote = count_commits_between_commit_ids(local_id, remote_id, commits);
-    let behind_remote = count_commits_between_commit_ids(remote_id, local_id, commits);
+    let ahead_of_remote =
+      count_commits_between_commit_ids(repo_path, local_id, remote_id, commits);
+    let behind_remote =
+      count_commits_between_commit_ids(repo_path, remote_id, local_id, commits);
 
     LocalRefCommitDiff {
       ahead_of_remote,
@@ -0,0 +1,112 @@
+use serde::Deserialize;
+use ts_rs::TS;
+
+use crate::f;
+use crate::server::request_util::{ES, R};
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct RefNameOptions {
+  pub name: String,
+}
+
+// For the UI to validate as the user types. Ok means git will accept the name.
+pub fn check_branch_name(options: &RefNameOptions) -> R<()> {
+  check_short_ref_name(&options.name)
+}
+
+/*
+The same rules as "git check-ref-format --branch", for a name without the
+"refs/heads/" or "refs/tags/" prefix. Tags share the rules, apart from git
+allowing a tag to start with '-' (which would be confused for an option).
+ */
+pub(crate) fn check_short_ref_name(name: &str) -> R<()> {
+  if name.starts_with('-') {
+    return Err(ES::from("Name can't start with '-'."));
+  }
+  if name == "HEAD" {
+    return Err(ES::from("Name can't be HEAD."));
+  }
+
+  check_ref_format(name)
+}
+
+// Rules from "git help check-ref-format". Names can have several levels, e.g.
+// "feature/login".
+pub(crate) fn check_ref_format(name: &str) -> R<()> {
+  if name.is_empty() {
+    return Err(ES::from("Name can't be empty."));
+  }
+  if name == "@" {
+    return Err(ES::from("Name can't be '@'."));
+  }
+  if name.starts_with('/') || name.ends_with('/') {
+    return Err(ES::from("Name can't start or end with '/'."));
+  }
+  if name.ends_with('.') {
+    return Err(ES::from("Name can't end with '.'."));
+  }
+
+  for sequence in ["..", "//", "@{"] {
+    if name.contains(sequence) {
+      return Err(ES::from(&f!("Name can't contain '{}'.", sequence)));
+    }
+  }
+
+  if let Some(c) = name.chars().find(|c| is_bad_char(*c)) {
+    return Err(ES::from(&f!("Name can't contain {:?}.", c)));
+  }
+
+  for component in name.split('/') {
+    if component.starts_with('.') {
+      return Err(ES::from("A name component can't start with '.'."));
+    }
+    if component.ends_with(".lock") {
+      return Err(ES::from("A name component can't end with '.lock'."));
+    }
+  }
+
+  Ok(())
+}
+
+fn is_bad_char(c: char) -> bool {
+  c.is_ascii_control() || matches!(c, ' ' | '~' | '^' | ':' | '?' | '*' | '[' | '\\')
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::git::queries::refs::ref_name::{check_ref_format, check_short_ref_name};
+
+  #[test]
+  fn test_check_ref_format_valid() {
+    for name in [
+      "main",
+      "feature/thing",
+      "a.b",
+      "fix-1",
+      "über",
+      "a@b",
+      "v1.0",
+    ] {
+      assert!(check_ref_format(name).is_ok(), "{}", name);
+    }
+  }
+
+  #[test]
+  fn test_check_ref_format_invalid() {
+    for name in [
+      "", "@", "/a", "a/", "a.", "a..b", "a//b", "a@{b", "a b", "a~1", "a^", "a:b", "a?",
+      "a*", "a[b", "a\\b", "a\tb", ".a", "a/.b", "a.lock", "a.lock/b",
+    ] {
+      assert!(check_ref_format(name).is_err(), "{:?}", name);
+    }
+  }
+
+  #[test]
+  fn test_check_short_ref_name() {
+    assert!(check_short_ref_name("-a").is_err());
+    assert!(check_short_ref_name("HEAD").is_err());
+    assert!(check_short_ref_name("a-HEAD").is_ok());
+  }
+}
@@ -0,0 +1,132 @@
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::f;
+use crate::git::git_types::RefInfo;
+use crate::git::queries::refs::{make_ref_info, RefInfoPart, P_REF_NAME};
+use crate::git::run_git::{run_git_err, RunGitOptions};
+use crate::parser::standard_parsers::{ANY_WORD, UNSIGNED_INT, WS};
+use crate::parser::{parse_all_err, Parser};
+use crate::server::request_util::R;
+use crate::{and, many, map2};
+
+#[derive(Debug, Clone, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct RemoteRefChanges {
+  pub added: Vec<RefInfo>,
+  pub removed: Vec<RefInfo>,
+  // Still exists, but points at a different commit.
+  pub updated: Vec<RefInfo>,
+}
+
+// Remote tracking refs for one remote, read directly rather than from the commit log.
+pub fn load_remote_refs(repo_path: &str, remote: &str) -> R<Vec<RefInfo>> {
+  let out = run_git_err(RunGitOptions {
+    repo_path,
+    args: [
+      "for-each-ref",
+      "--format=%(refname) %(objectname) %(committerdate:unix)",
+      &f!("refs/remotes/{}/", remote),
+    ],
+  })?
+  .stdout;
+
+  let refs = parse_all_err(P_REMOTE_REFS, &out)?;
+
+  // Skip symbolic refs like refs/remotes/origin/HEAD, as get_ref_info_from_commits does.
+  Ok(
+    refs
+      .into_iter()
+      .filter(|r| !r.full_name.ends_with("/HEAD"))
+      .collect(),
+  )
+}
+
+const P_REMOTE_REF: Parser<RefInfo> = map2!(
+  and!(P_REF_NAME, WS, ANY_WORD, WS, UNSIGNED_INT, WS),
+  res,
+  make_remote_ref_info(res.0, res.2, &res.4)
+);
+
+const P_REMOTE_REFS: Parser<Vec<RefInfo>> = many!(P_REMOTE_REF);
+
+fn make_remote_ref_info(info: RefInfoPart, commit_id: String, time: &str) -> RefInfo {
+  make_ref_info(
+    info,
+    commit_id,
+    time.parse::<usize>().unwrap_or_default() * 1000,
+  )
+}
+
+pub fn diff_remote_refs(before: &[RefInfo], after: &[RefInfo]) -> RemoteRefChanges {
+  let added = after
+    .iter()
+    .filter(|a| !before.iter().any(|b| b.full_name == a.full_name))
+    .cloned()
+    .collect();
+
+  let removed = before
+    .iter()
+    .filter(|b| !after.iter().any(|a| a.full_name == b.full_name))
+    .cloned()
+    .collect();
+
+  let updated = after
+    .iter()
+    .filter(|a| {
+      before
+        .iter()
+        .any(|b| b.full_name == a.full_name && b.commit_id != a.commit_id)
+    })
+    .cloned()
+    .collect();
+
+  RemoteRefChanges {
+    added,
+    removed,
+    updated,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::git::git_types::RefLocation;
+  use crate::git::queries::refs::remote_refs::{diff_remote_refs, P_REMOTE_REFS};
+  use crate::parser::parse_all;
+
+  #[test]
+  fn test_p_remote_refs() {
+    let out = "refs/remotes/origin/HEAD 2e89669 1700000000
+refs/remotes/origin/main 2e89669 1700000000
+refs/remotes/origin/feature/a 8a3bb1c 1700000100
+";
+    let refs = parse_all(P_REMOTE_REFS, out).unwrap();
+
+    assert_eq!(refs.len(), 3);
+    assert_eq!(refs[2].short_name, "feature/a");
+    assert_eq!(refs[2].remote_name, Some(String::from("origin")));
+    assert_eq!(refs[2].location, RefLocation::Remote);
+    assert_eq!(refs[2].time, 1700000100000);
+  }
+
+  #[test]
+  fn test_diff_remote_refs() {
+    let before = parse_all(
+      P_REMOTE_REFS,
+      "refs/remotes/origin/main aaa 1\nrefs/remotes/origin/old bbb 1\n",
+    )
+    .unwrap();
+    let after = parse_all(
+      P_REMOTE_REFS,
+      "refs/remotes/origin/main ccc 2\nrefs/remotes/origin/new ddd 2\n",
+    )
+    .unwrap();
+
+    let changes = diff_remote_refs(&before, &after);
+
+    assert_eq!(changes.added[0].short_name, "new");
+    assert_eq!(changes.removed[0].short_name, "old");
+    assert_eq!(changes.updated[0].commit_id, "ccc");
+  }
+}
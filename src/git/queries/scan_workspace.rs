@@ -5,7 +5,7 @@ use crate::git::store::{RepoPath, STORE};
 use crate::server::request_util::{ES, R};
 use serde::Deserialize;
 use std::fs::{read_dir, read_to_string};
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use ahash::HashSet;
 use ts_rs::TS;
 use crate::dprintln;
@@ -116,13 +116,16 @@ fn get_git_repo(dir: &Path) -> Option<RepoPath> {
   if dir.is_dir() {
     let git_file_path = dir.join(".git");
 
+    // Submodules and linked worktrees have a .git file pointing at their git dir.
     if git_file_path.is_file() {
       let text = read_to_string(&git_file_path).ok()?;
       let path = parse_submodule_git_file(&text)?;
+      let git_path = dir.join(path);
 
       return Some(RepoPath {
         path: dir.to_path_buf(),
-        git_path: dir.join(path),
+        common_dir: read_common_dir(&git_path).unwrap_or_else(|| git_path.clone()),
+        git_path,
         // submodule: true,
       });
     }
@@ -131,6 +134,7 @@ fn get_git_repo(dir: &Path) -> Option<RepoPath> {
       return Some(RepoPath {
         path: dir.to_path_buf(),
         git_path: dir.join(".git"),
+        common_dir: dir.join(".git"),
         // submodule: false,
       });
     }
@@ -139,6 +143,30 @@ fn get_git_repo(dir: &Path) -> Option<RepoPath> {
   None
 }
 
+// Only linked worktree git dirs have a "commondir" file, e.g. containing "../..".
+fn read_common_dir(git_path: &Path) -> Option<PathBuf> {
+  let text = read_to_string(git_path.join("commondir")).ok()?;
+
+  Some(normalise_path(&git_path.join(text.trim())))
+}
+
+// Removes ".." so paths from different worktrees compare equal.
+fn normalise_path(path: &Path) -> PathBuf {
+  let mut result = PathBuf::new();
+
+  for component in path.components() {
+    match component {
+      Component::ParentDir => {
+        result.pop();
+      }
+      Component::CurDir => {}
+      c => result.push(c),
+    }
+  }
+
+  result
+}
+
 fn parse_submodule_git_file(text: &str) -> Option<String> {
   if let Some(i) = text.chars().position(|c| c == ':') {
     let path = &text[(i + 1)..];
@@ -168,4 +196,14 @@ mod tests {
     assert!(p.is_some());
     assert_eq!(p.unwrap(), "../.git/modules/fiend-ui");
   }
+
+  #[test]
+  fn test_normalise_path() {
+    let path: PathBuf = ["/", "repo", ".git", "worktrees", "wt", "..", ".."]
+      .iter()
+      .collect();
+    let expected: PathBuf = ["/", "repo", ".git"].iter().collect();
+
+    assert_eq!(normalise_path(&path), expected);
+  }
 }
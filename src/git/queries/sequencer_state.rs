@@ -0,0 +1,132 @@
+use std::fs::read_to_string;
+use std::path::Path;
+
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::git::queries::rebase_state::{read_optional, RebaseTodoLine, P_TODO};
+use crate::git::store::STORE;
+use crate::parser::parse_all_err;
+use crate::server::git_request::ReqOptions;
+use crate::server::request_util::R;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, TS)]
+#[ts(export)]
+pub enum SequencerOperation {
+  CherryPick,
+  Revert,
+}
+
+#[derive(Debug, Clone, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct SequencerState {
+  pub operation: SequencerOperation,
+  // The commit that stopped with a conflict, if any.
+  pub current_commit_id: Option<String>,
+  // Only filled in for multi-commit picks. A single commit doesn't use .git/sequencer.
+  pub remaining: Vec<RebaseTodoLine>,
+  // HEAD before the operation started. --abort goes back here.
+  pub original_head: Option<String>,
+}
+
+// Returns None if there isn't a cherry-pick or revert in progress.
+pub fn load_sequencer_state(options: &ReqOptions) -> R<Option<SequencerState>> {
+  let repo = STORE.get_repo_path(&options.repo_path)?;
+
+  calc_sequencer_state(&repo.git_path)
+}
+
+fn calc_sequencer_state(git_path: &Path) -> R<Option<SequencerState>> {
+  let cherry_pick_head = read_optional(&git_path.join("CHERRY_PICK_HEAD"));
+  let revert_head = read_optional(&git_path.join("REVERT_HEAD"));
+
+  let sequencer_dir = git_path.join("sequencer");
+  let remaining = match read_to_string(sequencer_dir.join("todo")) {
+    Ok(text) => parse_all_err(P_TODO, &text)?,
+    Err(_) => Vec::new(),
+  };
+
+  let operation = if cherry_pick_head.is_some() {
+    SequencerOperation::CherryPick
+  } else if revert_head.is_some() {
+    SequencerOperation::Revert
+  } else {
+    // Stopped between commits (e.g. after a resolved conflict was committed). The todo
+    // tells us which operation it was.
+    match remaining.first() {
+      Some(line) if line.command == "revert" => SequencerOperation::Revert,
+      Some(_) => SequencerOperation::CherryPick,
+      None => return Ok(None),
+    }
+  };
+
+  Ok(Some(SequencerState {
+    operation,
+    current_commit_id: cherry_pick_head.or(revert_head),
+    remaining,
+    original_head: read_optional(&sequencer_dir.join("head")),
+  }))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs::write;
+
+  use crate::git::queries::sequencer_state::{calc_sequencer_state, SequencerOperation};
+  use crate::util::test_util::{write_file, TempDir, ID_A as A, ID_B as B, ID_C as C};
+
+  #[test]
+  fn test_nothing_in_progress() {
+    let (_dir, git_path) = TempDir::with_git_dir("sequencer_none");
+
+    assert!(calc_sequencer_state(&git_path).unwrap().is_none());
+  }
+
+  #[test]
+  fn test_single_cherry_pick() {
+    let (_dir, git_path) = TempDir::with_git_dir("sequencer_single");
+    write(git_path.join("CHERRY_PICK_HEAD"), format!("{}\n", A)).unwrap();
+
+    let state = calc_sequencer_state(&git_path).unwrap().unwrap();
+
+    assert_eq!(state.operation, SequencerOperation::CherryPick);
+    assert_eq!(state.current_commit_id.as_deref(), Some(A));
+    assert!(state.remaining.is_empty());
+    assert_eq!(state.original_head, None);
+  }
+
+  #[test]
+  fn test_multi_commit_revert() {
+    let (_dir, git_path) = TempDir::with_git_dir("sequencer_revert");
+    let sequencer_dir = git_path.join("sequencer");
+    write(git_path.join("REVERT_HEAD"), format!("{}\n", A)).unwrap();
+    write_file(
+      &sequencer_dir.join("todo"),
+      &format!("revert {} First\nrevert {} Second\n", A, B),
+    );
+    write(sequencer_dir.join("head"), format!("{}\n", C)).unwrap();
+
+    let state = calc_sequencer_state(&git_path).unwrap().unwrap();
+
+    assert_eq!(state.operation, SequencerOperation::Revert);
+    assert_eq!(state.current_commit_id.as_deref(), Some(A));
+    assert_eq!(state.remaining.len(), 2);
+    assert_eq!(state.remaining[1].commit_id.as_deref(), Some(B));
+    assert_eq!(state.original_head.as_deref(), Some(C));
+  }
+
+  #[test]
+  fn test_stopped_between_commits() {
+    let (_dir, git_path) = TempDir::with_git_dir("sequencer_between");
+    write_file(
+      &git_path.join("sequencer").join("todo"),
+      &format!("revert {} Second\n", B),
+    );
+
+    let state = calc_sequencer_state(&git_path).unwrap().unwrap();
+
+    assert_eq!(state.operation, SequencerOperation::Revert);
+    assert_eq!(state.current_commit_id, None);
+  }
+}
@@ -1,30 +1,22 @@
 use loggers::elapsed;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
 
-use crate::git::git_types::CommitInfo;
+use crate::git::git_types::{CommitInfo, DateResult, Patch};
 use crate::git::queries::commits_parsers::{PRETTY_FORMATTED, P_COMMITS};
+use crate::git::queries::lfs::mark_lfs_patches;
+use crate::git::queries::patches::patch_parsers::{map_data_to_patch, P_PATCHES};
+use crate::git::queries::COMMIT_0_ID;
 use crate::git::run_git;
 use crate::git::run_git::RunGitOptions;
 use crate::git::store::PathString;
 use crate::parser::parse_all_err;
+use crate::server::git_request::ReqOptions;
 use crate::server::request_util::R;
 
 #[elapsed]
 pub fn load_stashes(repo_path: &PathString) -> R<Vec<CommitInfo>> {
-  let out = run_git::run_git_err(RunGitOptions {
-    args: [
-      "reflog",
-      "show",
-      "stash",
-      // "-z",
-      "--decorate=full",
-      PRETTY_FORMATTED,
-      "--date=raw",
-    ],
-    repo_path,
-  })?
-  .stdout;
-
-  let mut commits = parse_all_err(P_COMMITS, out.as_str())?;
+  let mut commits = load_stash_reflog(repo_path)?;
 
   for (i, c) in commits.iter_mut().enumerate() {
     c.stash_id = format!("refs/stash@{{{}}}", i);
@@ -41,6 +33,24 @@ pub fn load_stashes(repo_path: &PathString) -> R<Vec<CommitInfo>> {
   Ok(commits)
 }
 
+fn load_stash_reflog(repo_path: &str) -> R<Vec<CommitInfo>> {
+  let out = run_git::run_git_err(RunGitOptions {
+    args: [
+      "reflog",
+      "show",
+      "stash",
+      // "-z",
+      "--decorate=full",
+      PRETTY_FORMATTED,
+      "--date=raw",
+    ],
+    repo_path,
+  })?
+  .stdout;
+
+  parse_all_err(P_COMMITS, out.as_str())
+}
+
 fn tidy_commit_message(message: &str) -> String {
   message
     .split(':')
@@ -48,3 +58,140 @@ fn tidy_commit_message(message: &str) -> String {
     .unwrap_or("Stash")
     .replace("WIP", "Stash")
 }
+
+#[derive(Debug, Clone, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct StashInfo {
+  // E.g. "refs/stash@{0}". Matches CommitInfo.stash_id.
+  pub stash_id: String,
+  pub commit_id: String,
+  // The commit HEAD was on when the stash was made.
+  pub base_commit_id: String,
+  // None if the stash was made on a detached HEAD.
+  pub branch: Option<String>,
+  pub message: String,
+  pub date: DateResult,
+  pub has_untracked: bool,
+}
+
+// Unlike load_stashes, this keeps the branch, the full message and the untracked parent.
+pub fn load_stash_list(options: &ReqOptions) -> R<Vec<StashInfo>> {
+  let commits = load_stash_reflog(&options.repo_path)?;
+
+  Ok(
+    commits
+      .into_iter()
+      .enumerate()
+      .map(|(i, c)| {
+        let (branch, message) = parse_stash_message(&c.message);
+
+        StashInfo {
+          stash_id: format!("refs/stash@{{{}}}", i),
+          base_commit_id: c.parent_ids.first().cloned().unwrap_or_default(),
+          has_untracked: c.parent_ids.len() > 2,
+          commit_id: c.id,
+          branch,
+          message,
+          date: c.date,
+        }
+      })
+      .collect(),
+  )
+}
+
+/*
+Git writes "WIP on <branch>: <short id> <subject>" for a stash without a message and
+"On <branch>: <message>" otherwise. The branch is "(no branch)" when detached.
+ */
+pub(crate) fn parse_stash_message(message: &str) -> (Option<String>, String) {
+  let rest = message
+    .strip_prefix("WIP on ")
+    .or_else(|| message.strip_prefix("On "));
+
+  if let Some((branch, text)) = rest.and_then(|r| r.split_once(": ")) {
+    let branch = if branch == "(no branch)" {
+      None
+    } else {
+      Some(branch.to_string())
+    };
+
+    return (branch, text.trim().to_string());
+  }
+
+  (None, message.trim().to_string())
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct StashPatchesOptions {
+  pub repo_path: String,
+  pub commit_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct StashPatches {
+  pub tracked: Vec<Patch>,
+  // From the stash's third parent. The patches' commit_id is that parent's id, so hunks
+  // load from the right place.
+  pub untracked: Vec<Patch>,
+}
+
+pub fn load_stash_patches(options: &StashPatchesOptions) -> R<StashPatches> {
+  let StashPatchesOptions {
+    repo_path,
+    commit_id,
+  } = options;
+
+  let mut tracked = load_diff_patches(repo_path, &format!("{}^1", commit_id), commit_id)?;
+
+  let untracked_id = run_git::run_git_err(RunGitOptions {
+    repo_path,
+    args: [
+      "rev-parse",
+      "--verify",
+      "--quiet",
+      &format!("{}^3", commit_id),
+    ],
+  })?
+  .stdout
+  .trim()
+  .to_string();
+
+  let mut untracked = if untracked_id.is_empty() {
+    Vec::new()
+  } else {
+    load_diff_patches(repo_path, COMMIT_0_ID, &untracked_id)?
+  };
+
+  mark_lfs_patches(repo_path, &mut tracked);
+  mark_lfs_patches(repo_path, &mut untracked);
+
+  Ok(StashPatches { tracked, untracked })
+}
+
+fn load_diff_patches(repo_path: &str, from: &str, to: &str) -> R<Vec<Patch>> {
+  let out = run_git::run_git_err(RunGitOptions {
+    repo_path,
+    args: [
+      "diff",
+      &format!("{}..{}", from, to),
+      "--no-color",
+      "--name-status",
+      "-z",
+    ],
+  })?
+  .stdout;
+
+  let patch_data = parse_all_err(P_PATCHES, &out)?;
+
+  Ok(
+    patch_data
+      .into_iter()
+      .map(|data| map_data_to_patch(data, to.to_string()))
+      .collect(),
+  )
+}
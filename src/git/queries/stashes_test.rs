@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod tests {
-  use crate::git::queries::stashes::load_stashes;
+  use crate::git::queries::stashes::{load_stashes, parse_stash_message};
 
   #[test]
   fn test_load_stashes() {
@@ -8,4 +8,27 @@ mod tests {
 
     println!("{:?}", result);
   }
+
+  #[test]
+  fn test_parse_stash_message() {
+    assert_eq!(
+      parse_stash_message("WIP on main: 2e89669 Fix thing"),
+      (
+        Some(String::from("main")),
+        String::from("2e89669 Fix thing")
+      )
+    );
+    assert_eq!(
+      parse_stash_message("On feature/a: my: message"),
+      (Some(String::from("feature/a")), String::from("my: message"))
+    );
+    assert_eq!(
+      parse_stash_message("On (no branch): detached"),
+      (None, String::from("detached"))
+    );
+    assert_eq!(
+      parse_stash_message("Something else"),
+      (None, String::from("Something else"))
+    );
+  }
 }
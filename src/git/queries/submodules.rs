@@ -0,0 +1,217 @@
+use ahash::AHashMap;
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::git::run_git::{run_git_err, RunGitOptions};
+use crate::parser::standard_parsers::{ANY_WORD, UNTIL_LINE_END};
+use crate::parser::{parse_all_err, Parser};
+use crate::server::git_request::ReqOptions;
+use crate::server::request_util::R;
+use crate::{and, character, many, map2, or};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, TS)]
+#[ts(export)]
+pub enum SubmoduleState {
+  Uninitialized,
+  InSync,
+  // Checked out commit differs from the one recorded in the parent repo.
+  OutOfSync,
+  Conflicted,
+}
+
+#[derive(Debug, Clone, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct SubmoduleStatus {
+  // Relative to the top repo, including nested submodules.
+  pub path: String,
+  pub state: SubmoduleState,
+  // The commit the parent repo's index records.
+  pub recorded_commit_id: String,
+  // None if not initialized.
+  pub checked_out_commit_id: Option<String>,
+  // E.g. "heads/main" or "v1.0-2-g2e89669".
+  pub describe: Option<String>,
+  // Has modified or untracked files. Only known for top level submodules.
+  pub dirty: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct StatusLine {
+  prefix: char,
+  commit_id: String,
+  path: String,
+  describe: Option<String>,
+}
+
+pub fn load_submodule_status(options: &ReqOptions) -> R<Vec<SubmoduleStatus>> {
+  let repo_path = &options.repo_path;
+
+  let checked_out = run_submodule_status(repo_path, false)?;
+  let recorded: AHashMap<String, String> = run_submodule_status(repo_path, true)?
+    .into_iter()
+    .map(|line| (line.path, line.commit_id))
+    .collect();
+
+  let dirty = load_dirty_submodules(repo_path)?;
+
+  Ok(
+    checked_out
+      .into_iter()
+      .map(|line| {
+        let StatusLine {
+          prefix,
+          commit_id,
+          path,
+          describe,
+        } = line;
+
+        let state = match prefix {
+          '-' => SubmoduleState::Uninitialized,
+          '+' => SubmoduleState::OutOfSync,
+          'U' => SubmoduleState::Conflicted,
+          _ => SubmoduleState::InSync,
+        };
+
+        SubmoduleStatus {
+          recorded_commit_id: recorded.get(&path).cloned().unwrap_or(commit_id.clone()),
+          checked_out_commit_id: if state == SubmoduleState::Uninitialized {
+            None
+          } else {
+            Some(commit_id)
+          },
+          dirty: dirty.contains(&path),
+          path,
+          state,
+          describe,
+        }
+      })
+      .collect(),
+  )
+}
+
+fn run_submodule_status(repo_path: &str, cached: bool) -> R<Vec<StatusLine>> {
+  let mut args = vec!["submodule", "status", "--recursive"];
+  if cached {
+    args.push("--cached");
+  }
+
+  let out = run_git_err(RunGitOptions { repo_path, args })?.stdout;
+
+  parse_all_err(P_STATUS_LINES, &out)
+}
+
+const P_STATUS_LINE: Parser<StatusLine> = map2!(
+  and!(
+    or!(
+      character!(' '),
+      character!('-'),
+      character!('+'),
+      character!('U')
+    ),
+    ANY_WORD,
+    character!(' '),
+    UNTIL_LINE_END
+  ),
+  res,
+  make_status_line(res.0, res.1, &res.3)
+);
+
+const P_STATUS_LINES: Parser<Vec<StatusLine>> = many!(P_STATUS_LINE);
+
+// The path can contain spaces, so the describe part is found from the end.
+fn make_status_line(prefix: char, commit_id: String, rest: &str) -> StatusLine {
+  if rest.ends_with(')') {
+    if let Some((path, describe)) = rest.rsplit_once(" (") {
+      return StatusLine {
+        prefix,
+        commit_id,
+        path: path.to_string(),
+        describe: Some(describe.trim_end_matches(')').to_string()),
+      };
+    }
+  }
+
+  StatusLine {
+    prefix,
+    commit_id,
+    path: rest.to_string(),
+    describe: None,
+  }
+}
+
+// Porcelain v2 entries for changed submodules look like:
+// 1 .M S.MU 160000 160000 160000 <head id> <index id> <path>
+// Where M is modified tracked files and U is untracked files.
+fn load_dirty_submodules(repo_path: &str) -> R<Vec<String>> {
+  let out = run_git_err(RunGitOptions {
+    repo_path,
+    args: ["status", "--porcelain=v2", "-z", "--ignore-submodules=none"],
+  })?
+  .stdout;
+
+  Ok(parse_dirty_submodules(&out))
+}
+
+fn parse_dirty_submodules(out: &str) -> Vec<String> {
+  out
+    .split('\0')
+    .filter_map(|entry| {
+      let fields: Vec<&str> = entry.splitn(9, ' ').collect();
+
+      if fields.len() == 9 && fields[0] == "1" && fields[2].starts_with('S') {
+        let flags = fields[2].as_bytes();
+
+        if flags[2] == b'M' || flags[3] == b'U' {
+          return Some(fields[8].to_string());
+        }
+      }
+
+      None
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::git::queries::submodules::{
+    parse_dirty_submodules, StatusLine, P_STATUS_LINES,
+  };
+  use crate::parser::parse_all;
+
+  #[test]
+  fn test_p_status_lines() {
+    let out = " 1a7b35b my sub (heads/master)
++c603bd6 other (c603bd6)
+-1a7b35b uninit
+";
+    let lines = parse_all(P_STATUS_LINES, out).unwrap();
+
+    assert_eq!(lines.len(), 3);
+    assert_eq!(
+      lines[0],
+      StatusLine {
+        prefix: ' ',
+        commit_id: String::from("1a7b35b"),
+        path: String::from("my sub"),
+        describe: Some(String::from("heads/master")),
+      }
+    );
+    assert_eq!(lines[1].prefix, '+');
+    assert_eq!(lines[2].path, "uninit");
+    assert_eq!(lines[2].describe, None);
+  }
+
+  #[test]
+  fn test_parse_dirty_submodules() {
+    let out = [
+      "1 .M S..U 160000 160000 160000 1a7b 1a7b my sub",
+      "1 .M SC.. 160000 160000 160000 1a7b 1a7b other",
+      "1 .M N... 100644 100644 100644 2e89 2e89 file.txt",
+      "",
+    ]
+    .join("\0");
+
+    assert_eq!(parse_dirty_submodules(&out), vec!["my sub"]);
+  }
+}
@@ -0,0 +1,219 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::f;
+use crate::git::git_types::DateResult;
+use crate::git::queries::commits_parsers::P_DATE;
+use crate::git::run_git::{run_git_err, RunGitOptions};
+use crate::parser::standard_parsers::UNTIL_NUL;
+use crate::parser::{parse_all, parse_all_err, Parser};
+use crate::server::git_request::ReqOptions;
+use crate::server::request_util::R;
+use crate::{and, character, many, map2};
+
+#[derive(Debug, Clone, PartialEq, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct TagInfo {
+  pub name: String,
+  pub full_name: String,
+  // The commit the tag points to, after peeling annotated tags.
+  pub commit_id: String,
+  pub annotated: bool,
+  // The rest are only set for annotated tags.
+  pub tagger: Option<String>,
+  pub tagger_email: Option<String>,
+  pub date: Option<DateResult>,
+  pub message: Option<String>,
+  pub signature: TagSignature,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, TS)]
+#[ts(export)]
+pub enum TagSignature {
+  Unsigned,
+  // Has a signature block. Use verify_tag to check it.
+  Signed,
+}
+
+const FORMAT: &str = "--format=%(refname)%00%(objecttype)%00%(objectname)%00\
+%(*objectname)%00%(taggername)%00%(taggeremail)%00%(taggerdate:raw)%00\
+%(contents:subject)%00%(contents:body)%00%(contents:signature)%00";
+
+pub fn load_tags(options: &ReqOptions) -> R<Vec<TagInfo>> {
+  let out = run_git_err(RunGitOptions {
+    repo_path: &options.repo_path,
+    args: ["for-each-ref", "--sort=-creatordate", FORMAT, "refs/tags"],
+  })?
+  .stdout;
+
+  parse_all_err(P_TAGS, &out)
+}
+
+const P_TAG: Parser<TagInfo> = map2!(
+  and!(
+    /* 0 */ UNTIL_NUL,
+    /* 1 */ UNTIL_NUL,
+    /* 2 */ UNTIL_NUL,
+    /* 3 */ UNTIL_NUL,
+    /* 4 */ UNTIL_NUL,
+    /* 5 */ UNTIL_NUL,
+    /* 6 */ UNTIL_NUL,
+    /* 7 */ UNTIL_NUL,
+    /* 8 */ UNTIL_NUL,
+    /* 9 */ UNTIL_NUL,
+    /* 10 */ character!('\n')
+  ),
+  res,
+  make_tag_info(res.0, res.1, res.2, res.3, res.4, res.5, res.6, res.7, res.8, res.9)
+);
+
+const P_TAGS: Parser<Vec<TagInfo>> = many!(P_TAG);
+
+#[allow(clippy::too_many_arguments)]
+fn make_tag_info(
+  full_name: String,
+  object_type: String,
+  object_id: String,
+  peeled_id: String,
+  tagger: String,
+  tagger_email: String,
+  date: String,
+  subject: String,
+  body: String,
+  signature: String,
+) -> TagInfo {
+  let name = full_name
+    .strip_prefix("refs/tags/")
+    .unwrap_or(&full_name)
+    .to_string();
+
+  let signature = if signature.is_empty() {
+    TagSignature::Unsigned
+  } else {
+    TagSignature::Signed
+  };
+
+  // Lightweight tags point straight at the commit. The "contents" fields are then the
+  // commit's, so we ignore them.
+  if object_type != "tag" {
+    return TagInfo {
+      name,
+      full_name,
+      commit_id: object_id,
+      annotated: false,
+      tagger: None,
+      tagger_email: None,
+      date: None,
+      message: None,
+      signature: TagSignature::Unsigned,
+    };
+  }
+
+  let body = body.trim();
+  let message = if body.is_empty() {
+    subject
+  } else {
+    f!("{}\n\n{}", subject, body)
+  };
+
+  TagInfo {
+    name,
+    full_name,
+    commit_id: peeled_id,
+    annotated: true,
+    tagger: Some(tagger),
+    tagger_email: Some(
+      tagger_email
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .to_string(),
+    ),
+    date: parse_all(P_DATE, &date),
+    message: Some(message),
+    signature,
+  }
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct VerifyTagOptions {
+  pub repo_path: String,
+  pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct TagVerification {
+  pub valid: bool,
+  // What gpg or ssh-keygen printed, e.g. who signed it.
+  pub output: String,
+}
+
+pub fn verify_tag(options: &VerifyTagOptions) -> R<TagVerification> {
+  let out = run_git_err(RunGitOptions {
+    repo_path: &options.repo_path,
+    // A name starting with "-" would be taken as an option otherwise.
+    args: ["verify-tag", "--raw", "--", &options.name],
+  })?;
+
+  // --raw gives gpg status lines. SSH signatures print a plain message instead.
+  let output = out.stderr.trim().to_string();
+  let valid =
+    output.contains("[GNUPG:] GOODSIG") || output.starts_with("Good \"git\" signature");
+
+  Ok(TagVerification { valid, output })
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::f;
+  use crate::git::queries::tags::{TagSignature, P_TAGS};
+  use crate::parser::parse_all;
+
+  #[test]
+  fn test_p_tags() {
+    let light = [
+      "refs/tags/light",
+      "commit",
+      "5373",
+      "",
+      "",
+      "",
+      "",
+      "c2b",
+      "",
+      "",
+    ];
+    let annotated = [
+      "refs/tags/v1",
+      "tag",
+      "3ca6",
+      "5373",
+      "a",
+      "<a@b>",
+      "1792379036 +0000",
+      "Release one",
+      "Body here\n",
+      "",
+    ];
+    let out = f!("{}\0\n{}\0\n", light.join("\0"), annotated.join("\0"));
+
+    let tags = parse_all(P_TAGS, &out).unwrap();
+
+    assert_eq!(tags.len(), 2);
+    assert_eq!(tags[0].name, "light");
+    assert!(!tags[0].annotated);
+    assert_eq!(tags[0].message, None);
+    assert_eq!(tags[1].commit_id, "5373");
+    assert_eq!(tags[1].tagger_email, Some(String::from("a@b")));
+    assert_eq!(tags[1].date.as_ref().unwrap().ms, 1792379036000);
+    assert_eq!(
+      tags[1].message,
+      Some(String::from("Release one\n\nBody here"))
+    );
+    assert_eq!(tags[1].signature, TagSignature::Unsigned);
+  }
+}
@@ -6,6 +6,7 @@ use crate::git::store::STORE;
 use crate::server::git_request::ReqOptions;
 use std::fs::read_to_string;
 
+// Rebase state is per worktree, so this is under git_path rather than common_dir.
 pub fn is_rebase_in_progress(options: &ReqOptions) -> bool {
   if let Ok(path) = STORE.get_repo_path(&options.repo_path) {
     return path.git_path.join("rebase-merge").exists();
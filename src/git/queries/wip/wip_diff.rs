@@ -66,11 +66,14 @@ pub fn load_wip_hunk_lines(options: &ReqWipHunksOptions) -> R<(Vec<HunkLine>, bo
   let WipPatch {
     new_file,
     is_image,
+    is_lfs,
     patch_type,
     ..
   } = patch;
 
-  if *is_image {
+  // HEAD has the lfs pointer while the working file has the real content, so a text
+  // diff isn't useful.
+  if *is_image || *is_lfs {
     return Ok((Vec::new(), true));
   }
 
@@ -1,4 +1,5 @@
 use crate::git::git_types::{WipPatch, WipPatchType};
+use crate::git::queries::lfs::find_lfs_paths;
 use crate::git::queries::patches::file_is_image;
 use crate::git::queries::wip::read_merge_head;
 use crate::git::queries::wip::wip_patch_parsers::P_WIP_PATCHES;
@@ -33,7 +34,13 @@ pub fn load_wip_patches(options: &ReqOptions) -> R<WipPatches> {
 
   let info = parse_all_err(P_WIP_PATCHES, &stdout)?;
 
-  let (patches, conflicted) = get_patches_from_info(info);
+  let (mut patches, conflicted) = get_patches_from_info(info);
+
+  let paths: Vec<&str> = patches.iter().map(|p| p.new_file.as_str()).collect();
+  let lfs_paths = find_lfs_paths(&options.repo_path, &paths);
+  for patch in patches.iter_mut() {
+    patch.is_lfs = lfs_paths.contains(&patch.new_file);
+  }
 
   if conflicted {
     if let Some(id) = read_merge_head(&options.repo_path) {
@@ -77,6 +84,7 @@ fn get_patches_from_info(info: Vec<WipPatchInfo>) -> (Vec<WipPatch>, bool) {
       conflicted,
       id: format!("{}{}", &new_file, patch_type),
       is_image: file_is_image(&new_file),
+      is_lfs: false,
     })
   }
 
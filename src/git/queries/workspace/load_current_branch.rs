@@ -1,4 +1,6 @@
 use crate::f;
+use crate::git::queries::refs::native_refs::{read_head, HeadRef};
+use crate::git::run_git::{run_git_err, RunGitOptions};
 use crate::git::store::STORE;
 use crate::server::request_util::{ES, R};
 use std::collections::HashSet;
@@ -6,24 +8,50 @@ use std::fs::{read_dir, read_to_string};
 use std::path::{Path, PathBuf};
 
 pub fn load_current_branch(repo_path: &str) -> R<(String, String)> {
-  let repo = STORE.get_repo_path(repo_path)?;
-  let head = repo.git_path.join("HEAD");
+  // Each worktree has its own HEAD.
+  let head = match read_head(repo_path) {
+    Ok(head) => head,
+    // Reftable repos don't keep refs in files.
+    Err(_) => read_head_with_git(repo_path)?,
+  };
 
-  if let Ok(text) = read_to_string(head) {
-    return if let Some(branch) = text.split(':').last() {
-      let id = branch.trim();
-      let name = id.replace("refs/heads/", "");
-      Ok((id.to_string(), name))
-    } else {
-      Err(ES::from(
-        "Failed to load current branch. Failed to parse .git/HEAD. Could be a detached head?",
-      ))
-    };
+  match head {
+    HeadRef::Branch { name, .. } => {
+      let short_name = name.replace("refs/heads/", "");
+      Ok((name, short_name))
+    }
+    HeadRef::Detached { commit_id } => Ok((commit_id.clone(), commit_id)),
+  }
+}
+
+fn read_head_with_git(repo_path: &str) -> R<HeadRef> {
+  let name = run_git_text(repo_path, ["symbolic-ref", "-q", "HEAD"])?;
+
+  if !name.is_empty() {
+    return Ok(HeadRef::Branch {
+      name,
+      commit_id: None,
+    });
+  }
+
+  let commit_id = run_git_text(repo_path, ["rev-parse", "-q", "--verify", "HEAD"])?;
+
+  if commit_id.is_empty() {
+    return Err(ES::from(
+      "Failed to load current branch. Failed to read HEAD",
+    ));
   }
 
-  Err(ES::from(
-    "Failed to load current branch. Failed to read .git/HEAD",
-  ))
+  Ok(HeadRef::Detached { commit_id })
+}
+
+fn run_git_text<const N: usize>(repo_path: &str, args: [&str; N]) -> R<String> {
+  Ok(
+    run_git_err(RunGitOptions { repo_path, args })?
+      .stdout
+      .trim()
+      .to_string(),
+  )
 }
 
 // Some info
@@ -37,7 +65,7 @@ pub fn read_refs(repo_path: &str, branch_name: &str) -> R<Refs> {
   };
 
   let repo = STORE.get_repo_path(repo_path)?;
-  let path = repo.git_path.join("refs");
+  let path = repo.common_dir.join("refs");
 
   let heads_dir = path.join("heads");
 
@@ -155,7 +183,11 @@ fn read_id_from_ref_file(file: &PathBuf) -> R<String> {
 
 #[cfg(test)]
 mod tests {
-  use crate::git::queries::workspace::load_current_branch::get_ref_name_from_path;
+  use crate::git::queries::refs::native_refs::HeadRef;
+  use crate::git::queries::workspace::load_current_branch::{
+    get_ref_name_from_path, read_head_with_git,
+  };
+  use crate::util::test_util::TempDir;
   use std::path::PathBuf;
 
   #[test]
@@ -167,4 +199,28 @@ mod tests {
 
     assert_eq!(res, "cc/dd")
   }
+
+  #[test]
+  fn test_read_head_with_git() {
+    let repo = TempDir::with_repo("head_with_git");
+    let repo_path = repo.repo_path();
+
+    repo.commit("c1", 1_600_001_000);
+
+    assert_eq!(
+      read_head_with_git(&repo_path).unwrap(),
+      HeadRef::Branch {
+        name: String::from("refs/heads/main"),
+        commit_id: None,
+      }
+    );
+
+    let id = repo.git(&["rev-parse", "HEAD"]).trim().to_string();
+    repo.git(&["checkout", "-q", "--detach"]);
+
+    assert_eq!(
+      read_head_with_git(&repo_path).unwrap(),
+      HeadRef::Detached { commit_id: id }
+    );
+  }
 }
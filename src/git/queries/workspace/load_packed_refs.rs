@@ -7,7 +7,7 @@ use std::fs::read_to_string;
 
 pub fn load_packed_refs(repo_path: &str) -> R<Vec<PackedRef>> {
   let repo = STORE.get_repo_path(repo_path)?;
-  let path = repo.git_path.join("packed-refs");
+  let path = repo.common_dir.join("packed-refs");
 
   let text = read_to_string(path)?;
 
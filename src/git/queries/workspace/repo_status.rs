@@ -1,6 +1,7 @@
 use crate::git::queries::commit_calcs::count_commits_between_fallback;
 use crate::git::queries::config::load_full_config;
 use crate::git::queries::config::GitConfig;
+use crate::git::queries::refs::head_info::{load_head_state, HeadState};
 use crate::git::queries::wip::wip_patches::{load_wip_patches, WipPatches};
 use crate::git::queries::workspace::load_current_branch::{
   load_current_branch, read_refs, Refs,
@@ -27,6 +28,8 @@ pub struct RepoStatus {
   remote_ahead: u32,
   remote_behind: u32,
   state: BranchState,
+  // None if we couldn't read HEAD ourselves, e.g. with reftable.
+  head_state: Option<HeadState>,
 }
 
 #[derive(Debug, Serialize, TS)]
@@ -43,6 +46,7 @@ pub fn load_repo_status(options: &ReqOptions) -> R<RepoStatus> {
   let patches = load_wip_patches(options)?;
   let config = load_full_config(options)?;
 
+  let head_state = load_head_state(repo_path).ok();
   let (head_id, current_branch) = load_current_branch(repo_path)?;
 
   let Refs {
@@ -98,6 +102,7 @@ pub fn load_repo_status(options: &ReqOptions) -> R<RepoStatus> {
         remote_ahead,
         remote_behind,
         state: BranchState::Both,
+        head_state,
       });
     }
   }
@@ -126,5 +131,6 @@ pub fn load_repo_status(options: &ReqOptions) -> R<RepoStatus> {
     remote_ahead: 0,
     remote_behind: 0,
     state,
+    head_state,
   })
 }
@@ -0,0 +1,115 @@
+use std::path::Path;
+
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::git::run_git::{run_git_err, RunGitOptions};
+use crate::parser::standard_parsers::{UNTIL_LINE_END, WS};
+use crate::parser::{parse_all_err, Parser};
+use crate::server::git_request::ReqOptions;
+use crate::server::request_util::R;
+use crate::{and, many, map2, take_char_while};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct WorktreeInfo {
+  pub path: String,
+  pub head_commit_id: Option<String>,
+  // Full ref name, e.g. "refs/heads/main". None when detached.
+  pub branch: Option<String>,
+  pub bare: bool,
+  pub locked: bool,
+  pub lock_reason: Option<String>,
+  // The worktree's directory is gone. "worktree prune" will remove it.
+  pub prunable: bool,
+  // The worktree repo_path refers to.
+  pub current: bool,
+}
+
+pub fn list_worktrees(options: &ReqOptions) -> R<Vec<WorktreeInfo>> {
+  let ReqOptions { repo_path } = options;
+
+  let out = run_git_err(RunGitOptions {
+    repo_path,
+    args: ["worktree", "list", "--porcelain"],
+  })?
+  .stdout;
+
+  let mut worktrees = parse_all_err(P_WORKTREES, &out)?;
+
+  let current = Path::new(repo_path).canonicalize().ok();
+  for w in worktrees.iter_mut() {
+    w.current = current.is_some() && Path::new(&w.path).canonicalize().ok() == current;
+  }
+
+  Ok(worktrees)
+}
+
+// Either "<key> <value>" or just "<key>". Worktrees are separated by a blank line.
+const P_ATTRIBUTE: Parser<(String, String)> = map2!(
+  and!(
+    take_char_while!(|c: char| !c.is_whitespace()),
+    UNTIL_LINE_END
+  ),
+  res,
+  (res.0, res.1.trim().to_string())
+);
+
+const P_WORKTREE: Parser<WorktreeInfo> =
+  map2!(and!(many!(P_ATTRIBUTE), WS), res, make_worktree_info(res.0));
+
+const P_WORKTREES: Parser<Vec<WorktreeInfo>> = many!(P_WORKTREE);
+
+fn make_worktree_info(attributes: Vec<(String, String)>) -> WorktreeInfo {
+  let mut info = WorktreeInfo::default();
+
+  for (key, value) in attributes {
+    let value = if value.is_empty() { None } else { Some(value) };
+
+    match key.as_str() {
+      "worktree" => info.path = value.unwrap_or_default(),
+      "HEAD" => info.head_commit_id = value,
+      "branch" => info.branch = value,
+      "bare" => info.bare = true,
+      "locked" => {
+        info.locked = true;
+        info.lock_reason = value;
+      }
+      "prunable" => info.prunable = true,
+      _ => {}
+    }
+  }
+
+  info
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::git::queries::worktrees::P_WORKTREES;
+  use crate::parser::parse_all;
+
+  #[test]
+  fn test_p_worktrees() {
+    let out = "worktree /tmp/main
+HEAD 53738d67146d480b155faf1ed8900c341966009e
+branch refs/heads/main
+
+worktree /tmp/wt 2
+HEAD 53738d67146d480b155faf1ed8900c341966009e
+detached
+locked on usb
+prunable gitdir file points to non-existent location
+
+";
+    let worktrees = parse_all(P_WORKTREES, out).unwrap();
+
+    assert_eq!(worktrees.len(), 2);
+    assert_eq!(worktrees[0].branch, Some(String::from("refs/heads/main")));
+    assert!(!worktrees[0].locked);
+    assert_eq!(worktrees[1].path, "/tmp/wt 2");
+    assert_eq!(worktrees[1].branch, None);
+    assert_eq!(worktrees[1].lock_reason, Some(String::from("on usb")));
+    assert!(worktrees[1].prunable);
+  }
+}
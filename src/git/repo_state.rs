@@ -0,0 +1,303 @@
+use std::fs::{read, read_dir};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use ahash::AHashMap;
+
+use crate::git::store::{invalidate_config, invalidate_ref_calcs, PathString, STORE};
+use crate::util::bin_io::{checksum, BinWriter};
+use crate::util::global::Global;
+use crate::{dprintln, global};
+
+/*
+A cheap summary of the files git changes when the repo changes, so we can tell when
+cached data is stale without asking git. Most parts are just file sizes and mtimes.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct RepoFingerprint {
+  pub head: u64,
+  // Loose refs and packed-refs.
+  pub refs: u64,
+  // Staged changes. Nothing cached depends on it, callers use it to know the WIP changed.
+  pub index: u64,
+  pub stash: u64,
+  pub config: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct RepoStateChanges {
+  pub head: bool,
+  pub refs: bool,
+  pub index: bool,
+  pub stash: bool,
+  pub config: bool,
+}
+
+impl RepoStateChanges {
+  pub fn any(&self) -> bool {
+    self.head || self.refs || self.index || self.stash || self.config
+  }
+}
+
+impl RepoFingerprint {
+  pub fn changes_from(&self, old: &RepoFingerprint) -> RepoStateChanges {
+    RepoStateChanges {
+      head: self.head != old.head,
+      refs: self.refs != old.refs,
+      index: self.index != old.index,
+      stash: self.stash != old.stash,
+      config: self.config != old.config,
+    }
+  }
+
+  // The parts the commits list depends on.
+  fn commits_key(&self) -> (u64, u64, u64) {
+    (self.head, self.refs, self.stash)
+  }
+}
+
+#[derive(Clone)]
+struct StateCheck {
+  fingerprint: RepoFingerprint,
+  time: Instant,
+}
+
+static REPO_STATES: Global<AHashMap<PathString, StateCheck>> = global!(AHashMap::new());
+// The state each repo's cached commits were loaded in.
+static COMMITS_STATES: Global<AHashMap<PathString, (u64, u64, u64)>> =
+  global!(AHashMap::new());
+
+// A request usually reads a few caches, no need to look at the files for each one.
+const MIN_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/*
+Invalidates the caches that depend on whatever changed since the last check. The
+first check for a repo only records the fingerprint. Cached commits are kept, the
+incremental loader diffs against them. Loaders check commits_changed instead.
+ */
+pub(crate) fn check_repo_state(repo_path: &str) -> RepoStateChanges {
+  if let Some(last) = REPO_STATES.get_by_key(&repo_path.to_string()) {
+    if last.time.elapsed() < MIN_CHECK_INTERVAL {
+      return RepoStateChanges::default();
+    }
+  }
+
+  check_repo_state_now(repo_path)
+}
+
+// Ignores MIN_CHECK_INTERVAL. For when a stale answer would be wrong, e.g. a fast load
+// right after an action finished.
+pub(crate) fn check_repo_state_now(repo_path: &str) -> RepoStateChanges {
+  let last = REPO_STATES.get_by_key(&repo_path.to_string());
+
+  let fingerprint = calc_repo_fingerprint(repo_path);
+
+  REPO_STATES.insert(
+    repo_path.to_string(),
+    StateCheck {
+      fingerprint,
+      time: Instant::now(),
+    },
+  );
+
+  let Some(last) = last else {
+    return RepoStateChanges::default();
+  };
+
+  let changes = fingerprint.changes_from(&last.fingerprint);
+
+  if changes.any() {
+    dprintln!("Repo state changed for {}: {:?}", repo_path, changes);
+  }
+  if changes.head || changes.refs || changes.stash {
+    invalidate_ref_calcs(repo_path);
+  }
+  if changes.config {
+    invalidate_config(repo_path);
+  }
+
+  changes
+}
+
+// Call when commits are cached. Uses the last checked state, which errs towards
+// reloading if the repo changed during the load.
+pub(crate) fn mark_commits_loaded(repo_path: &str) {
+  if let Some(state) = current_state(repo_path) {
+    COMMITS_STATES.insert(repo_path.to_string(), state.fingerprint.commits_key());
+  }
+}
+
+// True if refs, HEAD or stashes changed since the cached commits were loaded.
+pub(crate) fn commits_changed(repo_path: &str) -> bool {
+  check_repo_state_now(repo_path);
+
+  let loaded = COMMITS_STATES.get_by_key(&repo_path.to_string());
+
+  match (loaded, current_state(repo_path)) {
+    (Some(loaded), Some(state)) => loaded != state.fingerprint.commits_key(),
+    _ => true,
+  }
+}
+
+fn current_state(repo_path: &str) -> Option<StateCheck> {
+  if REPO_STATES.get_by_key(&repo_path.to_string()).is_none() {
+    check_repo_state(repo_path);
+  }
+
+  REPO_STATES.get_by_key(&repo_path.to_string())
+}
+
+pub(crate) fn calc_repo_fingerprint(repo_path: &str) -> RepoFingerprint {
+  let (git_path, common_dir) = match STORE.get_repo_path(repo_path) {
+    Ok(p) => (p.git_path, p.common_dir),
+    Err(_) => {
+      let git_path = Path::new(repo_path).join(".git");
+      (git_path.clone(), git_path)
+    }
+  };
+
+  calc_fingerprint(&git_path, &common_dir)
+}
+
+fn calc_fingerprint(git_path: &Path, common_dir: &Path) -> RepoFingerprint {
+  // HEAD is tiny, and switching between branches can keep the same size.
+  let head = read(git_path.join("HEAD"))
+    .map(|bytes| checksum(&bytes))
+    .unwrap_or(0);
+
+  let mut refs = BinWriter::new();
+  add_file_stats(&mut refs, &common_dir.join("packed-refs"));
+  add_dir_stats(&mut refs, &common_dir.join("refs"));
+
+  RepoFingerprint {
+    head,
+    refs: checksum(&refs.buf),
+    index: file_stats(&git_path.join("index")),
+    stash: file_stats(&common_dir.join("logs").join("refs").join("stash")),
+    config: file_stats(&common_dir.join("config")),
+  }
+}
+
+fn file_stats(path: &Path) -> u64 {
+  let mut w = BinWriter::new();
+  add_file_stats(&mut w, path);
+
+  checksum(&w.buf)
+}
+
+fn add_file_stats(w: &mut BinWriter, path: &Path) {
+  if let Ok(meta) = path.metadata() {
+    let modified = meta
+      .modified()
+      .ok()
+      .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+      .map(|d| d.as_nanos() as u64)
+      .unwrap_or(0);
+
+    w.u64(meta.len());
+    w.u64(modified);
+  }
+}
+
+// Loose refs. Sorted, as read_dir order isn't defined.
+fn add_dir_stats(w: &mut BinWriter, dir: &Path) {
+  let Ok(entries) = read_dir(dir) else {
+    return;
+  };
+
+  let mut paths: Vec<PathBuf> = entries.filter_map(|e| Some(e.ok()?.path())).collect();
+  paths.sort();
+
+  for path in paths {
+    if path.is_dir() {
+      add_dir_stats(w, &path);
+    } else {
+      w.str(&path.to_string_lossy());
+      add_file_stats(w, &path);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs::write;
+  use std::thread::sleep;
+
+  use ahash::AHashMap;
+
+  use crate::git::queries::config::GitConfig;
+  use crate::git::repo_state::{
+    calc_fingerprint, check_repo_state, commits_changed, MIN_CHECK_INTERVAL,
+  };
+  use crate::git::store::{CONFIG, REF_DIFFS, STORE};
+  use crate::util::test_util::{write_file, TempDir};
+
+  #[test]
+  fn test_fingerprint_changes() {
+    let (_dir, git_path) = TempDir::with_git_dir("fingerprint");
+
+    write(git_path.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+    write_file(&git_path.join("refs").join("heads").join("main"), "aa11\n");
+
+    let a = calc_fingerprint(&git_path, &git_path);
+    assert_eq!(a, calc_fingerprint(&git_path, &git_path));
+
+    write(git_path.join("HEAD"), "ref: refs/heads/dev1\n").unwrap();
+    write(git_path.join("refs").join("heads").join("dev1"), "bb22\n").unwrap();
+
+    let b = calc_fingerprint(&git_path, &git_path);
+    let changes = b.changes_from(&a);
+
+    assert!(changes.head);
+    assert!(changes.refs);
+    assert!(!changes.stash);
+    assert!(changes.any());
+
+    write(git_path.join("config"), "[core]\n").unwrap();
+
+    let c = calc_fingerprint(&git_path, &git_path);
+    let changes = c.changes_from(&b);
+
+    assert!(changes.config);
+    assert!(!changes.refs);
+    assert!(!changes.index);
+
+    write(git_path.join("index"), "DIRC").unwrap();
+
+    let changes = calc_fingerprint(&git_path, &git_path).changes_from(&c);
+    assert!(changes.index);
+  }
+
+  #[test]
+  fn test_check_repo_state_invalidation() {
+    let (dir, git_path) = TempDir::with_git_dir("check_state");
+    let repo_path = dir.path.to_string_lossy().to_string();
+
+    write(git_path.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+    write_file(&git_path.join("refs").join("heads").join("main"), "aa11\n");
+
+    STORE.insert_commits(&repo_path, &Vec::new(), &Vec::new());
+    REF_DIFFS.insert_repo(&repo_path, AHashMap::new());
+    CONFIG.insert_repo(&repo_path, GitConfig::new());
+    assert!(!commits_changed(&repo_path));
+
+    write(git_path.join("HEAD"), "ref: refs/heads/dev1\n").unwrap();
+    write(git_path.join("refs").join("heads").join("dev1"), "bb22\n").unwrap();
+
+    // Seen straight away, a fast load could follow an action at any time.
+    assert!(commits_changed(&repo_path));
+    // The incremental loader needs the old commits to diff with.
+    assert!(STORE.get_commits_and_refs(&repo_path).is_some());
+    assert!(REF_DIFFS.get_repo(&repo_path).is_none());
+    assert!(CONFIG.get_repo(&repo_path).is_some());
+
+    write(git_path.join("config"), "[core]\n").unwrap();
+    sleep(MIN_CHECK_INTERVAL);
+
+    assert!(check_repo_state(&repo_path).config);
+    assert!(CONFIG.get_repo(&repo_path).is_none());
+
+    STORE.insert_commits(&repo_path, &Vec::new(), &Vec::new());
+    assert!(!commits_changed(&repo_path));
+  }
+}
@@ -0,0 +1,629 @@
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use ahash::{AHashMap, AHashSet};
+use notify::event::EventKind;
+use notify::{Config, Event, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::git::repo_state::check_repo_state;
+use crate::git::run_git::{run_git_with_input, RunGitOptions};
+use crate::git::store::{PathString, STORE};
+use crate::server::git_request::ReqOptions;
+use crate::util::global::Global;
+use crate::{dprintln, global};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct RepoChanges {
+  // Files in the worktree, not counting ignored files.
+  pub worktree: bool,
+  pub index: bool,
+  // Includes packed-refs and the stash.
+  pub refs: bool,
+  pub head: bool,
+  pub config: bool,
+  // Relative to the repo. Only the first MAX_PATHS.
+  pub paths: Vec<String>,
+}
+
+const MAX_PATHS: usize = 200;
+// Builds can write any number of ignored files, don't remember them all.
+const MAX_IGNORED_PATHS: usize = 10_000;
+
+// Git writes a burst of files for most commands. Wait until they've stopped.
+const DEBOUNCE: Duration = Duration::from_millis(150);
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, PartialEq, Eq)]
+enum Change {
+  Worktree(String),
+  Index,
+  Refs,
+  Head,
+  Config,
+}
+
+/*
+Worktree paths wait in unchecked until the next poll asks git which are ignored. Only
+the rest count towards the debounce, and ignored ones are dropped straight away after.
+ */
+#[derive(Default)]
+struct PendingChanges {
+  changes: RepoChanges,
+  // When each path last changed.
+  worktree_paths: AHashMap<String, Instant>,
+  unchecked: AHashMap<String, Instant>,
+  ignored: AHashSet<String>,
+  last_git_event: Option<Instant>,
+}
+
+impl PendingChanges {
+  fn last_event(&self) -> Option<Instant> {
+    self
+      .worktree_paths
+      .values()
+      .copied()
+      .chain(self.last_git_event)
+      .max()
+  }
+}
+
+struct WatchedPaths {
+  path: PathBuf,
+  git_path: PathBuf,
+  common_dir: PathBuf,
+  // Relative to path and ending in "/", as git lists them.
+  ignored_dirs: Vec<String>,
+}
+
+struct RepoWatcher {
+  // Dropping this stops the watcher.
+  _watcher: Box<dyn Watcher + Send + Sync>,
+  pending: Arc<Mutex<PendingChanges>>,
+}
+
+static WATCHERS: Global<AHashMap<PathString, RepoWatcher>> = global!(AHashMap::new());
+
+/*
+Returns what's changed since the last call. The first call starts watching the repo
+and returns nothing. Changes are held back until events have stopped for DEBOUNCE.
+ */
+pub fn poll_repo_changes(options: &ReqOptions) -> RepoChanges {
+  let repo_path = &options.repo_path;
+
+  let pending = match WATCHERS.data.read() {
+    Ok(watchers) => watchers.get(repo_path).map(|w| w.pending.clone()),
+    Err(_) => None,
+  };
+
+  let Some(pending) = pending else {
+    start_watching(repo_path);
+    return RepoChanges::default();
+  };
+
+  check_unchecked_paths(repo_path, &pending);
+
+  let (mut changes, paths) = {
+    let Ok(mut pending) = pending.lock() else {
+      return RepoChanges::default();
+    };
+
+    match pending.last_event() {
+      Some(time) if time.elapsed() >= DEBOUNCE => {}
+      _ => return RepoChanges::default(),
+    }
+
+    pending.last_git_event = None;
+
+    (
+      std::mem::take(&mut pending.changes),
+      std::mem::take(&mut pending.worktree_paths),
+    )
+  };
+
+  if !paths.is_empty() {
+    let mut paths: Vec<String> = paths.into_keys().collect();
+    paths.sort();
+    paths.truncate(MAX_PATHS);
+
+    changes.worktree = true;
+    changes.paths = paths;
+  }
+
+  if changes.head || changes.refs || changes.config {
+    // Drop stale caches now rather than on the next load.
+    check_repo_state(repo_path);
+  }
+
+  changes
+}
+
+// Git isn't run while holding the lock, so events aren't held up.
+fn check_unchecked_paths(repo_path: &str, pending: &Mutex<PendingChanges>) {
+  let unchecked = match pending.lock() {
+    Ok(mut pending) => std::mem::take(&mut pending.unchecked),
+    Err(_) => return,
+  };
+
+  if unchecked.is_empty() {
+    return;
+  }
+
+  let paths: AHashSet<String> = unchecked.keys().cloned().collect();
+  let ignored = find_ignored(repo_path, &paths);
+
+  let Ok(mut pending) = pending.lock() else {
+    return;
+  };
+
+  for (path, time) in unchecked {
+    if !ignored.contains(&path) {
+      let last = pending.worktree_paths.entry(path).or_insert(time);
+      *last = (*last).max(time);
+    }
+  }
+
+  if pending.ignored.len() + ignored.len() > MAX_IGNORED_PATHS {
+    pending.ignored.clear();
+  }
+  pending.ignored.extend(ignored);
+}
+
+pub fn stop_watching_repo(options: &ReqOptions) {
+  if let Ok(mut watchers) = WATCHERS.data.write() {
+    if watchers.remove(&options.repo_path).is_some() {
+      dprintln!("Stopped watching {}", options.repo_path);
+    }
+  }
+}
+
+fn start_watching(repo_path: &str) {
+  let paths = match STORE.get_repo_path(repo_path) {
+    Ok(p) => WatchedPaths {
+      path: p.path,
+      git_path: p.git_path,
+      common_dir: p.common_dir,
+      ignored_dirs: Vec::new(),
+    },
+    Err(_) => {
+      let git_path = Path::new(repo_path).join(".git");
+
+      WatchedPaths {
+        path: PathBuf::from(repo_path),
+        git_path: git_path.clone(),
+        common_dir: git_path,
+        ignored_dirs: Vec::new(),
+      }
+    }
+  };
+
+  let (ignored_dirs, ignored_files) = find_ignored_at_start(repo_path);
+  let paths = WatchedPaths {
+    ignored_dirs,
+    ..paths
+  };
+
+  let pending = Arc::new(Mutex::new(PendingChanges {
+    ignored: ignored_files,
+    ..PendingChanges::default()
+  }));
+
+  match create_watcher(paths, pending.clone()) {
+    Ok(watcher) => {
+      if let Ok(mut watchers) = WATCHERS.data.write() {
+        watchers.insert(
+          repo_path.to_string(),
+          RepoWatcher {
+            _watcher: watcher,
+            pending,
+          },
+        );
+      }
+    }
+    Err(e) => {
+      dprintln!("Failed to watch {}: {}", repo_path, e);
+    }
+  }
+}
+
+// Falls back to polling when we can't use the native watcher, e.g. when we're over
+// the inotify watch limit.
+fn create_watcher(
+  paths: WatchedPaths,
+  pending: Arc<Mutex<PendingChanges>>,
+) -> notify::Result<Box<dyn Watcher + Send + Sync>> {
+  let dirs = watched_dirs(&paths);
+  let paths = Arc::new(paths);
+
+  let native = {
+    let paths = paths.clone();
+    let pending = pending.clone();
+
+    RecommendedWatcher::new(
+      move |event| handle_event(event, &paths, &pending),
+      Config::default(),
+    )
+    .and_then(|mut watcher| {
+      for (dir, mode) in &dirs {
+        watcher.watch(dir, *mode)?;
+      }
+      Ok(watcher)
+    })
+  };
+
+  match native {
+    Ok(watcher) => Ok(Box::new(watcher)),
+    Err(e) => {
+      dprintln!("Native watcher failed, polling instead: {}", e);
+
+      let mut watcher = PollWatcher::new(
+        move |event| handle_event(event, &paths, &pending),
+        Config::default().with_poll_interval(POLL_INTERVAL),
+      )?;
+
+      for (dir, mode) in &dirs {
+        watcher.watch(dir, *mode)?;
+      }
+
+      Ok(Box::new(watcher))
+    }
+  }
+}
+
+// The git dirs are inside the worktree unless this is a linked worktree or submodule.
+fn watched_dirs(paths: &WatchedPaths) -> Vec<(PathBuf, RecursiveMode)> {
+  let mut dirs = Vec::new();
+  add_worktree_dirs(&paths.path, "", &paths.ignored_dirs, &mut dirs);
+
+  for dir in [&paths.common_dir, &paths.git_path] {
+    if !dirs
+      .iter()
+      .any(|(d, mode)| *mode == RecursiveMode::Recursive && dir.starts_with(d))
+    {
+      dirs.push((dir.clone(), RecursiveMode::Recursive));
+    }
+  }
+
+  dirs
+}
+
+/*
+Ignored dirs like "target" or "node_modules" can hold more dirs than the inotify limit
+allows, so we don't watch them. Dirs above one are watched without recursing, which
+means files in dirs made after we start watching are missed until the repo is watched
+again. We still see the new dir itself.
+ */
+fn add_worktree_dirs(
+  dir: &Path,
+  rel: &str,
+  ignored_dirs: &[String],
+  dirs: &mut Vec<(PathBuf, RecursiveMode)>,
+) {
+  if !ignored_dirs.iter().any(|d| d.starts_with(rel)) {
+    dirs.push((dir.to_path_buf(), RecursiveMode::Recursive));
+    return;
+  }
+
+  dirs.push((dir.to_path_buf(), RecursiveMode::NonRecursive));
+
+  let Ok(entries) = read_dir(dir) else {
+    return;
+  };
+
+  for entry in entries.flatten() {
+    // Don't follow symlinks, the recursive watch doesn't either.
+    if !entry.file_type().is_ok_and(|t| t.is_dir()) {
+      continue;
+    }
+
+    let child_rel = format!("{}{}/", rel, entry.file_name().to_string_lossy());
+
+    if !ignored_dirs.contains(&child_rel) {
+      add_worktree_dirs(&entry.path(), &child_rel, ignored_dirs, dirs);
+    }
+  }
+}
+
+fn handle_event(
+  event: notify::Result<Event>,
+  paths: &WatchedPaths,
+  pending: &Mutex<PendingChanges>,
+) {
+  let Ok(event) = event else {
+    return;
+  };
+
+  if matches!(event.kind, EventKind::Access(_)) {
+    return;
+  }
+
+  let changes: Vec<Change> = event
+    .paths
+    .iter()
+    .filter_map(|p| classify_path(p, paths))
+    .collect();
+
+  if changes.is_empty() {
+    return;
+  }
+
+  let Ok(mut pending) = pending.lock() else {
+    return;
+  };
+
+  let now = Instant::now();
+
+  for change in changes {
+    match change {
+      Change::Worktree(path) => {
+        if path.ends_with(".gitignore") {
+          pending.ignored.clear();
+        }
+        if !pending.ignored.contains(&path) {
+          pending.unchecked.insert(path, now);
+        }
+        continue;
+      }
+      Change::Index => pending.changes.index = true,
+      Change::Refs => pending.changes.refs = true,
+      Change::Head => pending.changes.head = true,
+      Change::Config => pending.changes.config = true,
+    }
+
+    pending.last_git_event = Some(now);
+  }
+}
+
+// None for files we don't care about, like objects and lock files.
+fn classify_path(path: &Path, paths: &WatchedPaths) -> Option<Change> {
+  for dir in [&paths.git_path, &paths.common_dir] {
+    if let Ok(rel) = path.strip_prefix(dir) {
+      return classify_git_path(&rel.to_string_lossy().replace('\\', "/"));
+    }
+  }
+
+  let rel = path.strip_prefix(&paths.path).ok()?;
+
+  // A .git file in a submodule or nested repo.
+  if rel.components().any(|c| c.as_os_str() == ".git") || rel.as_os_str().is_empty() {
+    return None;
+  }
+
+  let rel = rel.to_string_lossy().replace('\\', "/");
+
+  // Events from a watch on an ignored dir's parent.
+  if paths
+    .ignored_dirs
+    .iter()
+    .any(|d| rel.starts_with(d.as_str()) || rel == d.trim_end_matches('/'))
+  {
+    return None;
+  }
+
+  Some(Change::Worktree(rel))
+}
+
+fn classify_git_path(rel: &str) -> Option<Change> {
+  if rel.ends_with(".lock") {
+    return None;
+  }
+
+  match rel {
+    "HEAD" => Some(Change::Head),
+    "index" => Some(Change::Index),
+    "config" => Some(Change::Config),
+    "packed-refs" | "logs/refs/stash" => Some(Change::Refs),
+    _ if rel.starts_with("refs/") => Some(Change::Refs),
+    _ => None,
+  }
+}
+
+// Ignored dirs, and the ignored files that aren't in one.
+fn find_ignored_at_start(repo_path: &str) -> (Vec<String>, AHashSet<String>) {
+  let out = match run_git_with_input(
+    RunGitOptions {
+      repo_path,
+      args: [
+        "ls-files",
+        "--others",
+        "--ignored",
+        "--exclude-standard",
+        "--directory",
+        "-z",
+      ],
+    },
+    &[],
+  ) {
+    Ok(out) => out,
+    Err(_) => return (Vec::new(), AHashSet::new()),
+  };
+
+  let (dirs, files): (Vec<String>, Vec<String>) = String::from_utf8_lossy(&out)
+    .split('\0')
+    .filter(|p| !p.is_empty())
+    .map(|p| p.to_string())
+    .partition(|p| p.ends_with('/'));
+
+  (dirs, files.into_iter().take(MAX_IGNORED_PATHS).collect())
+}
+
+fn find_ignored(repo_path: &str, paths: &AHashSet<String>) -> AHashSet<String> {
+  let input: Vec<u8> = paths
+    .iter()
+    .flat_map(|p| p.as_bytes().iter().chain(&[0]))
+    .copied()
+    .collect();
+
+  // Exits with 1 when nothing is ignored, so any error is treated as nothing ignored.
+  match run_git_with_input(
+    RunGitOptions {
+      repo_path,
+      args: ["check-ignore", "--stdin", "-z"],
+    },
+    &input,
+  ) {
+    Ok(out) => String::from_utf8_lossy(&out)
+      .split('\0')
+      .filter(|p| !p.is_empty())
+      .map(|p| p.to_string())
+      .collect(),
+    Err(_) => AHashSet::new(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+  use std::sync::Mutex;
+
+  use notify::event::{CreateKind, EventKind};
+  use notify::{Event, RecursiveMode};
+
+  use crate::git::repo_watcher::{
+    classify_path, find_ignored_at_start, handle_event, watched_dirs, Change,
+    PendingChanges, WatchedPaths,
+  };
+  use crate::util::test_util::{write_file, TempDir};
+
+  fn make_paths(git_path: &str, common_dir: &str) -> WatchedPaths {
+    WatchedPaths {
+      path: PathBuf::from("/repo"),
+      git_path: PathBuf::from(git_path),
+      common_dir: PathBuf::from(common_dir),
+      ignored_dirs: Vec::new(),
+    }
+  }
+
+  #[test]
+  fn test_classify_path() {
+    let paths = make_paths("/repo/.git", "/repo/.git");
+    let classify = |p: &str| classify_path(&PathBuf::from(p), &paths);
+
+    assert_eq!(classify("/repo/.git/HEAD"), Some(Change::Head));
+    assert_eq!(classify("/repo/.git/index"), Some(Change::Index));
+    assert_eq!(classify("/repo/.git/index.lock"), None);
+    assert_eq!(classify("/repo/.git/refs/heads/main"), Some(Change::Refs));
+    assert_eq!(classify("/repo/.git/packed-refs"), Some(Change::Refs));
+    assert_eq!(classify("/repo/.git/logs/refs/stash"), Some(Change::Refs));
+    assert_eq!(classify("/repo/.git/config"), Some(Change::Config));
+    assert_eq!(classify("/repo/.git/objects/ab/cdef"), None);
+    assert_eq!(
+      classify("/repo/src/main.rs"),
+      Some(Change::Worktree(String::from("src/main.rs")))
+    );
+    assert_eq!(classify("/repo/sub/.git/HEAD"), None);
+    assert_eq!(classify("/elsewhere/file"), None);
+  }
+
+  #[test]
+  fn test_linked_worktree() {
+    let paths = make_paths("/main/.git/worktrees/wt", "/main/.git");
+    let classify = |p: &str| classify_path(&PathBuf::from(p), &paths);
+
+    assert_eq!(classify("/main/.git/worktrees/wt/HEAD"), Some(Change::Head));
+    assert_eq!(classify("/main/.git/refs/tags/v1"), Some(Change::Refs));
+
+    assert_eq!(
+      watched_dirs(&paths),
+      vec![
+        (PathBuf::from("/repo"), RecursiveMode::Recursive),
+        (PathBuf::from("/main/.git"), RecursiveMode::Recursive)
+      ]
+    );
+  }
+
+  #[test]
+  fn test_skips_ignored_dirs() {
+    let dir = TempDir::new("watch_ignored");
+    for file in [
+      "target/debug/a",
+      "sub/build/b",
+      "sub/src/c",
+      "other/d",
+      ".git/HEAD",
+    ] {
+      write_file(&dir.path.join(file), "");
+    }
+
+    let paths = WatchedPaths {
+      path: dir.path.clone(),
+      git_path: dir.path.join(".git"),
+      common_dir: dir.path.join(".git"),
+      ignored_dirs: vec![String::from("target/"), String::from("sub/build/")],
+    };
+
+    let mut dirs = watched_dirs(&paths);
+    dirs.sort_by(|a, b| a.0.cmp(&b.0));
+
+    assert_eq!(
+      dirs,
+      vec![
+        (dir.path.clone(), RecursiveMode::NonRecursive),
+        (dir.path.join(".git"), RecursiveMode::Recursive),
+        (dir.path.join("other"), RecursiveMode::Recursive),
+        (dir.path.join("sub"), RecursiveMode::NonRecursive),
+        (dir.path.join("sub").join("src"), RecursiveMode::Recursive),
+      ]
+    );
+
+    let classify = |p: PathBuf| classify_path(&p, &paths);
+
+    assert_eq!(classify(dir.path.join("target")), None);
+    assert_eq!(classify(dir.path.join("sub").join("build").join("b")), None);
+    assert_eq!(
+      classify(dir.path.join("sub").join("src").join("c")),
+      Some(Change::Worktree(String::from("sub/src/c")))
+    );
+  }
+
+  #[test]
+  fn test_ignored_paths_dont_reset_debounce() {
+    let paths = make_paths("/repo/.git", "/repo/.git");
+    let pending = Mutex::new(PendingChanges::default());
+    pending
+      .lock()
+      .unwrap()
+      .ignored
+      .insert(String::from("app.log"));
+
+    let event = |path: &str| {
+      Ok(Event::new(EventKind::Create(CreateKind::File)).add_path(PathBuf::from(path)))
+    };
+
+    handle_event(event("/repo/app.log"), &paths, &pending);
+    assert!(pending.lock().unwrap().unchecked.is_empty());
+    assert_eq!(pending.lock().unwrap().last_event(), None);
+
+    handle_event(event("/repo/src/main.rs"), &paths, &pending);
+    assert!(pending
+      .lock()
+      .unwrap()
+      .unchecked
+      .contains_key("src/main.rs"));
+
+    handle_event(event("/repo/.gitignore"), &paths, &pending);
+    assert!(pending.lock().unwrap().ignored.is_empty());
+  }
+
+  #[test]
+  fn test_find_ignored_at_start() {
+    let repo = TempDir::with_repo("watch_ignored_start");
+    write_file(&repo.path.join(".gitignore"), "target/\n*.log\n");
+    write_file(&repo.path.join("target").join("debug").join("a"), "");
+    write_file(&repo.path.join("app.log"), "");
+    write_file(&repo.path.join("main.rs"), "");
+
+    let (dirs, files) = find_ignored_at_start(&repo.repo_path());
+
+    assert_eq!(dirs, vec![String::from("target/")]);
+    assert_eq!(
+      files.into_iter().collect::<Vec<_>>(),
+      vec![String::from("app.log")]
+    );
+  }
+}
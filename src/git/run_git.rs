@@ -1,11 +1,12 @@
 use crate::dprintln;
 use chardetng::EncodingDetector;
 use std::ffi::OsStr;
+use std::io::Write;
 use std::path::Path;
-use std::process::{Command, Output};
+use std::process::{Command, Output, Stdio};
 
 use crate::git::git_settings::GIT_PATH;
-use crate::server::request_util::R;
+use crate::server::request_util::{ES, R};
 
 #[derive(Clone, Debug)]
 pub struct RunGitOptions<'a, I, S>
@@ -73,6 +74,33 @@ where
   None
 }
 
+// For filters like "lfs smudge" that read from stdin. Fails if git exits with an error.
+pub fn run_git_with_input<I, S>(options: RunGitOptions<I, S>, input: &[u8]) -> R<Vec<u8>>
+where
+  I: IntoIterator<Item = S>,
+  S: AsRef<OsStr>,
+{
+  let mut child = Command::new(Path::new(GIT_PATH.as_path()))
+    .args(options.args)
+    .current_dir(options.repo_path)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()?;
+
+  if let Some(mut stdin) = child.stdin.take() {
+    stdin.write_all(input)?;
+  }
+
+  let out = child.wait_with_output()?;
+
+  if !out.status.success() {
+    return Err(ES::Text(read_buffer_to_string(&out.stderr)));
+  }
+
+  Ok(out.stdout)
+}
+
 #[cfg(test)]
 mod tests {
   use std::path::Path;
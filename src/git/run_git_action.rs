@@ -54,6 +54,27 @@ pub fn run_git_action<const N: usize>(options: RunGitActionOptions<N>) -> u32 {
 }
 
 pub fn run_git_action_with_vec(repo_path: &str, commands: Vec<Vec<String>>) -> u32 {
+  run_git_action_with_env(repo_path, commands, Vec::new())
+}
+
+// Env vars are applied to every command. Used when git would otherwise try to open an
+// editor, e.g. GIT_SEQUENCE_EDITOR for interactive rebase.
+pub fn run_git_action_with_env(
+  repo_path: &str,
+  commands: Vec<Vec<String>>,
+  env: Vec<(String, String)>,
+) -> u32 {
+  run_git_action_then(repo_path, commands, env, || {})
+}
+
+// on_finish runs after the last command, or the one that failed, but before the action
+// is marked done. For removing files that git still needs while the commands run.
+pub fn run_git_action_then(
+  repo_path: &str,
+  commands: Vec<Vec<String>>,
+  env: Vec<(String, String)>,
+  on_finish: impl FnOnce() + Send + 'static,
+) -> u32 {
   let id = start_action();
 
   let git_version = STORE.get_git_version();
@@ -61,25 +82,91 @@ pub fn run_git_action_with_vec(repo_path: &str, commands: Vec<Vec<String>>) -> u
   let repo_path = repo_path.to_string();
 
   thread::spawn(move || {
-    let mut failed = false;
+    let mut error = None;
 
     for c in commands {
-      if let Err(e) = run_git_action_inner(id, repo_path.clone(), git_version.clone(), c)
+      if let Err(e) =
+        run_git_action_inner(id, repo_path.clone(), git_version.clone(), c, &env)
       {
-        set_action_error(id, e);
-        failed = true;
+        error = Some(e);
         break;
       }
     }
 
-    if !failed {
-      set_action_done(id);
+    on_finish();
+
+    match error {
+      Some(e) => set_action_error(id, e),
+      None => set_action_done(id),
+    }
+  });
+
+  id
+}
+
+pub struct GitStep {
+  pub command: Vec<String>,
+  // Reverses this step. Only run when a later step fails.
+  pub undo: Vec<Vec<String>>,
+}
+
+// Like run_git_action_then, but if a step fails the earlier steps are undone in reverse
+// order before on_finish. The action still reports the original error.
+pub fn run_git_action_with_undo(
+  repo_path: &str,
+  steps: Vec<GitStep>,
+  on_finish: impl FnOnce() + Send + 'static,
+) -> u32 {
+  let id = start_action();
+
+  let git_version = STORE.get_git_version();
+
+  let repo_path = repo_path.to_string();
+
+  thread::spawn(move || {
+    for (i, step) in steps.iter().enumerate() {
+      if let Err(e) = run_git_action_inner(
+        id,
+        repo_path.clone(),
+        git_version.clone(),
+        step.command.clone(),
+        &[],
+      ) {
+        for done in steps[..i].iter().rev() {
+          for c in done.undo.iter() {
+            let _ = run_git_action_inner(
+              id,
+              repo_path.clone(),
+              git_version.clone(),
+              c.clone(),
+              &[],
+            );
+          }
+        }
+
+        on_finish();
+        set_action_error(id, e);
+        return;
+      }
     }
+
+    on_finish();
+    set_action_done(id);
   });
 
   id
 }
 
+// For actions that fail before running git. The message shows up in the action's stderr.
+pub fn failed_action(message: &str) -> u32 {
+  let id = start_action();
+
+  add_stderr_log(id, message);
+  set_action_error(id, IO(message.to_string()));
+
+  id
+}
+
 #[derive(Debug, Deserialize, TS)]
 #[serde(rename_all = "camelCase")]
 #[ts(export)]
@@ -114,9 +201,11 @@ pub fn run_git_action_inner(
   repo_path: String,
   git_version: GitVersion,
   args: Vec<String>,
+  env: &[(String, String)],
 ) -> Result<(), ActionError> {
   let mut cmd = Command::new(GIT_PATH.as_path())
     .args(args_with_config(args, git_version))
+    .envs(env.iter().map(|(k, v)| (k, v)))
     .current_dir(repo_path)
     .stderr(Stdio::piped())
     .stdout(Stdio::piped())
@@ -1,21 +1,32 @@
 use crate::git::git_types::{Commit, Patch, RefInfo};
 use crate::git::git_version::GitVersion;
+use crate::git::queries::commit_signatures::CommitSignature;
 use crate::git::queries::config::GitConfig;
-use crate::git::queries::patches::cache::clear_patch_cache;
+use crate::git::queries::patches::cache::{clear_patch_cache, load_patch_index_stats};
 use crate::git::queries::search::search_request::clear_completed_searches;
+use crate::git::repo_state::mark_commits_loaded;
+use crate::index::auto_complete::{
+  clear_auto_complete_indexes, load_auto_complete_stats,
+};
 use crate::server::git_request::ReqOptions;
 use crate::server::request_util::{ES, R};
 use crate::util::global::{Glo, Global};
+use crate::util::repo_cache::{str_size, CacheSize, CacheStats, RepoCache};
 use crate::{dprintln, glo, global, time_block};
 use ahash::AHashMap;
 use std::collections::HashMap;
 use std::env;
+use std::mem::size_of;
 use std::path::PathBuf;
 
 #[derive(Debug, Clone)]
 pub struct RepoPath {
   pub path: PathBuf,
+  // Per-worktree files: HEAD, index, rebase and merge state.
   pub git_path: PathBuf,
+  // Shared between linked worktrees: refs, packed-refs, config, objects. The same as
+  // git_path for the main worktree.
+  pub common_dir: PathBuf,
 }
 
 pub type PathString = String;
@@ -24,14 +35,21 @@ type CommitsAndRefs = (Vec<Commit>, Vec<RefInfo>);
 
 static REPO_PATHS: Glo<Vec<RepoPath>> = glo!(Vec::new());
 
-static COMMITS_AND_REFS: Glo<AHashMap<PathString, CommitsAndRefs>> =
-  glo!(AHashMap::new());
-static PATCHES: Glo<(PathString, HashMap<PatchPath, Vec<Patch>>)> =
-  glo!((PathString::new(), HashMap::new()));
+const MB: usize = 1024 * 1024;
+
+static COMMITS_AND_REFS: Global<RepoCache<CommitsAndRefs>> =
+  global!(RepoCache::new("commits", 512 * MB));
+static PATCHES: Global<RepoCache<HashMap<PatchPath, Vec<Patch>>>> =
+  global!(RepoCache::new("patches", 256 * MB));
 // Key is 2 commit ids joined.
-pub static REF_DIFFS: Glo<AHashMap<String, u32>> = glo!(AHashMap::new());
-// This probably needs to be per repo. We could then watch for changes?
-pub static CONFIG: Global<AHashMap<PathString, GitConfig>> = global!(AHashMap::new());
+pub static REF_DIFFS: Global<RepoCache<AHashMap<String, u32>>> =
+  global!(RepoCache::new("ref diffs", 16 * MB));
+pub static CONFIG: Global<RepoCache<GitConfig>> =
+  global!(RepoCache::new("config", 8 * MB));
+// Commits are immutable, so a signature only needs checking once. Except CannotCheck,
+// which changes if the user sets up their keys, so those aren't kept.
+pub static SIGNATURES: Global<RepoCache<AHashMap<String, CommitSignature>>> =
+  global!(RepoCache::new("signatures", 16 * MB));
 pub static GIT_VERSION: Glo<GitVersion> = glo!(GitVersion::new());
 
 pub const STORE: Store = Store {};
@@ -51,66 +69,25 @@ impl Store {
     commits: &Vec<Commit>,
     refs: &Vec<RefInfo>,
   ) {
-    if let Ok(mut cr) = COMMITS_AND_REFS.write() {
-      (*cr).insert(repo_path.to_string(), (commits.to_owned(), refs.to_owned()));
-    }
+    COMMITS_AND_REFS.insert_repo(repo_path, (commits.to_owned(), refs.to_owned()));
+    mark_commits_loaded(repo_path);
   }
 
+  // May be out of date, loaders check commits_changed.
   pub fn get_commits_and_refs(&self, repo_path: &PathString) -> Option<CommitsAndRefs> {
-    if let Ok(cr) = COMMITS_AND_REFS.read() {
-      return Some((*cr).get(repo_path)?.to_owned());
-    }
-
-    None
-  }
-
-  fn get_all_commits_and_refs(&self) -> Option<AHashMap<PathString, CommitsAndRefs>> {
-    let cr = COMMITS_AND_REFS.read().ok()?;
-
-    Some((*cr).to_owned())
-  }
-
-  pub fn clear_unwatched_repos_from_commits(
-    &self,
-    watched_repos: &HashMap<String, bool>,
-  ) -> Option<()> {
-    let commits = self
-      .get_all_commits_and_refs()?
-      .into_iter()
-      .filter(|(repo_path, _)| watched_repos.contains_key(repo_path))
-      .collect();
-
-    if let Ok(mut cr) = COMMITS_AND_REFS.write() {
-      *cr = commits
-    }
-
-    let configs = CONFIG
-      .get()?
-      .into_iter()
-      .filter(|(repo_path, _)| watched_repos.contains_key(repo_path))
-      .collect();
-
-    CONFIG.set(configs);
-
-    Some(())
+    COMMITS_AND_REFS.get_repo(repo_path)
   }
 
   pub fn insert_patches(&self, repo_path: &str, patches: &HashMap<String, Vec<Patch>>) {
     time_block!("insert_patches", {
-      if let Ok(mut saved_patches) = PATCHES.write() {
-        *saved_patches = (repo_path.to_owned(), patches.to_owned());
-      }
+      PATCHES.insert_repo(repo_path, patches.to_owned());
     });
   }
 
   pub fn get_patches(&self, repo_path: &str) -> Option<HashMap<String, Vec<Patch>>> {
-    if let Ok(stored) = PATCHES.read() {
-      if stored.0 == repo_path && !stored.1.is_empty() {
-        return Some(stored.1.clone());
-      }
-    }
-
-    None
+    PATCHES
+      .get_repo(repo_path)
+      .filter(|patches| !patches.is_empty())
   }
 
   pub fn set_repo_paths(&self, repo_paths: Vec<RepoPath>) {
@@ -138,9 +115,59 @@ pub fn clear_cache(_: &ReqOptions) {
   dprintln!("Cleared cache.");
 }
 
+// Usually check_repo_state does this for us when it sees the repo files change.
+pub fn invalidate_repo(repo_path: &str) {
+  invalidate_commits(repo_path);
+  invalidate_config(repo_path);
+  PATCHES.invalidate(repo_path);
+
+  dprintln!("Invalidated caches for {}", repo_path);
+}
+
+// The commits and everything worked out from them.
+pub fn invalidate_commits(repo_path: &str) {
+  COMMITS_AND_REFS.invalidate(repo_path);
+  invalidate_ref_calcs(repo_path);
+}
+
+// What's worked out from the commits and refs, but not the commits themselves.
+pub fn invalidate_ref_calcs(repo_path: &str) {
+  REF_DIFFS.invalidate(repo_path);
+  clear_auto_complete_indexes(Some(repo_path));
+}
+
+pub fn invalidate_config(repo_path: &str) {
+  CONFIG.invalidate(repo_path);
+}
+
+pub fn invalidate_repo_caches(options: &ReqOptions) {
+  invalidate_repo(&options.repo_path);
+}
+
+pub fn load_cache_stats(_: &ReqOptions) -> Vec<CacheStats> {
+  [
+    COMMITS_AND_REFS.stats(),
+    PATCHES.stats(),
+    REF_DIFFS.stats(),
+    CONFIG.stats(),
+    SIGNATURES.stats(),
+    load_patch_index_stats(),
+  ]
+  .into_iter()
+  .flatten()
+  .chain(load_auto_complete_stats())
+  .collect()
+}
+
 pub fn clear_all_caches(_: &ReqOptions) {
   clear_completed_searches();
   clear_patch_cache();
+  COMMITS_AND_REFS.clear_repos();
+  PATCHES.clear_repos();
+  REF_DIFFS.clear_repos();
+  CONFIG.clear_repos();
+  SIGNATURES.clear_repos();
+  clear_auto_complete_indexes(None);
 
   dprintln!("Cleared all caches.");
 }
@@ -150,3 +177,80 @@ pub fn override_git_home(options: &ReqOptions) {
 
   env::set_var("HOME", &options.repo_path);
 }
+
+impl CacheSize for CommitsAndRefs {
+  fn cache_size(&self) -> usize {
+    let (commits, refs) = self;
+
+    let commits_size: usize = commits
+      .iter()
+      .map(|c| {
+        size_of::<Commit>()
+          + c.author.len()
+          + c.email.len()
+          + c.id.len()
+          + c.message.len()
+          + c.stash_id.len()
+          + c.parent_ids.iter().map(|id| str_size(id)).sum::<usize>()
+          + c.refs.iter().map(|r| str_size(r)).sum::<usize>()
+      })
+      .sum();
+
+    let refs_size: usize = refs
+      .iter()
+      .map(|r| {
+        size_of::<RefInfo>()
+          + r.id.len()
+          + r.full_name.len()
+          + r.short_name.len()
+          + r.sibling_id.len()
+          + r.commit_id.len()
+      })
+      .sum();
+
+    commits_size + refs_size
+  }
+}
+
+impl CacheSize for HashMap<PatchPath, Vec<Patch>> {
+  fn cache_size(&self) -> usize {
+    self
+      .iter()
+      .map(|(id, patches)| {
+        str_size(id)
+          + patches
+            .iter()
+            .map(|p| {
+              size_of::<Patch>()
+                + p.commit_id.len()
+                + p.old_file.len()
+                + p.new_file.len()
+                + p.id.len()
+            })
+            .sum::<usize>()
+      })
+      .sum()
+  }
+}
+
+impl CacheSize for AHashMap<String, CommitSignature> {
+  fn cache_size(&self) -> usize {
+    self
+      .iter()
+      .map(|(id, s)| {
+        str_size(id)
+          + size_of::<CommitSignature>()
+          + s.commit_id.len()
+          + s.signer.as_ref().map_or(0, |signer| signer.len())
+          + s.key.as_ref().map_or(0, |key| key.len())
+      })
+      .sum()
+  }
+}
+
+impl CacheSize for AHashMap<String, u32> {
+  // Keys are always 2 commit ids, no need to look at each one.
+  fn cache_size(&self) -> usize {
+    self.len() * (str_size("") + 80 + size_of::<u32>())
+  }
+}
@@ -1,5 +1,7 @@
 use crate::index::ac_node::ACNode;
+use crate::util::repo_cache::CacheSize;
 use ahash::{HashMap, HashMapExt};
+use std::mem::size_of;
 
 #[derive(Debug, Clone)]
 pub struct ACIndex {
@@ -54,6 +56,12 @@ impl ACIndex {
   }
 }
 
+impl CacheSize for ACIndex {
+  fn cache_size(&self) -> usize {
+    self.nodes.values().map(|n| n.num_nodes()).sum::<usize>() * size_of::<ACNode>()
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use crate::index::ac_index::ACIndex;
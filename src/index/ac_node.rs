@@ -49,6 +49,10 @@ impl ACNode {
 
     matches
   }
+
+  pub fn num_nodes(&self) -> usize {
+    1 + self.nodes.values().map(|n| n.num_nodes()).sum::<usize>()
+  }
 }
 
 #[cfg(test)]
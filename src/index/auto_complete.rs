@@ -1,6 +1,9 @@
-use crate::index::commit_message_ac::commit_message_ac;
+use crate::index::commit_message_ac::{
+  clear_commit_message_index, commit_message_ac, commit_message_index_stats,
+};
 use crate::index::create_branch_ac::create_branch_ac;
 use crate::server::request_util::R;
+use crate::util::repo_cache::CacheStats;
 use serde::Deserialize;
 use ts_rs::TS;
 
@@ -34,3 +37,11 @@ pub fn auto_complete(options: &MessageAC) -> R<Vec<String>> {
     ACType::CreateBranch => create_branch_ac(repo_path, current_word, *max_num),
   }
 }
+
+pub fn clear_auto_complete_indexes(repo_path: Option<&str>) {
+  clear_commit_message_index(repo_path);
+}
+
+pub fn load_auto_complete_stats() -> Option<CacheStats> {
+  commit_message_index_stats()
+}
@@ -5,6 +5,9 @@ use crate::global;
 use crate::index::ac_index::ACIndex;
 use crate::server::request_util::{ES, R};
 use crate::util::global::Global;
+use crate::util::repo_cache::{CacheStats, RepoCache};
+
+const MB: usize = 1024 * 1024;
 
 pub fn commit_message_ac(
   repo_path: &String,
@@ -15,45 +18,35 @@ pub fn commit_message_ac(
     return Ok(Vec::new());
   }
 
-  let index = get_index(repo_path)?;
+  let words = match INDEX.with_repo(repo_path, |index| index.find_matching(current_word))
+  {
+    Some(words) => words,
+    None => {
+      let index = build_index(repo_path)?;
+      let words = index.find_matching(current_word);
+
+      INDEX.insert_repo(repo_path, index);
 
-  let words = index.find_matching(current_word);
+      words
+    }
+  };
 
   Ok(words.into_iter().take(max_num).collect())
 }
 
-#[derive(Clone)]
-struct CommitMessageAC {
-  pub repo_path: String,
-  pub index: ACIndex,
-}
+static INDEX: Global<RepoCache<ACIndex>> =
+  global!(RepoCache::new("autocomplete", 64 * MB));
 
-impl CommitMessageAC {
-  fn new() -> Self {
-    Self {
-      repo_path: String::from(""),
-      index: ACIndex::new(),
-    }
+// None clears every repo.
+pub fn clear_commit_message_index(repo_path: Option<&str>) {
+  match repo_path {
+    Some(repo_path) => INDEX.invalidate(repo_path),
+    None => INDEX.clear_repos(),
   }
 }
 
-static INDEX: Global<CommitMessageAC> = global!(CommitMessageAC::new());
-
-fn get_index(repo_path: &String) -> R<ACIndex> {
-  if let Some(index) = INDEX.get() {
-    if &index.repo_path == repo_path {
-      return Ok(index.index);
-    }
-  }
-
-  let index = build_index(repo_path)?;
-
-  INDEX.set(CommitMessageAC {
-    repo_path: repo_path.clone(),
-    index: index.clone(),
-  });
-
-  Ok(index)
+pub fn commit_message_index_stats() -> Option<CacheStats> {
+  INDEX.stats()
 }
 
 fn build_index(repo_path: &String) -> R<ACIndex> {
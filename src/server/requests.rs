@@ -3,27 +3,67 @@ use std::process::exit;
 use tiny_http::{Response, Server};
 
 use crate::git::actions::add::git_add_files;
+use crate::git::actions::branch::{
+  create_branch, delete_branch, is_branch_merged, rename_branch, set_upstream,
+};
+use crate::git::actions::cherry_pick::{
+  cherry_pick_abort, cherry_pick_commits, cherry_pick_continue, revert_abort,
+  revert_commits, revert_continue,
+};
 use crate::git::actions::clone::clone_repo;
 use crate::git::actions::command::command;
 use crate::git::actions::create_repo::create_repo;
 use crate::git::actions::credentials::set_credentials;
-use crate::git::actions::fetch::fetch_all;
-use crate::git::actions::stash::{stash_changes, stash_staged};
-use crate::git::conflicts::api::load_conflicted_file;
+use crate::git::actions::fetch::{fetch_all, fetch_remote, load_fetch_changes};
+use crate::git::actions::merge::{merge_abort, merge_branch};
+use crate::git::actions::rebase::{
+  interactive_rebase, rebase_abort, rebase_continue, rebase_skip,
+};
+use crate::git::actions::remote::{
+  add_remote, check_remote_url, prune_remote, remove_remote, rename_remote,
+  set_remote_url,
+};
+use crate::git::actions::stash::{
+  apply_stash, clear_stashes, drop_stash, pop_stash, stash_changes, stash_push,
+  stash_staged,
+};
+use crate::git::actions::submodule::{
+  deinit_submodules, init_submodules, sync_submodules, update_submodules,
+};
+use crate::git::actions::tag::{create_tag, delete_tag, push_tags};
+use crate::git::actions::worktree::{
+  add_worktree, lock_worktree, prune_worktrees, remove_worktree, unlock_worktree,
+};
+use crate::git::conflicts::api::{load_conflicted_file, load_conflicted_files};
 use crate::git::git_version::git_version;
+use crate::git::queries::blame::{load_blame, poll_blame, start_blame};
+use crate::git::queries::commit_graph::load_commits_refs_and_graph;
+use crate::git::queries::commit_signatures::load_commit_signatures;
 use crate::git::queries::commits::{
   commit_ids_between_commits, commit_is_ancestor, commit_is_on_branch,
   get_all_commits_on_current_branch, load_commits_and_refs,
 };
+use crate::git::queries::commits_incremental::load_commits_and_refs_incremental;
+use crate::git::queries::commits_paging::load_more_commits;
+use crate::git::queries::file_history::load_file_history;
 use crate::git::queries::hunks::html_code::get_patch_as_html;
 use crate::git::queries::hunks::images::load_commit_image;
 use crate::git::queries::hunks::load_hunks::{load_hunks, load_hunks_split};
+use crate::git::queries::lfs::{load_lfs_files, load_lfs_locks};
+use crate::git::queries::line_history::load_line_history;
+use crate::git::queries::merge_preview::preview_merge;
 use crate::git::queries::patches::patches_for_commit::load_patches_for_commit;
+use crate::git::queries::rebase_state::load_rebase_state;
 use crate::git::queries::refs::ref_diffs::calc_ref_diffs;
+use crate::git::queries::refs::ref_name::check_branch_name;
 use crate::git::queries::run::run;
 use crate::git::queries::scan_workspace::scan_workspace;
 use crate::git::queries::search::search_commits::search_commits;
 use crate::git::queries::search::search_request::{poll_diff_search, start_diff_search};
+use crate::git::queries::sequencer_state::load_sequencer_state;
+use crate::git::queries::stashes::{load_stash_list, load_stash_patches};
+use crate::git::queries::submodules::load_submodule_status;
+use crate::git::queries::tags::{load_tags, verify_tag};
 use crate::git::queries::unpushed_commits::get_un_pushed_commits;
 use crate::git::queries::wip::is_rebase_in_progress;
 use crate::git::queries::wip::wip_diff::{
@@ -31,8 +71,13 @@ use crate::git::queries::wip::wip_diff::{
 };
 use crate::git::queries::wip::wip_patches::load_wip_patches;
 use crate::git::queries::workspace::repo_status::load_repo_status;
+use crate::git::queries::worktrees::list_worktrees;
+use crate::git::repo_watcher::{poll_repo_changes, stop_watching_repo};
 use crate::git::run_git_action::poll_action2;
-use crate::git::store::{clear_all_caches, clear_cache, override_git_home};
+use crate::git::store::{
+  clear_all_caches, clear_cache, invalidate_repo_caches, load_cache_stats,
+  override_git_home,
+};
 use crate::index::auto_complete::auto_complete;
 use crate::server::static_files::{
   file_size, handle_resource_request, path_exists, temp_dir, write_file,
@@ -82,6 +127,32 @@ pub fn start_async_server() {
           load_repo_status,
 
           is_rebase_in_progress,
+          load_rebase_state,
+          load_sequencer_state,
+          preview_merge,
+          is_branch_merged,
+          check_branch_name,
+          load_tags,
+          verify_tag,
+          load_stash_list,
+          load_stash_patches,
+          load_conflicted_files,
+          check_remote_url,
+          load_fetch_changes,
+          load_submodule_status,
+          list_worktrees,
+          load_lfs_locks,
+          load_lfs_files,
+          load_commit_signatures,
+          load_blame,
+          start_blame,
+          poll_blame,
+          load_file_history,
+          load_line_history,
+          load_commits_refs_and_graph,
+          load_commits_and_refs_incremental,
+          load_more_commits,
+          poll_repo_changes,
           load_commits_and_refs,
 
           load_hunks,
@@ -117,6 +188,9 @@ pub fn start_async_server() {
           // Core messages
           clear_cache,
           clear_all_caches,
+          invalidate_repo_caches,
+          load_cache_stats,
+          stop_watching_repo,
           set_credentials,
           poll_action2,
           override_git_home,
@@ -130,7 +204,46 @@ pub fn start_async_server() {
           fetch_all,
           clone_repo,
           create_repo,
-          stash_staged
+          stash_staged,
+          interactive_rebase,
+          rebase_continue,
+          rebase_skip,
+          rebase_abort,
+          cherry_pick_commits,
+          cherry_pick_continue,
+          cherry_pick_abort,
+          revert_commits,
+          revert_continue,
+          revert_abort,
+          merge_branch,
+          merge_abort,
+          create_branch,
+          rename_branch,
+          delete_branch,
+          set_upstream,
+          create_tag,
+          delete_tag,
+          push_tags,
+          stash_push,
+          apply_stash,
+          pop_stash,
+          drop_stash,
+          clear_stashes,
+          add_remote,
+          remove_remote,
+          rename_remote,
+          set_remote_url,
+          prune_remote,
+          fetch_remote,
+          init_submodules,
+          update_submodules,
+          sync_submodules,
+          deinit_submodules,
+          add_worktree,
+          remove_worktree,
+          prune_worktrees,
+          lock_worktree,
+          unlock_worktree
         }
       }
       _ => {
@@ -0,0 +1,186 @@
+/*
+Minimal binary encoding for our on-disk caches. Everything is little endian, strings
+are length prefixed. Readers return None on truncated or invalid data, so a bad file
+is treated like a missing one.
+ */
+
+#[derive(Default)]
+pub struct BinWriter {
+  pub buf: Vec<u8>,
+}
+
+impl BinWriter {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn u8(&mut self, v: u8) {
+    self.buf.push(v);
+  }
+
+  pub fn u32(&mut self, v: u32) {
+    self.buf.extend_from_slice(&v.to_le_bytes());
+  }
+
+  pub fn u64(&mut self, v: u64) {
+    self.buf.extend_from_slice(&v.to_le_bytes());
+  }
+
+  pub fn i32(&mut self, v: i32) {
+    self.buf.extend_from_slice(&v.to_le_bytes());
+  }
+
+  pub fn bool(&mut self, v: bool) {
+    self.u8(v as u8);
+  }
+
+  pub fn str(&mut self, s: &str) {
+    self.u32(s.len() as u32);
+    self.buf.extend_from_slice(s.as_bytes());
+  }
+
+  // Commit ids are hex, so store them as half as many bytes.
+  pub fn hex_id(&mut self, id: &str) {
+    match hex_to_bytes(id) {
+      Some(bytes) => {
+        self.u8(bytes.len() as u8);
+        self.buf.extend_from_slice(&bytes);
+      }
+      None => {
+        // Not valid hex, e.g. an abbreviated odd length id.
+        self.u8(0);
+        self.str(id);
+      }
+    }
+  }
+}
+
+pub struct BinReader<'a> {
+  data: &'a [u8],
+  pos: usize,
+}
+
+impl<'a> BinReader<'a> {
+  pub fn new(data: &'a [u8]) -> Self {
+    Self { data, pos: 0 }
+  }
+
+  pub fn at_end(&self) -> bool {
+    self.pos >= self.data.len()
+  }
+
+  pub fn pos(&self) -> usize {
+    self.pos
+  }
+
+  pub fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+    let end = self.pos.checked_add(len)?;
+    let slice = self.data.get(self.pos..end)?;
+    self.pos = end;
+
+    Some(slice)
+  }
+
+  pub fn u8(&mut self) -> Option<u8> {
+    Some(self.bytes(1)?[0])
+  }
+
+  pub fn u32(&mut self) -> Option<u32> {
+    Some(u32::from_le_bytes(self.bytes(4)?.try_into().ok()?))
+  }
+
+  pub fn u64(&mut self) -> Option<u64> {
+    Some(u64::from_le_bytes(self.bytes(8)?.try_into().ok()?))
+  }
+
+  pub fn i32(&mut self) -> Option<i32> {
+    Some(i32::from_le_bytes(self.bytes(4)?.try_into().ok()?))
+  }
+
+  pub fn bool(&mut self) -> Option<bool> {
+    Some(self.u8()? != 0)
+  }
+
+  pub fn str(&mut self) -> Option<String> {
+    let len = self.u32()? as usize;
+
+    String::from_utf8(self.bytes(len)?.to_vec()).ok()
+  }
+
+  pub fn hex_id(&mut self) -> Option<String> {
+    let len = self.u8()? as usize;
+
+    if len == 0 {
+      return self.str();
+    }
+
+    Some(bytes_to_hex(self.bytes(len)?))
+  }
+}
+
+// FNV-1a. Only for spotting truncated or corrupted files.
+pub fn checksum(data: &[u8]) -> u64 {
+  let mut hash: u64 = 0xcbf29ce484222325;
+
+  for b in data {
+    hash ^= *b as u64;
+    hash = hash.wrapping_mul(0x100000001b3);
+  }
+
+  hash
+}
+
+fn hex_to_bytes(hex: &str) -> Option<Vec<u8>> {
+  if hex.is_empty() || !hex.len().is_multiple_of(2) || hex.len() > 255 * 2 {
+    return None;
+  }
+
+  (0..hex.len())
+    .step_by(2)
+    .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+    .collect()
+}
+
+fn bytes_to_hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::util::bin_io::{checksum, BinReader, BinWriter};
+
+  #[test]
+  fn test_round_trip() {
+    let mut w = BinWriter::new();
+    w.u32(7);
+    w.str("héllo");
+    w.hex_id("53738d67146d480b155faf1ed8900c341966009e");
+    w.hex_id("abc");
+    w.i32(-130);
+    w.bool(true);
+
+    let mut r = BinReader::new(&w.buf);
+    assert_eq!(r.u32(), Some(7));
+    assert_eq!(r.str(), Some(String::from("héllo")));
+    assert_eq!(
+      r.hex_id(),
+      Some(String::from("53738d67146d480b155faf1ed8900c341966009e"))
+    );
+    assert_eq!(r.hex_id(), Some(String::from("abc")));
+    assert_eq!(r.i32(), Some(-130));
+    assert_eq!(r.bool(), Some(true));
+    assert!(r.at_end());
+    assert_eq!(r.u8(), None);
+  }
+
+  #[test]
+  fn test_truncated() {
+    let mut w = BinWriter::new();
+    w.str("some text");
+
+    let mut r = BinReader::new(&w.buf[..6]);
+    assert_eq!(r.str(), None);
+
+    assert_ne!(checksum(&w.buf), checksum(&w.buf[..6]));
+  }
+}
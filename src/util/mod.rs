@@ -1,7 +1,11 @@
+pub(crate) mod bin_io;
 pub(crate) mod data_store;
 pub(crate) mod debug_print;
 pub(crate) mod global;
+pub(crate) mod repo_cache;
 pub(crate) mod short_cache;
+#[cfg(test)]
+pub(crate) mod test_util;
 
 #[macro_export]
 macro_rules! f {
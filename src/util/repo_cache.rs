@@ -0,0 +1,289 @@
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use ahash::AHashMap;
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::dprintln;
+use crate::util::global::Global;
+
+/*
+Per-repo caches. Each keeps a value per repo, evicting the least recently used repos
+once the estimated memory use goes over max_bytes. The most recently inserted repo is
+always kept, even if it's over the limit on its own.
+ */
+
+// Rough memory use, only used to decide when to evict.
+pub trait CacheSize {
+  fn cache_size(&self) -> usize;
+}
+
+pub fn str_size(s: &str) -> usize {
+  size_of::<String>() + s.len()
+}
+
+struct CacheEntry<V> {
+  value: V,
+  size: usize,
+  last_used: AtomicU64,
+}
+
+pub struct RepoCache<V> {
+  name: &'static str,
+  max_bytes: usize,
+  entries: AHashMap<String, CacheEntry<V>>,
+  total_bytes: usize,
+  // Incremented on every access, for LRU order.
+  tick: AtomicU64,
+  hits: AtomicU64,
+  misses: AtomicU64,
+  evictions: u64,
+  invalidations: u64,
+}
+
+#[derive(Debug, Clone, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct CacheStats {
+  pub name: String,
+  pub num_repos: usize,
+  pub bytes: usize,
+  pub max_bytes: usize,
+  pub hits: u64,
+  pub misses: u64,
+  pub evictions: u64,
+  pub invalidations: u64,
+}
+
+impl<V: CacheSize> RepoCache<V> {
+  pub fn new(name: &'static str, max_bytes: usize) -> Self {
+    Self {
+      name,
+      max_bytes,
+      entries: AHashMap::new(),
+      total_bytes: 0,
+      tick: AtomicU64::new(0),
+      hits: AtomicU64::new(0),
+      misses: AtomicU64::new(0),
+      evictions: 0,
+      invalidations: 0,
+    }
+  }
+
+  // Only needs a read lock, so lookups from parallel iterators don't block each other.
+  pub fn get(&self, repo_path: &str) -> Option<&V> {
+    match self.entries.get(repo_path) {
+      Some(entry) => {
+        entry.last_used.store(self.next_tick(), Ordering::Relaxed);
+        self.hits.fetch_add(1, Ordering::Relaxed);
+
+        Some(&entry.value)
+      }
+      None => {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        None
+      }
+    }
+  }
+
+  pub fn insert(&mut self, repo_path: &str, value: V) {
+    let size = value.cache_size();
+
+    let entry = CacheEntry {
+      value,
+      size,
+      last_used: AtomicU64::new(self.next_tick()),
+    };
+
+    if let Some(old) = self.entries.insert(repo_path.to_string(), entry) {
+      self.total_bytes -= old.size;
+    }
+    self.total_bytes += size;
+
+    self.evict(repo_path);
+  }
+
+  // For values that are added to a bit at a time. Inserts the default if missing.
+  pub fn update<T>(&mut self, repo_path: &str, f: impl FnOnce(&mut V) -> T) -> T
+  where
+    V: Default,
+  {
+    let tick = self.next_tick();
+
+    let entry = self
+      .entries
+      .entry(repo_path.to_string())
+      .or_insert_with(|| CacheEntry {
+        value: V::default(),
+        size: 0,
+        last_used: AtomicU64::new(tick),
+      });
+
+    let result = f(&mut entry.value);
+
+    let size = entry.value.cache_size();
+    self.total_bytes = self.total_bytes - entry.size + size;
+    entry.size = size;
+    entry.last_used.store(tick, Ordering::Relaxed);
+
+    self.evict(repo_path);
+
+    result
+  }
+
+  pub fn invalidate(&mut self, repo_path: &str) {
+    if let Some(old) = self.entries.remove(repo_path) {
+      self.total_bytes -= old.size;
+      self.invalidations += 1;
+    }
+  }
+
+  pub fn clear(&mut self) {
+    self.invalidations += self.entries.len() as u64;
+    self.entries.clear();
+    self.total_bytes = 0;
+  }
+
+  pub fn stats(&self) -> CacheStats {
+    CacheStats {
+      name: self.name.to_string(),
+      num_repos: self.entries.len(),
+      bytes: self.total_bytes,
+      max_bytes: self.max_bytes,
+      hits: self.hits.load(Ordering::Relaxed),
+      misses: self.misses.load(Ordering::Relaxed),
+      evictions: self.evictions,
+      invalidations: self.invalidations,
+    }
+  }
+
+  fn next_tick(&self) -> u64 {
+    self.tick.fetch_add(1, Ordering::Relaxed)
+  }
+
+  fn evict(&mut self, keep_repo: &str) {
+    while self.total_bytes > self.max_bytes {
+      let oldest = self
+        .entries
+        .iter()
+        .filter(|(repo_path, _)| repo_path.as_str() != keep_repo)
+        .min_by_key(|(_, entry)| entry.last_used.load(Ordering::Relaxed))
+        .map(|(repo_path, _)| repo_path.clone());
+
+      let Some(oldest) = oldest else {
+        break;
+      };
+
+      if let Some(old) = self.entries.remove(&oldest) {
+        dprintln!("Evicted {} from {} cache", oldest, self.name);
+
+        self.total_bytes -= old.size;
+        self.evictions += 1;
+      }
+    }
+  }
+}
+
+impl<V: CacheSize + Clone> Global<RepoCache<V>> {
+  pub fn get_repo(&self, repo_path: &str) -> Option<V> {
+    self.data.read().ok()?.get(repo_path).cloned()
+  }
+
+  // Like get_repo without the clone.
+  pub fn with_repo<T>(&self, repo_path: &str, f: impl FnOnce(&V) -> T) -> Option<T> {
+    Some(f(self.data.read().ok()?.get(repo_path)?))
+  }
+
+  pub fn insert_repo(&self, repo_path: &str, value: V) {
+    if let Ok(mut cache) = self.data.write() {
+      cache.insert(repo_path, value);
+    }
+  }
+
+  pub fn update_repo<T>(&self, repo_path: &str, f: impl FnOnce(&mut V) -> T) -> Option<T>
+  where
+    V: Default,
+  {
+    Some(self.data.write().ok()?.update(repo_path, f))
+  }
+
+  pub fn invalidate(&self, repo_path: &str) {
+    if let Ok(mut cache) = self.data.write() {
+      cache.invalidate(repo_path);
+    }
+  }
+
+  pub fn clear_repos(&self) {
+    if let Ok(mut cache) = self.data.write() {
+      cache.clear();
+    }
+  }
+
+  pub fn stats(&self) -> Option<CacheStats> {
+    Some(self.data.read().ok()?.stats())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::util::repo_cache::{CacheSize, RepoCache};
+
+  #[derive(Clone, Default)]
+  struct Sized(usize);
+
+  impl CacheSize for Sized {
+    fn cache_size(&self) -> usize {
+      self.0
+    }
+  }
+
+  #[test]
+  fn test_evicts_least_recently_used() {
+    let mut cache = RepoCache::new("test", 100);
+
+    cache.insert("a", Sized(40));
+    cache.insert("b", Sized(40));
+    assert!(cache.get("a").is_some());
+
+    cache.insert("c", Sized(40));
+
+    assert!(cache.get("a").is_some());
+    assert!(cache.get("b").is_none());
+    assert!(cache.get("c").is_some());
+
+    let stats = cache.stats();
+    assert_eq!(stats.bytes, 80);
+    assert_eq!(stats.evictions, 1);
+    assert_eq!(stats.hits, 3);
+    assert_eq!(stats.misses, 1);
+  }
+
+  #[test]
+  fn test_keeps_oversized_latest() {
+    let mut cache = RepoCache::new("test", 100);
+
+    cache.insert("a", Sized(40));
+    cache.insert("b", Sized(400));
+
+    assert!(cache.get("a").is_none());
+    assert!(cache.get("b").is_some());
+  }
+
+  #[test]
+  fn test_update_and_invalidate() {
+    let mut cache = RepoCache::new("test", 100);
+
+    cache.update("a", |v: &mut Sized| v.0 += 10);
+    cache.update("a", |v| v.0 += 10);
+    assert_eq!(cache.stats().bytes, 20);
+
+    cache.invalidate("a");
+
+    let stats = cache.stats();
+    assert_eq!(stats.bytes, 0);
+    assert_eq!(stats.num_repos, 0);
+    assert_eq!(stats.invalidations, 1);
+  }
+}
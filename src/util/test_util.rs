@@ -0,0 +1,107 @@
+use std::env::temp_dir;
+use std::fs::{create_dir_all, remove_dir_all, write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::process::Command;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::git::git_settings::GIT_PATH;
+use crate::git::run_git::{run_git_err, RunGitOptions};
+
+// Commit ids for tests that only need something that looks like one.
+pub const ID_A: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+pub const ID_B: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+pub const ID_C: &str = "cccccccccccccccccccccccccccccccccccccccc";
+
+static NEXT_DIR: AtomicU32 = AtomicU32::new(0);
+
+// A dir under temp_dir() that's unique to this test run. Removed on drop.
+pub struct TempDir {
+  pub path: PathBuf,
+}
+
+impl TempDir {
+  pub fn new(name: &str) -> Self {
+    let path = temp_dir().join(format!(
+      "gitfiend_test_{}_{}_{}",
+      name,
+      process::id(),
+      NEXT_DIR.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = remove_dir_all(&path);
+    create_dir_all(&path).unwrap();
+
+    Self { path }
+  }
+
+  // An empty ".git" dir, for code that reads git's files without running git.
+  pub fn with_git_dir(name: &str) -> (Self, PathBuf) {
+    let dir = Self::new(name);
+    let git_path = dir.path.join(".git");
+    create_dir_all(&git_path).unwrap();
+
+    (dir, git_path)
+  }
+
+  // A real repo with an identity set, so commits work anywhere.
+  pub fn with_repo(name: &str) -> Self {
+    let dir = Self::new(name);
+
+    for args in [
+      vec!["init", "-q", "-b", "main"],
+      vec!["config", "user.name", "Test"],
+      vec!["config", "user.email", "test@example.com"],
+    ] {
+      dir.git(&args);
+    }
+
+    dir
+  }
+
+  pub fn repo_path(&self) -> String {
+    self.path.to_string_lossy().to_string()
+  }
+
+  pub fn git(&self, args: &[&str]) -> String {
+    run_git_err(RunGitOptions {
+      repo_path: &self.repo_path(),
+      args,
+    })
+    .unwrap()
+    .stdout
+  }
+
+  // Commits a change to a file named after the message. time is in seconds, for both
+  // the author and committer dates.
+  pub fn commit(&self, message: &str, time: u64) {
+    write_file(&self.path.join(message), message);
+    self.git(&["add", "-A"]);
+
+    let date = format!("{} +0000", time);
+    let out = Command::new(GIT_PATH.as_path())
+      .args(["commit", "-q", "-m", message])
+      .env("GIT_AUTHOR_DATE", &date)
+      .env("GIT_COMMITTER_DATE", &date)
+      .current_dir(&self.path)
+      .output()
+      .unwrap();
+
+    assert!(
+      out.status.success(),
+      "{}",
+      String::from_utf8_lossy(&out.stderr)
+    );
+  }
+}
+
+impl Drop for TempDir {
+  fn drop(&mut self) {
+    let _ = remove_dir_all(&self.path);
+  }
+}
+
+// Creates parent dirs as needed.
+pub fn write_file(path: &Path, text: &str) {
+  create_dir_all(path.parent().unwrap()).unwrap();
+  write(path, text).unwrap();
+}